@@ -0,0 +1,80 @@
+//! `cargo bench` runs every bundled ROM under each engine, then the sprite
+//! drawing path on its own. Numbers are printed rather than compared, so
+//! check them against a run on the previous commit.
+
+use std::fs;
+use std::hint::black_box;
+use std::time::Instant;
+
+use chip_8_emulator::bench::{self, BenchConfig, CountingAllocator};
+use chip_8_emulator::display::Display;
+use chip_8_emulator::memory::Memory;
+use chip_8_emulator::recompiler::Engine;
+use chip_8_emulator::script::Script;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const FRAMES: u32 = 3000;
+
+const SPRITES: u32 = 1_000_000;
+
+fn main() {
+    let input = Script::parse(bench::DEFAULT_INPUT).unwrap();
+    let mut roms: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/data"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_none())
+        .collect();
+    roms.sort();
+
+    let configs = [
+        ("uncached", Engine::Interpreter, false),
+        ("cached", Engine::Interpreter, true),
+        ("compiled", Engine::Recompiler, true),
+    ];
+    for (name, engine, decode_cache) in configs {
+        let config = BenchConfig {
+            frames: FRAMES,
+            engine,
+            decode_cache,
+            ..BenchConfig::default()
+        };
+        let (mut instructions, mut elapsed, mut allocations) = (0, 0.0, 0);
+        for path in roms.iter() {
+            let result = bench::run(&fs::read(path).unwrap(), &input, config).unwrap();
+            instructions += result.instructions;
+            elapsed += result.elapsed.as_secs_f64();
+            allocations += result.allocations;
+        }
+        println!(
+            "roms/{:<10} {:>14.0} instructions/s {:>8} allocations",
+            name,
+            instructions as f64 / elapsed,
+            allocations
+        );
+    }
+
+    draw_sprites();
+}
+
+// Draw the 8x15 sprite at 0x200 all over the screen
+fn draw_sprites() {
+    let mut memory = Memory::new();
+    memory.load_program(&[0xAA, 0x55].repeat(8), 0x200);
+    let mut display = Display::new();
+
+    let allocations = bench::allocations();
+    let start = Instant::now();
+    for sprite in 0..SPRITES {
+        let (x, y) = ((sprite * 7) as u8, (sprite * 3) as u8);
+        let rows = (0x200..0x20F).map(|address| memory.read_byte(address));
+        black_box(display.draw_sprite(x, y, rows, false, None));
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "draw/sprite   {:>14.0} sprites/s {:>8} allocations",
+        SPRITES as f64 / elapsed,
+        bench::allocations() - allocations
+    );
+}
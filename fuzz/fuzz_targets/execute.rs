@@ -0,0 +1,71 @@
+//! Run one instruction from an arbitrary machine state.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip_8_emulator::cpu::STACK_SIZE;
+use chip_8_emulator::{Chip8, Engine, Platform, Rng};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Machine {
+    opcode: u16,
+    pc: u16,
+    i: u16,
+    registers: [u8; 16],
+    stack_depth: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+    keys: u16,
+    memory: Vec<(u16, u8)>,
+    vip: bool,
+    recompiler: bool,
+}
+
+fuzz_target!(|machine: Machine| {
+    let engine = if machine.recompiler {
+        Engine::Recompiler
+    } else {
+        Engine::Interpreter
+    };
+    let mut chip8 = Chip8::builder().engine(engine).rng(Rng::new(0)).build();
+
+    // 0000: 2000 calls itself, so each step pushes a return address
+    chip8.memory_mut().write_byte(0, 0x20);
+    chip8.memory_mut().write_byte(1, 0x00);
+    chip8.cpu_mut().write_pc(0);
+    for _ in 0..machine.stack_depth as usize % (STACK_SIZE + 1) {
+        assert!(chip8.step());
+    }
+    if machine.vip {
+        chip8.set_platform(Platform::CosmacVip);
+    }
+
+    let len = chip8.memory().len();
+    for &(address, value) in machine.memory.iter() {
+        chip8.memory_mut().write_byte(address as usize % len, value);
+    }
+    // A pc with no room for an opcode is left for the CPU to catch
+    let pc = machine.pc as usize;
+    if pc + 1 < len {
+        let [high, low] = machine.opcode.to_be_bytes();
+        chip8.memory_mut().write_byte(pc, high);
+        chip8.memory_mut().write_byte(pc + 1, low);
+    }
+
+    let cpu = chip8.cpu_mut();
+    cpu.write_pc(machine.pc);
+    cpu.write_i(machine.i);
+    for (x, &value) in machine.registers.iter().enumerate() {
+        cpu.write_register(x, value);
+    }
+    cpu.write_delay_timer(machine.delay_timer);
+    cpu.write_sound_timer(machine.sound_timer);
+    for key in 0..16 {
+        chip8.set_key(key, machine.keys & 1 << key != 0).unwrap();
+    }
+
+    if !chip8.step_engine() {
+        assert!(chip8.fault().is_some(), "stopped without a fault");
+    }
+});
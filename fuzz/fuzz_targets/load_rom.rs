@@ -0,0 +1,39 @@
+//! Load arbitrary bytes as a ROM and run it for a second. The first byte
+//! picks the platform, the engine and a key to hold down.
+
+#![no_main]
+
+use chip_8_emulator::{Chip8, Engine, Platform, Rng, Timing};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&config, rom)) = data.split_first() else {
+        return;
+    };
+    let (platform, timing) = if config & 1 != 0 {
+        (Platform::CosmacVip, Timing::CosmacVip)
+    } else {
+        (Platform::Chip8, Timing::Instructions)
+    };
+    let engine = if config & 2 != 0 {
+        Engine::Recompiler
+    } else {
+        Engine::Interpreter
+    };
+    let mut chip8 = Chip8::builder()
+        .platform(platform)
+        .timing(timing)
+        .engine(engine)
+        .rng(Rng::new(0))
+        .build();
+    if chip8.load_program(rom).is_err() {
+        return;
+    }
+    chip8.set_key(config >> 4, true).unwrap();
+    for _ in 0..60 {
+        if !chip8.run_frame() {
+            assert!(chip8.fault().is_some(), "stopped without a fault");
+            break;
+        }
+    }
+});
@@ -0,0 +1,26 @@
+//! Load arbitrary bytes as a save state, both as given and sealed with a
+//! valid header and checksum so the payload parser is reached, then run
+//! whatever loads.
+
+#![no_main]
+
+use chip_8_emulator::state::StateWriter;
+use chip_8_emulator::{Chip8, Rng};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut sealed = StateWriter::new();
+    sealed.write_bytes(data);
+    for state in [data.to_vec(), sealed.finish()] {
+        let mut chip8 = Chip8::builder().rng(Rng::new(0)).build();
+        if chip8.load_state(&state).is_err() {
+            continue;
+        }
+        for _ in 0..10 {
+            if !chip8.run_frame() {
+                assert!(chip8.fault().is_some(), "stopped without a fault");
+                break;
+            }
+        }
+    }
+});
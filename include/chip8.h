@@ -0,0 +1,173 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated from src/ffi.rs by cbindgen; do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Pixels per row of the framebuffer
+ */
+#define CHIP8_DISPLAY_WIDTH 64
+
+/**
+ * Rows in the framebuffer
+ */
+#define CHIP8_DISPLAY_HEIGHT 32
+
+/**
+ * Registers V0 to VF
+ */
+#define CHIP8_REGISTER_COUNT 16
+
+/**
+ * Return addresses the stack holds
+ */
+#define CHIP8_STACK_SIZE 16
+
+/**
+ * What went wrong. Mirrors `Chip8Error` and `StateError`, plus the ways a
+ * C caller can misuse the API.
+ */
+typedef enum {
+  CHIP8_OK = 0,
+  /**
+   * A required pointer was null
+   */
+  CHIP8_ERROR_NULL,
+  /**
+   * The ROM doesn't fit between the program start and the end of memory
+   */
+  CHIP8_ERROR_ROM_TOO_LARGE,
+  /**
+   * Keypad keys are 0-F
+   */
+  CHIP8_ERROR_INVALID_KEY,
+  /**
+   * Registers are V0-VF
+   */
+  CHIP8_ERROR_INVALID_REGISTER,
+  /**
+   * The buffer can't hold the save state; its size has been written back
+   */
+  CHIP8_ERROR_BUFFER_TOO_SMALL,
+  CHIP8_ERROR_STATE_BAD_MAGIC,
+  CHIP8_ERROR_STATE_UNSUPPORTED_VERSION,
+  CHIP8_ERROR_STATE_BAD_CHECKSUM,
+  CHIP8_ERROR_STATE_TRUNCATED,
+  CHIP8_ERROR_STATE_INVALID,
+  /**
+   * The return stack holds at most `CHIP8_STACK_SIZE` addresses
+   */
+  CHIP8_ERROR_INVALID_STACK_DEPTH,
+} chip8_error_t;
+
+/**
+ * A machine, from `chip8_create`
+ */
+typedef struct chip8_t chip8_t;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A description of the error, as a static string
+ */
+const char *chip8_error_message(chip8_error_t error);
+
+/**
+ * A new machine with its RNG seeded from `seed` and tracing off. Free it
+ * with `chip8_destroy`.
+ */
+chip8_t *chip8_create(uint64_t seed);
+
+/**
+ * Free a machine. Null is ignored.
+ */
+void chip8_destroy(chip8_t *chip8);
+
+chip8_error_t chip8_load_rom(chip8_t *chip8, const uint8_t *rom, size_t len);
+
+/**
+ * Run one instruction. Returns false if the program stopped.
+ */
+bool chip8_step(chip8_t *chip8);
+
+/**
+ * Run one 60 Hz frame. Returns false if the program stopped.
+ */
+bool chip8_run_frame(chip8_t *chip8);
+
+/**
+ * The `CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT` pixels, one byte (0 or 1)
+ * each, row by row. The pointer stays valid for the life of the machine.
+ */
+const uint8_t *chip8_framebuffer(const chip8_t *chip8);
+
+chip8_error_t chip8_set_key(chip8_t *chip8, uint8_t key, bool pressed);
+
+/**
+ * Read VX into `value`
+ */
+chip8_error_t chip8_get_register(const chip8_t *chip8, uint8_t x, uint8_t *value);
+
+chip8_error_t chip8_set_register(chip8_t *chip8, uint8_t x, uint8_t value);
+
+/**
+ * The program counter, or 0 for a null machine
+ */
+uint16_t chip8_get_pc(const chip8_t *chip8);
+
+chip8_error_t chip8_set_pc(chip8_t *chip8, uint16_t pc);
+
+/**
+ * The I register, or 0 for a null machine
+ */
+uint16_t chip8_get_index(const chip8_t *chip8);
+
+chip8_error_t chip8_set_index(chip8_t *chip8, uint16_t index);
+
+/**
+ * The depth of the return stack, or 0 for a null machine
+ */
+uint8_t chip8_get_sp(const chip8_t *chip8);
+
+/**
+ * Set the depth of the return stack; the addresses on it are kept
+ */
+chip8_error_t chip8_set_sp(chip8_t *chip8, uint8_t sp);
+
+/**
+ * The delay timer, or 0 for a null machine
+ */
+uint8_t chip8_get_delay_timer(const chip8_t *chip8);
+
+chip8_error_t chip8_set_delay_timer(chip8_t *chip8, uint8_t value);
+
+/**
+ * The sound timer, or 0 for a null machine
+ */
+uint8_t chip8_get_sound_timer(const chip8_t *chip8);
+
+chip8_error_t chip8_set_sound_timer(chip8_t *chip8, uint8_t value);
+
+/**
+ * Save state into `buffer`, which holds `*len` bytes. `*len` is set to the
+ * size of the state; if the buffer is null or too small nothing is copied
+ * and `CHIP8_ERROR_BUFFER_TOO_SMALL` is returned, so callers can ask for
+ * the size first.
+ */
+chip8_error_t chip8_save_state(const chip8_t *chip8, uint8_t *buffer, size_t *len);
+
+chip8_error_t chip8_load_state(chip8_t *chip8, const uint8_t *state, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,135 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::chip8::{Chip8, Chip8Error, DEFAULT_INSTRUCTIONS_PER_FRAME};
+use crate::recompiler::Engine;
+use crate::rng::Rng;
+use crate::script::Script;
+
+/// Seed every benchmark run uses, so runs take the same path through a ROM
+pub const SEED: u64 = 0;
+
+/// Input played over and over during a benchmark: a few of the keys games
+/// commonly use, so ROMs get past their title screens and into their main
+/// loops
+pub const DEFAULT_INPUT: &str = "wait 30\n\
+                                 hold 5 for 10\n\
+                                 wait 20\n\
+                                 hold 4 for 15\n\
+                                 hold 6 for 15\n\
+                                 hold 1 for 10\n\
+                                 hold C for 10\n\
+                                 hold 8 for 10\n\
+                                 hold 2 for 10\n\
+                                 wait 20\n";
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The system allocator, counting allocations. Install it with
+/// `#[global_allocator]` in a benchmark binary to get allocation counts in
+/// `BenchResult`; without it they read as zero.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Allocations made so far through `CountingAllocator`
+pub fn allocations() -> u64 {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// How fast a ROM ran headless
+pub struct BenchResult {
+    pub frames: u32,
+    pub instructions: u64,
+    pub elapsed: Duration,
+    /// Allocations made while running, not counting setup
+    pub allocations: u64,
+}
+
+impl BenchResult {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// Settings for a benchmark run
+#[derive(Clone, Copy, Debug)]
+pub struct BenchConfig {
+    pub frames: u32,
+    pub instructions_per_frame: u16,
+    pub engine: Engine,
+    pub decode_cache: bool,
+}
+
+impl Default for BenchConfig {
+    fn default() -> BenchConfig {
+        BenchConfig {
+            frames: 3000,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            engine: Engine::Interpreter,
+            decode_cache: true,
+        }
+    }
+}
+
+/// Run a ROM for `frames` frames as fast as possible, without the
+/// instruction trace, playing `input` in a loop, and time it. Stops early
+/// if the program runs off the end of memory or the input script fails.
+pub fn run(rom: &[u8], input: &Script, config: BenchConfig) -> Result<BenchResult, Chip8Error> {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(rom)?;
+    chip8.set_instructions_per_frame(config.instructions_per_frame);
+    chip8.set_rng(Rng::new(SEED));
+    chip8.set_trace(false);
+    chip8.set_decode_cache(config.decode_cache);
+    chip8.set_engine(config.engine);
+
+    let allocations_before = allocations();
+    let instructions_before = chip8.instructions_run();
+    let start = Instant::now();
+    let mut frames = 0;
+    while frames < config.frames {
+        match input.run_for(&mut chip8, config.frames - frames) {
+            Ok(0) => {
+                // The input never waits, so just run the frames
+                let remaining = config.frames - frames;
+                frames += (0..remaining).take_while(|_| chip8.run_frame()).count() as u32;
+                break;
+            }
+            Ok(ran) => frames += ran,
+            Err(_) => break,
+        }
+    }
+    let elapsed = start.elapsed();
+
+    Ok(BenchResult {
+        frames,
+        instructions: chip8.instructions_run() - instructions_before,
+        elapsed,
+        allocations: allocations() - allocations_before,
+    })
+}
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+use chip_8_emulator::bench::{self, BenchConfig, CountingAllocator};
+use chip_8_emulator::recompiler::Engine;
+use chip_8_emulator::script::Script;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const USAGE: &str = "usage: chip8-bench [--frames <n>] [--ipf <n>] \
+                     [--engine <interpreter|recompiler>] [--no-decode-cache] \
+                     [--input <script>] [rom...]";
+
+fn main() {
+    let mut config = BenchConfig::default();
+    let mut input = None;
+    let mut roms = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => config.frames = parse_arg(args.next()),
+            "--ipf" => config.instructions_per_frame = parse_arg(args.next()),
+            "--engine" => {
+                config.engine = Engine::parse(&args.next().unwrap_or_default())
+                    .unwrap_or_else(|message| exit(&message))
+            }
+            "--no-decode-cache" => config.decode_cache = false,
+            "--input" => input = Some(args.next().unwrap_or_else(|| exit(USAGE))),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => roms.push(PathBuf::from(arg)),
+        }
+    }
+
+    let input = match input {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path, e))
+            .and_then(|text| Script::parse(&text)),
+        None => Script::parse(bench::DEFAULT_INPUT),
+    }
+    .unwrap_or_else(|message| exit(&message));
+
+    if roms.is_empty() {
+        roms = bundled_roms();
+    }
+
+    println!(
+        "{:<10} {:>8} {:>14} {:>10} {:>10}",
+        "ROM", "frames", "IPS", "FPS", "allocs"
+    );
+    for path in roms {
+        let rom = fs::read(&path)
+            .unwrap_or_else(|e| exit(&format!("could not read {}: {}", path.display(), e)));
+        let result = bench::run(&rom, &input, config)
+            .unwrap_or_else(|e| exit(&format!("{}: {}", path.display(), e)));
+        println!(
+            "{:<10} {:>8} {:>14.0} {:>10.0} {:>10}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            result.frames,
+            result.instructions_per_second(),
+            result.frames_per_second(),
+            result.allocations
+        );
+    }
+}
+
+// Every ROM in data/, in name order
+fn bundled_roms() -> Vec<PathBuf> {
+    let mut roms: Vec<_> = fs::read_dir("data")
+        .unwrap_or_else(|e| exit(&format!("could not read data/: {}", e)))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_none())
+        .collect();
+    roms.sort();
+    roms
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<String>) -> T {
+    arg.and_then(|arg| arg.parse().ok())
+        .unwrap_or_else(|| exit(USAGE))
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -1,24 +1,506 @@
-use crate::cpu::{Cpu, PROGRAM_START};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use crate::cpu::{Cpu, Fault, PROGRAM_START};
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use crate::memory::Memory;
+use crate::platform::{Platform, Quirks};
+use crate::recompiler::Engine;
+#[cfg(feature = "alloc")]
+use crate::recompiler::Recompiler;
+use crate::rng::{RandomSource, Rng};
+#[cfg(feature = "alloc")]
+use crate::state::StateWriter;
+use crate::state::{StateError, StateReader};
+use crate::timing::{self, Timing};
+use crate::trace::Trace;
+
+/// Instructions run per 60 Hz frame unless configured otherwise, about
+/// 1000 per second
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u16 = 16;
+
+/// Length of every save state `save_state` writes, whatever the machine is
+/// doing: the header, the CPU and display, memory, the RNG, the platform and
+/// the checksum
+pub const STATE_SIZE: usize = 10 + 316 + 4096 + 9 + 1 + 4;
+
+/// The framebuffer, one byte (0 or 1) per pixel, row by row
+pub type Framebuffer = [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Chip8Error {
+    /// The ROM doesn't fit between the program start and the end of memory
+    RomTooLarge { size: usize, max: usize },
+    /// Keypad keys are 0-F
+    InvalidKey(u8),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} fit in memory", size, max)
+            }
+            Chip8Error::InvalidKey(key) => write!(f, "no keypad key {:X}", key),
+        }
+    }
+}
 
-pub struct Chip8 {
+impl Error for Chip8Error {}
+
+/// The whole machine. CXNN draws from `R`, the built-in `Rng` unless the
+/// host supplies its own.
+pub struct Chip8<R = Rng> {
     memory: Memory,
     cpu: Cpu,
+    rng: R,
+    platform: Platform,
+    instructions_per_frame: u16,
+    timing: Timing,
+    engine: Engine,
+    #[cfg(feature = "alloc")]
+    recompiler: Recompiler,
+    // VIP machine cycles left over from the last frame, negative when its
+    // last instruction ran past the end of the frame
+    cycles: i32,
+    instructions_run: u64,
+}
+
+#[cfg(feature = "std")]
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new()
+    }
 }
 
 impl Chip8 {
+    /// A machine with the random number generator seeded from the clock
+    #[cfg(feature = "std")]
     pub fn new() -> Chip8 {
-        Self {
+        Chip8::with_rng(Rng::from_time())
+    }
+
+    /// Configure a machine before creating it
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+
+    /// Snapshot the whole machine into the versioned save state format
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        self.cpu.save_state(&mut writer);
+        writer.write_bytes(self.memory.bytes());
+        self.rng.save_state(&mut writer);
+        self.platform.save_state(&mut writer);
+        writer.finish()
+    }
+
+    /// Restore a snapshot taken with `save_state`, platform and quirks
+    /// included. The machine is left untouched if the state is invalid.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader::open(state)?;
+        let mut cpu = Cpu::from_state(&mut reader)?;
+        let memory = reader.read_array()?;
+        // Version 1 states predate the owned RNG
+        let rng = match reader.version() {
+            1 => self.rng.clone(),
+            _ => Rng::from_state(&mut reader)?,
+        };
+        // Versions before 4 predate the platform and quirks
+        let platform = match reader.version() {
+            1..=3 => {
+                cpu.set_quirks(self.cpu.quirks());
+                self.platform
+            }
+            _ => Platform::from_state(&mut reader)?,
+        };
+        reader.finish()?;
+
+        cpu.set_tracer(self.cpu.tracer());
+        self.cpu = cpu;
+        self.memory.restore(&memory);
+        self.rng = rng;
+        self.platform = platform;
+        // Nothing carries over from before the state: no VIP cycles owed,
+        // no compiled blocks
+        self.cycles = 0;
+        #[cfg(feature = "alloc")]
+        {
+            self.recompiler = Recompiler::new();
+        }
+        Ok(())
+    }
+}
+
+impl<R: RandomSource> Chip8<R> {
+    pub fn with_rng(rng: R) -> Chip8<R> {
+        Chip8 {
             memory: Memory::new(),
             cpu: Cpu::new(),
+            rng,
+            platform: Platform::Chip8,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            timing: Timing::Instructions,
+            engine: Engine::Interpreter,
+            #[cfg(feature = "alloc")]
+            recompiler: Recompiler::new(),
+            cycles: 0,
+            instructions_run: 0,
         }
     }
 
-    pub fn load_program(&mut self, program: &Vec<u8>) {
+    /// Copy a ROM into memory at the program start
+    pub fn load_program(&mut self, program: &[u8]) -> Result<(), Chip8Error> {
+        let max = self.memory.len() - PROGRAM_START as usize;
+        if program.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: program.len(),
+                max,
+            });
+        }
         self.memory.load_program(program, PROGRAM_START as usize);
+        Ok(())
+    }
+
+    /// Run one instruction. Returns false if the program stopped.
+    pub fn step(&mut self) -> bool {
+        self.instructions_run += 1;
+        self.rng.on_fetch();
+        self.cpu.decode_and_execute(&mut self.memory, &mut self.rng)
+    }
+
+    /// Run one instruction on the machine's engine, where `step` always
+    /// interprets. Returns false if the program stopped.
+    pub fn step_engine(&mut self) -> bool {
+        #[cfg(feature = "alloc")]
+        if self.engine == Engine::Recompiler
+            && self.cpu.tracer().is_none()
+            && self.memory.watchpoints().is_empty()
+        {
+            let (ran, running) =
+                self.recompiler
+                    .run(&mut self.cpu, &mut self.memory, &mut self.rng, 1);
+            self.instructions_run += ran as u64;
+            return match ran {
+                _ if !running => false,
+                // Off the end of memory; let the CPU report it
+                0 if !self.cpu.waiting_for_vblank() => self.step(),
+                _ => true,
+            };
+        }
+        self.step()
+    }
+
+    /// Run one 60 Hz frame, then tick the timers. How many instructions
+    /// that is depends on the timing model; a draw waiting for the vertical
+    /// blank ends the frame early. Returns false if the program stopped.
+    pub fn run_frame(&mut self) -> bool {
+        let running = match self.timing {
+            Timing::Instructions => self.run_instructions(),
+            Timing::CosmacVip => self.run_vip_cycles(),
+        };
+        if running {
+            self.tick_timers();
+        }
+        running
+    }
+
+    fn run_instructions(&mut self) -> bool {
+        #[cfg(feature = "alloc")]
+        if self.engine == Engine::Recompiler
+            && self.cpu.tracer().is_none()
+            && self.memory.watchpoints().is_empty()
+        {
+            return self.run_compiled();
+        }
+
+        for _ in 0..self.instructions_per_frame {
+            if self.cpu.waiting_for_vblank() {
+                break;
+            }
+            if !self.step() {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[cfg(feature = "alloc")]
+    fn run_compiled(&mut self) -> bool {
+        let budget = self.instructions_per_frame as usize;
+        let (ran, running) =
+            self.recompiler
+                .run(&mut self.cpu, &mut self.memory, &mut self.rng, budget);
+        self.instructions_run += ran as u64;
+        match ran {
+            _ if !running => false,
+            // Off the end of memory; let the CPU report it
+            ran if ran < budget && !self.cpu.waiting_for_vblank() => self.step(),
+            _ => true,
+        }
+    }
+
+    /// Spend the cycles of one VIP frame that the display interrupt leaves,
+    /// carrying any overrun into the next frame
+    fn run_vip_cycles(&mut self) -> bool {
+        self.cycles += (timing::VIP_CYCLES_PER_FRAME - timing::VIP_DISPLAY_INTERRUPT_CYCLES) as i32;
+        while self.cycles > 0 {
+            let pc = self.cpu.read_pc();
+            if pc as usize + 1 >= self.memory.len() {
+                // Let the CPU report it
+                return self.step();
+            }
+            let (_, instruction) = self.memory.fetch_instruction(pc as usize);
+            let cycles = timing::vip_cycles(instruction, &self.cpu);
+            if !self.step() {
+                return false;
+            }
+            self.cycles -= cycles as i32;
+            if self.cpu.waiting_for_vblank() {
+                // The rest of the frame is spent waiting
+                self.cycles = 0;
+                break;
+            }
+        }
+        true
+    }
+
+    /// Instructions run since the machine was created, on either engine
+    pub fn instructions_run(&self) -> u64 {
+        self.instructions_run
+    }
+
+    pub fn instructions_per_frame(&self) -> u16 {
+        self.instructions_per_frame
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions: u16) {
+        self.instructions_per_frame = instructions;
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.cycles = 0;
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Choose how instructions are run. The recompiler only speeds up whole
+    /// frames; single steps are always interpreted.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    /// Replace the random number generator used by CXNN, e.g. with a seeded one
+    pub fn set_rng(&mut self, rng: R) {
+        self.rng = rng;
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
     }
 
-    pub fn decode_and_execute(&mut self) -> bool {
-        self.cpu.decode_and_execute(&mut self.memory)
+    /// Behave like another interpreter, switching on its quirks
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.cpu.set_quirks(platform.quirks());
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.cpu.quirks()
+    }
+
+    /// Override individual quirks of the platform
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    /// Print every instruction to standard output as it runs (on by default)
+    #[cfg(feature = "std")]
+    pub fn set_trace(&mut self, trace: bool) {
+        self.set_tracer(trace.then_some(&crate::trace::Stdout));
+    }
+
+    /// Send the instruction trace somewhere, or turn it off with None
+    pub fn set_tracer(&mut self, tracer: Option<&'static dyn Trace>) {
+        self.cpu.set_tracer(tracer);
+    }
+
+    /// Cache decoded instructions (on by default)
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.memory.set_decode_cache(enabled);
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), Chip8Error> {
+        if key > 0xF {
+            return Err(Chip8Error::InvalidKey(key));
+        }
+        self.cpu.set_key(key as usize, pressed);
+        Ok(())
+    }
+
+    /// Pressed keys, bit N set for key N
+    pub fn keys(&self) -> u16 {
+        self.cpu.read_keys()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        self.cpu.display().pixels()
+    }
+
+    /// End of a 60 Hz frame: tick the timers and signal the vertical blank
+    pub fn tick_timers(&mut self) {
+        self.cpu.tick_timers();
+        self.cpu.vertical_blank();
+    }
+
+    /// Why the program stopped, once `step` or `run_frame` has returned
+    /// false
+    pub fn fault(&self) -> Option<Fault> {
+        self.cpu.fault()
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+}
+
+/// Settings for a new `Chip8`. Unlike `Chip8::new`, machines built this
+/// way start with the instruction trace off.
+///
+/// ```
+/// use chip_8_emulator::{Chip8, Platform, Rng};
+///
+/// let mut quirks = Platform::CosmacVip.quirks();
+/// quirks.display_wait = false;
+/// let chip8 = Chip8::builder()
+///     .platform(Platform::CosmacVip)
+///     .quirks(quirks)
+///     .rng(Rng::new(1))
+///     .build();
+/// assert_eq!(chip8.platform(), Platform::CosmacVip);
+/// assert!(!chip8.quirks().display_wait);
+/// ```
+#[derive(Clone)]
+pub struct Chip8Builder {
+    platform: Platform,
+    quirks: Option<Quirks>,
+    instructions_per_frame: u16,
+    timing: Timing,
+    engine: Engine,
+    rng: Option<Rng>,
+    tracer: Option<&'static dyn Trace>,
+    decode_cache: bool,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+}
+
+impl Chip8Builder {
+    pub fn new() -> Chip8Builder {
+        Chip8Builder {
+            platform: Platform::Chip8,
+            quirks: None,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            timing: Timing::Instructions,
+            engine: Engine::Interpreter,
+            rng: None,
+            tracer: None,
+            decode_cache: true,
+        }
+    }
+
+    pub fn platform(mut self, platform: Platform) -> Chip8Builder {
+        self.platform = platform;
+        self
+    }
+
+    /// Quirks to use instead of the platform's
+    pub fn quirks(mut self, quirks: Quirks) -> Chip8Builder {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    pub fn instructions_per_frame(mut self, instructions: u16) -> Chip8Builder {
+        self.instructions_per_frame = instructions;
+        self
+    }
+
+    pub fn timing(mut self, timing: Timing) -> Chip8Builder {
+        self.timing = timing;
+        self
+    }
+
+    pub fn engine(mut self, engine: Engine) -> Chip8Builder {
+        self.engine = engine;
+        self
+    }
+
+    /// Random number generator for CXNN. If not set it is seeded from the
+    /// clock, or with 0 without `std`.
+    pub fn rng(mut self, rng: Rng) -> Chip8Builder {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// Print every instruction to standard output as it runs
+    #[cfg(feature = "std")]
+    pub fn trace(self, trace: bool) -> Chip8Builder {
+        self.tracer(trace.then_some(&crate::trace::Stdout))
+    }
+
+    pub fn tracer(mut self, tracer: Option<&'static dyn Trace>) -> Chip8Builder {
+        self.tracer = tracer;
+        self
+    }
+
+    pub fn decode_cache(mut self, enabled: bool) -> Chip8Builder {
+        self.decode_cache = enabled;
+        self
+    }
+
+    pub fn build(mut self) -> Chip8 {
+        #[cfg(feature = "std")]
+        let rng = self.rng.take().unwrap_or_else(Rng::from_time);
+        #[cfg(not(feature = "std"))]
+        let rng = self.rng.take().unwrap_or_else(|| Rng::new(0));
+        self.build_with_rng(rng)
+    }
+
+    /// Build a machine drawing its random numbers from the host's own
+    /// generator; any `rng` set on the builder is ignored
+    pub fn build_with_rng<R: RandomSource>(self, rng: R) -> Chip8<R> {
+        let mut chip8 = Chip8::with_rng(rng);
+        chip8.set_platform(self.platform);
+        if let Some(quirks) = self.quirks {
+            chip8.set_quirks(quirks);
+        }
+        chip8.set_instructions_per_frame(self.instructions_per_frame);
+        chip8.set_timing(self.timing);
+        chip8.set_engine(self.engine);
+        chip8.set_tracer(self.tracer);
+        chip8.set_decode_cache(self.decode_cache);
+        chip8
     }
 }
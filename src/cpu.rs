@@ -1,26 +1,178 @@
+use core::error::Error;
+use core::fmt;
+
+use crate::decode::Instruction;
+use crate::display::Display;
 use crate::memory::Memory;
+use crate::platform::{Platform, Quirks};
+use crate::rng::RandomSource;
+#[cfg(feature = "alloc")]
+use crate::state::StateWriter;
+use crate::state::{StateError, StateReader};
+use crate::trace::{self, Trace};
 
 pub const PROGRAM_START: u16 = 0x200;
 
+/// Deepest the return stack goes; a call beyond that stops the program
+pub const STACK_SIZE: usize = 16;
+
+// Write a line of the instruction trace, unless tracing is off
+macro_rules! trace {
+    ($cpu:expr, $($arg:tt)*) => {
+        if let Some(tracer) = $cpu.tracer {
+            tracer.write(format_args!("{}\n", format_args!($($arg)*)));
+        }
+    };
+}
+
+/// Why a program stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The program counter ran off the end of memory
+    PcOutOfBounds(u16),
+    /// 2NNN with the return stack full
+    StackOverflow { pc: u16, address: u16 },
+    /// 00EE with the return stack empty
+    StackUnderflow { pc: u16 },
+    /// FX33, FX55, FX65 or DXYN reached past the end of memory from I
+    MemoryOutOfBounds { pc: u16, address: u16 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::PcOutOfBounds(pc) => write!(f, "program counter out of bounds: 0x{:03X}", pc),
+            Fault::StackOverflow { pc, address } => write!(
+                f,
+                "stack overflow calling 0x{:03X} at 0x{:03X}",
+                address, pc
+            ),
+            Fault::StackUnderflow { pc } => {
+                write!(f, "stack underflow returning at 0x{:03X}", pc)
+            }
+            Fault::MemoryOutOfBounds { pc, address } => write!(
+                f,
+                "instruction at 0x{:03X} reached 0x{:03X}, past the end of memory",
+                pc, address
+            ),
+        }
+    }
+}
+
+impl Error for Fault {}
+
 pub struct Cpu {
     registers: [u8; 16],
-    return_stack: Vec<u16>,
+    return_stack: [u16; STACK_SIZE],
+    stack_depth: usize,
     pc: u16,
     i_register: u16,
-    display: [[u8; 64]; 32],
+    display: Display,
     keys: [bool; 16],
+    delay_timer: u8,
+    sound_timer: u8,
+    quirks: Quirks,
+    // DXYN is waiting for a vertical blank (display-wait quirk)
+    waiting_for_vblank: bool,
+    // A vertical blank came while DXYN was waiting, so it can draw
+    vblank: bool,
+    tracer: Option<&'static dyn Trace>,
+    fault: Option<Fault>,
+}
+
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
         Cpu {
             registers: [0; 16],
-            return_stack: Vec::new(),
+            return_stack: [0; STACK_SIZE],
+            stack_depth: 0,
             pc: PROGRAM_START,
             i_register: 0,
-            display: [[0; 64]; 32],
+            display: Display::new(),
             keys: [false; 16],
+            delay_timer: 0,
+            sound_timer: 0,
+            quirks: Platform::Chip8.quirks(),
+            waiting_for_vblank: false,
+            vblank: false,
+            tracer: trace::default_tracer(),
+            fault: None,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.registers);
+        writer.write_u16(self.pc);
+        writer.write_u16(self.i_register);
+        // The whole stack, so every state is the same size
+        writer.write_u16(self.stack_depth as u16);
+        for &address in self.return_stack.iter() {
+            writer.write_u16(address);
+        }
+        writer.write_u8(self.delay_timer);
+        writer.write_u8(self.sound_timer);
+        writer.write_u16(self.read_keys());
+        writer.write_u8(self.waiting_for_vblank as u8 | (self.vblank as u8) << 1);
+        self.display.save_state(writer);
+        self.quirks.save_state(writer);
+    }
+
+    pub fn from_state(reader: &mut StateReader) -> Result<Cpu, StateError> {
+        let registers = reader.read_array()?;
+        let pc = reader.read_u16()?;
+        let i_register = reader.read_u16()?;
+        let stack_depth = reader.read_u16()? as usize;
+        if stack_depth > STACK_SIZE {
+            return Err(StateError::Invalid("return stack too deep"));
+        }
+        // Versions before 5 only held the entries in use
+        let saved = match reader.version() {
+            1..=4 => stack_depth,
+            _ => STACK_SIZE,
+        };
+        let mut return_stack = [0; STACK_SIZE];
+        for address in return_stack[..saved].iter_mut() {
+            *address = reader.read_u16()?;
         }
+        let delay_timer = reader.read_u8()?;
+        let sound_timer = reader.read_u8()?;
+        let keys = reader.read_u16()?;
+        // Versions before 3 had no display wait
+        let vblank = match reader.version() {
+            1 | 2 => 0,
+            _ => reader.read_u8()?,
+        };
+        let display = Display::from_state(reader)?;
+        // Versions before 4 had no quirks; the machine loading the state
+        // keeps its own
+        let quirks = match reader.version() {
+            1..=3 => Platform::Chip8.quirks(),
+            _ => Quirks::from_state(reader)?,
+        };
+
+        Ok(Cpu {
+            registers,
+            return_stack,
+            stack_depth,
+            pc,
+            i_register,
+            display,
+            keys: core::array::from_fn(|k| keys & (1 << k) != 0),
+            delay_timer,
+            sound_timer,
+            quirks,
+            waiting_for_vblank: vblank & 1 != 0,
+            vblank: vblank & 2 != 0,
+            tracer: trace::default_tracer(),
+            fault: None,
+        })
     }
 
     // Read the program counter
@@ -41,417 +193,543 @@ impl Cpu {
         self.registers[register]
     }
 
+    /// V0 to VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn read_i(&self) -> u16 {
+        self.i_register
+    }
+
+    pub fn write_i(&mut self, value: u16) {
+        self.i_register = value;
+    }
+
+    // Depth of the return stack
+    pub fn read_sp(&self) -> usize {
+        self.stack_depth
+    }
+
+    /// Set the depth of the return stack, at most `STACK_SIZE`. The
+    /// addresses on it stay as they were.
+    pub fn write_sp(&mut self, depth: usize) {
+        self.stack_depth = depth.min(STACK_SIZE);
+    }
+
+    /// Return addresses on the stack, oldest first
+    pub fn stack(&self) -> &[u16] {
+        &self.return_stack[..self.stack_depth]
+    }
+
+    pub fn read_delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn read_sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn write_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn write_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    // Decrement both timers, called at 60Hz
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// Signal the vertical blank at the end of a frame, releasing a DXYN
+    /// that is waiting for it
+    pub fn vertical_blank(&mut self) {
+        self.vblank = self.waiting_for_vblank;
+    }
+
+    /// True while DXYN waits for the next vertical blank; nothing else
+    /// runs until then
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank && !self.vblank
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Why the program stopped, once an instruction has returned false
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    pub fn tracer(&self) -> Option<&'static dyn Trace> {
+        self.tracer
+    }
+
+    /// Trace every instruction as it runs, or stop tracing with None.
+    /// With `std` the trace goes to standard output by default.
+    pub fn set_tracer(&mut self, tracer: Option<&'static dyn Trace>) {
+        self.tracer = tracer;
+    }
+
     pub fn clear_display(&mut self) {
-        for row in self.display.iter_mut() {
-            row.fill(0);
-        }
+        self.display.clear();
     }
 
     pub fn set_key(&mut self, key: usize, pressed: bool) {
         self.keys[key] = pressed;
     }
 
-    pub fn draw_sprite(&mut self, vx: u8, vy: u8, height: u8, memory: &Memory) -> bool {
-        let x = vx as usize;
-        let y = vy as usize;
-        let mut collision = false;
-
-        for byte in 0..height {
-            let sprite = memory.read_byte(self.i_register as usize + byte as usize);
-            for bit in 0..8 {
-                let pixel = (sprite >> (7 - bit)) & 1;
-                if pixel == 1 {
-                    let display_x = (x + bit) % 64;
-                    let display_y = (y + byte as usize) % 32;
-                    if self.display[display_y][display_x] == 1 {
-                        collision = true;
-                    }
-                    self.display[display_y][display_x] ^= 1;
-                    print!("{} ", self.display[display_y][display_x]);
-                }
-            }
-            println!();
-        }
+    // Keypad state as a bitmask, bit k set while key k is pressed
+    pub fn read_keys(&self) -> u16 {
+        (0..16).fold(0, |keys, k| keys | ((self.keys[k] as u16) << k))
+    }
 
-        collision
+    pub fn display(&self) -> &Display {
+        &self.display
     }
 
-    pub fn decode_and_execute(&mut self, memory: &mut Memory) -> bool {
+    pub fn draw_sprite(&mut self, vx: u8, vy: u8, height: u8, clip: bool, memory: &Memory) -> bool {
+        let i = self.i_register as usize;
+        let rows = (0..height as usize).map(|row| memory.read_byte(i + row));
+        self.display.draw_sprite(vx, vy, rows, clip, self.tracer)
+    }
+
+    pub fn decode_and_execute<R: RandomSource + ?Sized>(
+        &mut self,
+        memory: &mut Memory,
+        rng: &mut R,
+    ) -> bool {
         // Ensure the program counter is within the bounds of memory
-        if (self.pc + 1) as usize >= memory.len() {
-            println!("Program counter out of bounds: {}", self.pc);
-            return false;
+        if self.pc as usize + 1 >= memory.len() {
+            return self.stop(Fault::PcOutOfBounds(self.pc));
         }
 
-        // Fetch the opcode from memory
-        let opcode = memory.fetch_opcode(self.pc as usize);
+        // Fetch the opcode from memory, decoded already if it ran before
+        let (opcode, instruction) = memory.fetch_instruction(self.pc as usize);
         if opcode != 0 {
-            println!("Fetched opcode: {:04X} at {}", opcode, self.pc);
+            trace!(self, "Fetched opcode: {:04X} at {}", opcode, self.pc);
         }
-        // Decode and execute the opcode
-        match opcode & 0xF000 {
-            0x0000 => {
-                match opcode & 0x00FF {
-                    0x00E0 => {
-                        // 00E0: Clear the display
-                        self.clear_display();
-                        self.pc += 2;
-                    }
-                    0x00EE => {
-                        // 00EE: Return from subroutine
-                        // if let Some(return_address) = stack.pop() {
-                        //     println!("Return from subroutine to 0x{:03X}", return_address);
-                        //     *pc = return_address;
-                        // } else {
-                        //     println!("Stack is empty. Cannot return from subroutine.");
-                        // }
-                        println!("Return from subroutine (ignored for now)");
-                    }
-                    _ => {
-                        let address = opcode & 0x0FFF;
-                        println!(
-                            "Call RCA 1802 program at 0x{:03X} (ignored for now)",
-                            address
-                        );
-                        // Ignoring this opcode for now
-                    }
+        if !self.execute(instruction, memory, rng) {
+            return false;
+        }
+
+        trace!(self, "Registers: {:?}", self.registers);
+        trace!(self, "Program counter: 0x{:03X}", self.pc);
+        trace!(self, "I register: 0x{:03X}", self.i_register);
+
+        true
+    }
+
+    /// Execute an already decoded instruction at the program counter.
+    /// Returns false if it stopped the program.
+    pub fn execute<R: RandomSource + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        memory: &mut Memory,
+        rng: &mut R,
+    ) -> bool {
+        match instruction {
+            Instruction::Return => {
+                // 00EE: Return from subroutine
+                if self.stack_depth == 0 {
+                    return self.stop(Fault::StackUnderflow { pc: self.pc });
                 }
+                self.stack_depth -= 1;
+                self.pc = self.return_stack[self.stack_depth];
+                trace!(self, "Return from subroutine to 0x{:03X}", self.pc);
+            }
+            Instruction::System(address) => {
+                trace!(
+                    self,
+                    "Call RCA 1802 program at 0x{:03X} (ignored for now)",
+                    address
+                );
+                // Ignoring this opcode for now
             }
-            0x1000 => {
+            Instruction::Jump(address) => {
                 // 1NNN: Jump to address NNN
-                let address = opcode & 0x0FFF;
-                println!("Jump to address 0x{:03X}", address);
+                trace!(self, "Jump to address 0x{:03X}", address);
                 // Set pc to address
                 self.pc = address; // Update the program counter
             }
-            0x2000 => {
+            Instruction::Call(address) => {
                 // Opcode 2NNN: call subroutine at NNN
-                let address = opcode & 0x0FFF;
-                println!("Call subroutine at 0x{:03X}", address);
+                trace!(self, "Call subroutine at 0x{:03X}", address);
                 // push pc + 2 to stack, then set pc = address
-                self.return_stack.push(self.pc + 2);
-                println!("Pushed 0x{:03X} to stack", self.pc);
+                if self.stack_depth == STACK_SIZE {
+                    let pc = self.pc;
+                    return self.stop(Fault::StackOverflow { pc, address });
+                }
+                self.return_stack[self.stack_depth] = self.pc + 2;
+                self.stack_depth += 1;
+                trace!(self, "Pushed 0x{:03X} to stack", self.pc);
 
                 // set pc to the subroutine address
                 self.pc = address;
             }
-            0x3000 => {
+            Instruction::SkipIfEqual(x, nn) => {
                 // Opcode 3XNN: skip if register X == NN
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = opcode & 0x00FF;
-                println!("Skip next instruction if V[{:X}] == 0x{:02X}", x, nn);
-
-                if self.registers[x] == nn as u8 {
-                    self.pc += 4; // Skip next instruction
+                trace!(self, "Skip next instruction if V[{:X}] == 0x{:02X}", x, nn);
+                self.skip_if(self.registers[x as usize] == nn);
+            }
+            Instruction::SkipIfNotEqual(x, nn) => {
+                trace!(self, "Skip next instruction if V[{:X}] != 0x{:02X}", x, nn);
+                self.skip_if(self.registers[x as usize] != nn);
+            }
+            Instruction::SkipIfRegistersEqual(x, y) => {
+                trace!(self, "Skip next instruction if V[{:X}] == V[{:X}]", x, y);
+                self.skip_if(self.registers[x as usize] == self.registers[y as usize]);
+            }
+            Instruction::SkipIfRegistersNotEqual(x, y) => {
+                // 9XY0: Skip next instruction if Vx != Vy
+                trace!(self, "Skip next instruction if V[{:X}] != V[{:X}]", x, y);
+                self.skip_if(self.registers[x as usize] != self.registers[y as usize]);
+            }
+            Instruction::JumpOffset(address) => {
+                // BNNN: Jump to address NNN + V0, or NNN + VX with the
+                // jump quirk
+                let x = if self.quirks.jump_with_vx {
+                    (address >> 8) as usize
                 } else {
-                    self.pc += 2; // Move to next instruction
-                }
+                    0
+                };
+                trace!(self, "Jump to address 0x{:03X} + V[{:X}]", address, x);
+                self.pc = address + self.registers[x] as u16;
             }
-            0x4000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = (opcode & 0x00FF) as u8;
-                println!("Skip next instruction if V[{:X}] != 0x{:02X}", x, nn);
-
-                if self.registers[x] != nn {
-                    self.pc += 4;
+            Instruction::Draw(x, y, height) => {
+                // DXYN: Draw a sprite at coordinate (VX, VY) with width 8 and height N
+                if self.quirks.display_wait && !self.vblank {
+                    // Wait for the vertical blank; pc stays put so this
+                    // runs again after it
+                    trace!(self, "Wait for vertical blank");
+                    self.waiting_for_vblank = true;
                 } else {
+                    if let Some(fault) = self.out_of_bounds(height as usize, memory) {
+                        return self.stop(fault);
+                    }
+                    self.waiting_for_vblank = false;
+                    self.vblank = false;
+                    let x = self.registers[x as usize];
+                    let y = self.registers[y as usize];
+                    let clip = self.quirks.clipping;
+                    let collision = self.draw_sprite(x, y, height, clip, memory);
+                    self.registers[0xF] = if collision { 1 } else { 0 };
                     self.pc += 2;
                 }
             }
-            0x5000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                println!("Skip next instruction if V[{:X}] == V[{:X}]", x, y);
-
-                if self.registers[x] == self.registers[y] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
+            Instruction::SkipIfKey(x) => {
+                // EX9E: Skip next instruction if key with the value of Vx is pressed,
+                // only the low 4 bits count
+                self.skip_if(self.keys[self.registers[x as usize] as usize & 0xF]);
+            }
+            Instruction::SkipIfNotKey(x) => {
+                // EXA1: Skip next instruction if key with the value of Vx is not pressed
+                self.skip_if(!self.keys[self.registers[x as usize] as usize & 0xF]);
+            }
+            Instruction::WaitForKey(x) => {
+                // FX0A: Wait for a key press, store the value of the key in Vx
+                match self.keys.iter().position(|&pressed| pressed) {
+                    Some(key) => {
+                        trace!(self, "Key pressed: V[{:X}] = 0x{:02X}", x, key);
+                        self.registers[x as usize] = key as u8;
+                        self.pc += 2;
+                    }
+                    // pc stays put, so this runs again until a key is down
+                    None => trace!(self, "Wait for key press, store in V[{:X}]", x),
                 }
             }
-            0x6000 => {
-                // Opcode 6XNN: set register X to NN
-                let x = (opcode & 0x0F00) >> 8;
-                let nn = (opcode & 0x00FF) as u8;
-                println!("Set V[{:X}] = 0x{:02X}", x, nn);
-                // Set register V[x] to NN
-                self.registers[x as usize] = nn as u8; // Store NN in register V[x]
+            Instruction::StoreBcd(x) => {
+                // FX33: Store BCD representation of Vx in memory locations I, I+1, I+2
+                let value = self.registers[x as usize];
+                let hundreds = value / 100;
+                let tens = (value / 10) % 10;
+                let ones = value % 10;
+                if let Some(fault) = self.out_of_bounds(3, memory) {
+                    return self.stop(fault);
+                }
+                memory.write_byte(self.i_register as usize, hundreds);
+                memory.write_byte(self.i_register as usize + 1, tens);
+                memory.write_byte(self.i_register as usize + 2, ones);
+                trace!(
+                    self,
+                    "Stored BCD of V[{:X}] ({}): [{}, {}, {}] at I = 0x{:04X}",
+                    x,
+                    value,
+                    hundreds,
+                    tens,
+                    ones,
+                    self.i_register
+                );
                 self.pc += 2;
             }
-            0x7000 => {
-                // 7XNN: Add NN to register X
-                let x = (opcode & 0x0F00) >> 8;
-                let nn = opcode & 0x00FF;
-                println!("Add 0x{:02X} to V[{:X}]", nn, x);
-                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn as u8);
-                self.pc += 2; // Advance PC after executing
-            }
-            0x8000 => {
-                match opcode & 0x000F {
-                    0x0000 => {
-                        // 8XY0: Set Vx = Vy
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Set V[{:X}] = V[{:X}]", x, y);
-                        self.registers[x as usize] = self.registers[y as usize];
-                    }
-                    0x0001 => {
-                        // 8XY1: Set Vx = Vx OR Vy
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Set V[{:X}] = V[{:X}] OR V[{:X}]", x, x, y);
-                        self.registers[x as usize] |= self.registers[y as usize];
-                    }
-                    0x0002 => {
-                        // 8XY2: Set Vx = Vx AND Vy
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Set V[{:X}] = V[{:X}] AND V[{:X}]", x, x, y);
-                        self.registers[x as usize] &= self.registers[y as usize];
-                    }
-                    0x0003 => {
-                        // 8XY3: Set Vx = Vx XOR Vy
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Set V[{:X}] = V[{:X}] XOR V[{:X}]", x, x, y);
-                        self.registers[x as usize] ^= self.registers[y as usize];
-                    }
-                    0x0004 => {
-                        // 8XY4: Add Vy to Vx, set VF to carry
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Add V[{:X}] to V[{:X}]", y, x);
-                        let sum =
-                            self.registers[x as usize] as u16 + self.registers[y as usize] as u16;
-                        self.registers[0xF] = if sum > 0xFF { 1 } else { 0 };
-                    }
-                    0x0005 => {
-                        // 8XY5: Subtract Vy from Vx, set VF to NOT borrow
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Subtract V[{:X}] from V[{:X}]", y, x);
-                        self.registers[0xF] =
-                            if self.registers[x as usize] > self.registers[y as usize] {
-                                1
-                            } else {
-                                0
-                            };
-                        self.registers[x as usize] =
-                            self.registers[x as usize].wrapping_sub(self.registers[y as usize]);
-                    }
-                    0x0006 => {
-                        // 8XY6: Shift Vx right by 1, set VF to LSB of Vx before shift
-                        let x = (opcode & 0x0F00) >> 8;
-                        println!("Shift V[{:X}] right by 1", x);
-                        self.registers[0xF] = self.registers[x as usize] & 0x1;
-                        self.registers[x as usize] >>= 1;
-                    }
-                    0x0007 => {
-                        // 8XY7: Set Vx = Vy - Vx, set VF to NOT borrow
-                        let x = (opcode & 0x0F00) >> 8;
-                        let y = (opcode & 0x00F0) >> 4;
-                        println!("Set V[{:X}] = V[{:X}] - V[{:X}]", x, y, x);
-                        self.registers[0xF] =
-                            if self.registers[y as usize] > self.registers[x as usize] {
-                                1
-                            } else {
-                                0
-                            };
-                        self.registers[x as usize] =
-                            self.registers[y as usize].wrapping_sub(self.registers[x as usize]);
-                    }
-                    0x000E => {
-                        // 8XYE: Shift Vx to the left by 1, set VF to MSB of Vx before shift
-                        let x = (opcode & 0x0F00) >> 8;
-                        println!("Shift V[{:X}] left by 1", x);
-                        self.registers[0xF] = (self.registers[x as usize] & 0x80) >> 7;
-                        self.registers[x as usize] <<= 1;
-                    }
-                    _ => {
-                        println!("Unknown opcode: 0x{:04X}", opcode);
-                        self.pc += 2; // Skip unknown opcodes
-                    }
+            Instruction::StoreRegisters(x) => {
+                // FX55: Store registers V0 through VX in memory starting at I
+                if let Some(fault) = self.out_of_bounds(x as usize + 1, memory) {
+                    return self.stop(fault);
                 }
+                for reg in 0..=x as usize {
+                    memory.write_byte((self.i_register as usize) + reg, self.registers[reg]);
+                }
+                self.increment_i(x);
+                trace!(
+                    self,
+                    "Stored registers V0 through V[{:X}] in memory starting at I = 0x{:04X}",
+                    x,
+                    self.i_register
+                );
                 self.pc += 2;
             }
-            0x9000 => {
-                // 9XY0: Skip next instruction if Vx != Vy
-                let x = (opcode & 0x0F00) >> 8;
-                let y = (opcode & 0x00F0) >> 4;
-                println!("Skip next instruction if V[{:X}] != V[{:X}]", x, y);
-                if self.registers[x as usize] != self.registers[y as usize] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
+            Instruction::LoadRegisters(x) => {
+                // FX65: Read registers V0 through VX from memory starting at I
+                if let Some(fault) = self.out_of_bounds(x as usize + 1, memory) {
+                    return self.stop(fault);
+                }
+                for reg in 0..=x as usize {
+                    self.registers[reg] = memory.read_byte(self.i_register as usize + reg);
                 }
+                self.increment_i(x);
+                trace!(
+                    self,
+                    "Read registers V0 through V[{:X}] from memory starting at I = 0x{:04X}",
+                    x,
+                    self.i_register
+                );
+                self.pc += 2;
             }
-            0xA000 => {
+            // Everything else falls through to the next instruction
+            _ => {
+                self.execute_straight_line(instruction, rng);
+                self.pc += 2;
+            }
+        }
+        true
+    }
+
+    /// Execute an instruction that falls through to the next one, leaving
+    /// the program counter for the caller to advance. The interpreter and
+    /// the recompiler both run these through here. Returns false, and does
+    /// nothing, for instructions that branch, skip, wait, touch memory or
+    /// can stop the program.
+    pub(crate) fn execute_straight_line<R: RandomSource + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        rng: &mut R,
+    ) -> bool {
+        match instruction {
+            Instruction::ClearScreen => {
+                // 00E0: Clear the display
+                self.clear_display();
+            }
+            Instruction::Set(x, nn) => {
+                // Opcode 6XNN: set register X to NN
+                trace!(self, "Set V[{:X}] = 0x{:02X}", x, nn);
+                // Set register V[x] to NN
+                self.registers[x as usize] = nn; // Store NN in register V[x]
+            }
+            Instruction::Add(x, nn) => {
+                // 7XNN: Add NN to register X
+                trace!(self, "Add 0x{:02X} to V[{:X}]", nn, x);
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn);
+            }
+            Instruction::Copy(x, y) => {
+                // 8XY0: Set Vx = Vy
+                trace!(self, "Set V[{:X}] = V[{:X}]", x, y);
+                self.registers[x as usize] = self.registers[y as usize];
+            }
+            Instruction::Or(x, y) => {
+                // 8XY1: Set Vx = Vx OR Vy
+                trace!(self, "Set V[{:X}] = V[{:X}] OR V[{:X}]", x, x, y);
+                self.registers[x as usize] |= self.registers[y as usize];
+                self.reset_vf();
+            }
+            Instruction::And(x, y) => {
+                // 8XY2: Set Vx = Vx AND Vy
+                trace!(self, "Set V[{:X}] = V[{:X}] AND V[{:X}]", x, x, y);
+                self.registers[x as usize] &= self.registers[y as usize];
+                self.reset_vf();
+            }
+            Instruction::Xor(x, y) => {
+                // 8XY3: Set Vx = Vx XOR Vy
+                trace!(self, "Set V[{:X}] = V[{:X}] XOR V[{:X}]", x, x, y);
+                self.registers[x as usize] ^= self.registers[y as usize];
+                self.reset_vf();
+            }
+            // The arithmetic and shift instructions write the flag after the
+            // result, so the flag wins when X is F
+            Instruction::AddRegister(x, y) => {
+                // 8XY4: Add Vy to Vx, set VF to carry
+                let (x, y) = (x as usize, y as usize);
+                trace!(self, "Add V[{:X}] to V[{:X}]", y, x);
+                let (sum, carry) = self.registers[x].overflowing_add(self.registers[y]);
+                self.registers[x] = sum;
+                self.registers[0xF] = carry as u8;
+            }
+            Instruction::Subtract(x, y) => {
+                // 8XY5: Subtract Vy from Vx, set VF to NOT borrow
+                let (x, y) = (x as usize, y as usize);
+                trace!(self, "Subtract V[{:X}] from V[{:X}]", y, x);
+                let (difference, borrow) = self.registers[x].overflowing_sub(self.registers[y]);
+                self.registers[x] = difference;
+                self.registers[0xF] = !borrow as u8;
+            }
+            Instruction::ShiftRight(x, y) => {
+                // 8XY6: Shift Vx (or Vy) right by 1 into Vx, set VF to the
+                // bit shifted out
+                trace!(self, "Shift V[{:X}] right by 1", x);
+                let value = self.registers[self.shift_source(x, y)];
+                self.registers[x as usize] = value >> 1;
+                self.registers[0xF] = value & 0x1;
+            }
+            Instruction::SubtractReversed(x, y) => {
+                // 8XY7: Set Vx = Vy - Vx, set VF to NOT borrow
+                let (x, y) = (x as usize, y as usize);
+                trace!(self, "Set V[{:X}] = V[{:X}] - V[{:X}]", x, y, x);
+                let (difference, borrow) = self.registers[y].overflowing_sub(self.registers[x]);
+                self.registers[x] = difference;
+                self.registers[0xF] = !borrow as u8;
+            }
+            Instruction::ShiftLeft(x, y) => {
+                // 8XYE: Shift Vx (or Vy) left by 1 into Vx, set VF to the
+                // bit shifted out
+                trace!(self, "Shift V[{:X}] left by 1", x);
+                let value = self.registers[self.shift_source(x, y)];
+                self.registers[x as usize] = value << 1;
+                self.registers[0xF] = value >> 7;
+            }
+            Instruction::SetI(address) => {
                 // ANNN: Set I to address NNN
-                let address = opcode & 0x0FFF;
-                println!("Set I = 0x{:03X}", address);
+                trace!(self, "Set I = 0x{:03X}", address);
                 // Set the index register I to the address NNN
                 self.i_register = address;
-                self.pc += 2;
-            }
-            0xB000 => {
-                // BNNN: Jump to address NNN + V0
-                let address = opcode & 0x0FFF;
-                println!("Jump to address 0x{:03X} + V[0]", address);
-                self.pc = address + self.registers[0] as u16;
             }
-            0xC000 => {
+            Instruction::Random(x, nn) => {
                 // CXNN: Set Vx to a random number AND NN
-                let x = (opcode & 0x0F00) >> 8;
-                let nn = opcode & 0x00FF;
-                println!("Set V[{:X}] = random() AND 0x{:02X}", x, nn);
-                let random_byte: u8 = rand::random();
-                self.registers[x as usize] = random_byte & nn as u8;
-                self.pc += 2;
+                trace!(self, "Set V[{:X}] = random() AND 0x{:02X}", x, nn);
+                let random_byte = rng.next_byte();
+                self.registers[x as usize] = random_byte & nn;
             }
-            0xD000 => {
-                // DXYN: Draw a sprite at coordinate (VX, VY) with width 8 and height N
-                let x = self.registers[((opcode & 0x0F00) >> 8) as usize];
-                let y = self.registers[((opcode & 0x00F0) >> 4) as usize];
-                let height = (opcode & 0x000F) as u8;
-                let collision = self.draw_sprite(x, y, height, memory);
-                self.registers[0xF] = if collision { 1 } else { 0 };
-                self.pc += 2;
+            Instruction::ReadDelayTimer(x) => {
+                // FX07: Set Vx = delay timer value
+                trace!(self, "Set V[{:X}] = delay timer", x);
+                self.registers[x as usize] = self.delay_timer;
             }
-            0xE000 => {
-                match opcode & 0x00FF {
-                    0x009E => {
-                        // EX9E: Skip next instruction if key with the value of Vx is pressed
-                        let x = (opcode & 0x0F00) >> 8;
-                        if self.keys[self.registers[x as usize] as usize] {
-                            self.pc += 4;
-                        } else {
-                            self.pc += 2;
-                        }
-                    }
-                    0x00A1 => {
-                        // EXA1: Skip next instruction if key with the value of Vx is not pressed
-                        let x = (opcode & 0x0F00) >> 8;
-                        if !self.keys[self.registers[x as usize] as usize] {
-                            self.pc += 4;
-                        } else {
-                            self.pc += 2;
-                        }
-                    }
-                    _ => {
-                        println!("Unknown opcode: 0x{:04X}", opcode);
-                        self.pc += 2; // Skip unknown opcodes
-                    }
-                }
+            Instruction::SetDelayTimer(x) => {
+                // FX15: Set delay timer = Vx
+                trace!(self, "Set delay timer = V[{:X}]", x);
+                self.delay_timer = self.registers[x as usize];
             }
-            0xF000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-
-                match opcode & 0x00FF {
-                    0x0007 => {
-                        // FX07: Set Vx = delay timer value
-                        println!("Set V[{:X}] = delay timer (ignored for now)", x);
-                        // self.registers[x] = *delay_timer;
-                        self.pc += 2;
-                    }
-                    0x000A => {
-                        // FX0A: Wait for a key press, store the value of the key in Vx
-                        println!("Wait for key press, store in V[{:X}]", x);
-                        // Ignoring this opcode for now
-                        // for (i, &key) in keys.iter().enumerate() {
-                        //     if key {
-                        //         registers[x] = i as u8;
-                        //         println!("Key pressed: V[{:X}] = 0x{:02X}", x, i);
-                        //         *pc += 2;
-                        //         return;
-                        //     }
-                        // }
-                        // If no key is pressed, return without incrementing pc
-                        println!("No key pressed. Waiting...");
-                    }
-                    0x0015 => {
-                        // FX15: Set delay timer = Vx
-                        println!("Set delay timer = V[{:X}] (ignored for now)", x);
-                        // *delay_timer = registers[x];
-                        self.pc += 2;
-                    }
-                    0x0018 => {
-                        // FX18: Set sound timer = Vx
-                        println!("Set sound timer = V[{:X}] <<Ignored>>", x);
-                        // *sound_timer = registers[x];
-                        self.pc += 2;
-                    }
-                    0x001E => {
-                        // FX1E: Set I = I + Vx
-                        println!("Set I = I + V[{:X}]", x);
-                        self.i_register += self.registers[x] as u16;
-                        self.pc += 2;
-                    }
-                    0x0029 => {
-                        // FX29: Set I = location of sprite for digit Vx
-                        let digit = self.registers[x] as u16 & 0xF; // Only the last 4 bits are used
-                        self.i_register = digit * 5; // Each sprite is 5 bytes long (is it?)
-                        println!(
-                            "Set I = location of sprite for digit {}; I = 0x{:04X}",
-                            digit, self.i_register
-                        );
-                        self.pc += 2;
-                    }
-                    0x0033 => {
-                        // FX33: Store BCD representation of Vx in memory locations I, I+1, I+2
-                        let value = self.registers[x];
-                        let hundreds = value / 100;
-                        let tens = (value / 10) % 10;
-                        let ones = value % 10;
-                        memory.write_byte(self.i_register as usize, hundreds);
-                        memory.write_byte(self.i_register as usize + 1, tens);
-                        memory.write_byte(self.i_register as usize + 2, ones);
-                        println!(
-                            "Stored BCD of V[{:X}] ({}): [{}, {}, {}] at I = 0x{:04X}",
-                            x, value, hundreds, tens, ones, self.i_register
-                        );
-                        self.pc += 2;
-                    }
-                    0x0055 => {
-                        // FX55: Store registers V0 through VX in memory starting at I
-                        for reg in 0..=x {
-                            memory
-                                .write_byte((self.i_register as usize) + reg, self.registers[reg]);
-                        }
-                        println!(
-                            "Stored registers V0 through V[{:X}] in memory starting at I = 0x{:04X}",
-                            x,
-                            self.i_register
-                        );
-                        self.pc += 2;
-                    }
-                    0x0065 => {
-                        // FX65: Read registers V0 through VX from memory starting at I
-                        for reg in 0..=x {
-                            self.registers[reg] = memory.read_byte(self.i_register as usize + reg);
-                        }
-                        println!(
-                            "Read registers V0 through V[{:X}] from memory starting at I = 0x{:04X}",
-                            x,
-                            self.i_register
-                        );
-                        self.pc += 2;
-                    }
-                    _ => {
-                        println!("Unknown opcode: 0x{:04X}", opcode);
-                        self.pc += 2; // Skip unknown opcodes
-                    }
-                }
+            Instruction::SetSoundTimer(x) => {
+                // FX18: Set sound timer = Vx
+                trace!(self, "Set sound timer = V[{:X}]", x);
+                self.sound_timer = self.registers[x as usize];
             }
-            _ => {
-                println!("Unknown opcode: 0x{:04X}", opcode);
-                self.pc += 2; // Skip unknown opcodes
+            Instruction::AddToI(x) => {
+                // FX1E: Set I = I + Vx
+                trace!(self, "Set I = I + V[{:X}]", x);
+                self.i_register = self
+                    .i_register
+                    .wrapping_add(self.registers[x as usize] as u16);
+            }
+            Instruction::FontCharacter(x) => {
+                // FX29: Set I = location of sprite for digit Vx
+                let digit = self.registers[x as usize] as u16 & 0xF; // Only the last 4 bits are used
+                self.i_register = digit * 5; // Each sprite is 5 bytes long (is it?)
+                trace!(
+                    self,
+                    "Set I = location of sprite for digit {}; I = 0x{:04X}",
+                    digit,
+                    self.i_register
+                );
+            }
+            Instruction::Unknown(opcode) => {
+                trace!(self, "Unknown opcode: 0x{:04X}", opcode);
             }
+            _ => return false,
         }
+        true
+    }
 
-        println!("Registers: {:?}", self.registers);
-        println!("Program counter: 0x{:03X}", self.pc);
-        println!("I register: 0x{:03X}", self.i_register);
+    // Record why the program stopped, for `fault`; returns false to pass on
+    fn stop(&mut self, fault: Fault) -> bool {
+        trace!(self, "{}", fault);
+        self.fault = Some(fault);
+        false
+    }
 
-        true
+    // The fault for reading or writing `count` bytes from I, if they run
+    // past the end of memory
+    fn out_of_bounds(&self, count: usize, memory: &Memory) -> Option<Fault> {
+        let start = self.i_register as usize;
+        (count > 0 && start + count > memory.len()).then(|| Fault::MemoryOutOfBounds {
+            pc: self.pc,
+            address: start.max(memory.len()) as u16,
+        })
     }
+
+    // 8XY1, 8XY2 and 8XY3 with the VF reset quirk
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    // The register 8XY6 and 8XYE shift
+    fn shift_source(&self, x: u8, y: u8) -> usize {
+        if self.quirks.shift_in_place {
+            x as usize
+        } else {
+            y as usize
+        }
+    }
+
+    // FX55 and FX65 with the memory quirk. I can't pass the end of memory,
+    // which they've just checked.
+    fn increment_i(&mut self, x: u8) {
+        if self.quirks.memory_increment {
+            self.i_register += x as u16 + 1;
+        }
+    }
+
+    // Skip the next instruction if the condition holds
+    fn skip_if(&mut self, condition: bool) {
+        self.pc += if condition { 4 } else { 2 };
+    }
+}
+
+/// Whether `Cpu::execute_straight_line` runs the instruction: it falls
+/// through to the next one, and can't touch memory or stop the program.
+/// The recompiler ends its blocks on anything else.
+#[cfg(feature = "alloc")]
+pub(crate) fn is_straight_line(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::ClearScreen
+            | Instruction::Set(..)
+            | Instruction::Add(..)
+            | Instruction::Copy(..)
+            | Instruction::Or(..)
+            | Instruction::And(..)
+            | Instruction::Xor(..)
+            | Instruction::AddRegister(..)
+            | Instruction::Subtract(..)
+            | Instruction::ShiftRight(..)
+            | Instruction::SubtractReversed(..)
+            | Instruction::ShiftLeft(..)
+            | Instruction::SetI(_)
+            | Instruction::Random(..)
+            | Instruction::ReadDelayTimer(_)
+            | Instruction::SetDelayTimer(_)
+            | Instruction::SetSoundTimer(_)
+            | Instruction::AddToI(_)
+            | Instruction::FontCharacter(_)
+            | Instruction::Unknown(_)
+    )
 }
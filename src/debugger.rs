@@ -0,0 +1,743 @@
+use std::fmt;
+
+use crate::chip8::Chip8;
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::memory::{AccessKind, WatchHit, Watchpoint};
+use crate::rewind::Rewind;
+
+// Bytes of per-instruction history kept for reverse-step
+const HISTORY_BUDGET: usize = 1 << 20;
+
+/// A value the debugger can read while evaluating a breakpoint condition
+#[derive(Clone, Debug)]
+pub enum Operand {
+    Register(usize),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+    Memory(Box<Operand>),
+    /// A framebuffer pixel, 1 if lit
+    Pixel(Box<Operand>, Box<Operand>),
+    Literal(u16),
+}
+
+impl Operand {
+    fn value(&self, chip8: &Chip8) -> u16 {
+        let cpu = chip8.cpu();
+        match self {
+            Operand::Register(x) => cpu.read_register(*x) as u16,
+            Operand::I => cpu.read_i(),
+            Operand::Pc => cpu.read_pc(),
+            Operand::Sp => cpu.read_sp() as u16,
+            Operand::DelayTimer => cpu.read_delay_timer() as u16,
+            Operand::SoundTimer => cpu.read_sound_timer() as u16,
+            Operand::Memory(address) => {
+                // Addresses wrap around the 4 KiB address space
+                let address = address.value(chip8) as usize % chip8.memory().len();
+                chip8.memory().peek_byte(address) as u16
+            }
+            Operand::Pixel(x, y) => {
+                // Coordinates wrap like sprites do
+                let x = x.value(chip8) as usize % DISPLAY_WIDTH;
+                let y = y.value(chip8) as usize % DISPLAY_HEIGHT;
+                cpu.display().pixels()[y][x] as u16
+            }
+            Operand::Literal(value) => *value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A boolean expression over registers, timers and memory,
+/// e.g. `V5 == 0x3F && I > 0x300` or `mem[I] != 0 || pixel(10, 20) == 1`
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Compare(Operand, Comparison, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let condition = parser.or()?;
+        match parser.peek() {
+            None => Ok(condition),
+            Some(token) => Err(format!("Unexpected '{}' in condition", token)),
+        }
+    }
+
+    pub fn evaluate(&self, chip8: &Chip8) -> bool {
+        match self {
+            Condition::Compare(left, comparison, right) => {
+                let (left, right) = (left.value(chip8), right.value(chip8));
+                match comparison {
+                    Comparison::Eq => left == right,
+                    Comparison::Ne => left != right,
+                    Comparison::Lt => left < right,
+                    Comparison::Le => left <= right,
+                    Comparison::Gt => left > right,
+                    Comparison::Ge => left >= right,
+                }
+            }
+            Condition::And(left, right) => left.evaluate(chip8) && right.evaluate(chip8),
+            Condition::Or(left, right) => left.evaluate(chip8) || right.evaluate(chip8),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u16),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Number(value) => write!(f, "0x{:X}", value),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 13] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "[", "]", "(", ")", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Unexpected character in condition: {}", rest));
+            }
+            let word = &rest[..end];
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                tokens.push(Token::Number(parse_number(word)?));
+            } else {
+                tokens.push(Token::Ident(word.to_ascii_uppercase()));
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or("Unexpected end of condition")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token::Symbol(s)) if *s == symbol => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' in condition", symbol))
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.eat("||") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.comparison()?;
+        while self.eat("&&") {
+            condition = Condition::And(Box::new(condition), Box::new(self.comparison()?));
+        }
+        Ok(condition)
+    }
+
+    fn comparison(&mut self) -> Result<Condition, String> {
+        if self.eat("(") {
+            let condition = self.or()?;
+            self.expect(")")?;
+            return Ok(condition);
+        }
+
+        let left = self.operand()?;
+        let comparison = match self.next()? {
+            Token::Symbol("==") => Comparison::Eq,
+            Token::Symbol("!=") => Comparison::Ne,
+            Token::Symbol("<") => Comparison::Lt,
+            Token::Symbol("<=") => Comparison::Le,
+            Token::Symbol(">") => Comparison::Gt,
+            Token::Symbol(">=") => Comparison::Ge,
+            token => return Err(format!("Expected a comparison, found '{}'", token)),
+        };
+        let right = self.operand()?;
+        Ok(Condition::Compare(left, comparison, right))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next()? {
+            Token::Number(value) => Ok(Operand::Literal(value)),
+            Token::Ident(name) => match name.as_str() {
+                "I" => Ok(Operand::I),
+                "PC" => Ok(Operand::Pc),
+                "SP" => Ok(Operand::Sp),
+                "DT" => Ok(Operand::DelayTimer),
+                "ST" => Ok(Operand::SoundTimer),
+                "MEM" => {
+                    self.expect("[")?;
+                    let address = self.operand()?;
+                    self.expect("]")?;
+                    Ok(Operand::Memory(Box::new(address)))
+                }
+                "PIXEL" => {
+                    self.expect("(")?;
+                    let x = self.operand()?;
+                    self.expect(",")?;
+                    let y = self.operand()?;
+                    self.expect(")")?;
+                    Ok(Operand::Pixel(Box::new(x), Box::new(y)))
+                }
+                _ => parse_register(&name).map(Operand::Register),
+            },
+            token => Err(format!("Expected a register or value, found '{}'", token)),
+        }
+    }
+}
+
+/// Parse `V0`..`VF` into a register index
+pub fn parse_register(name: &str) -> Result<usize, String> {
+    let name = name.to_ascii_uppercase();
+    match name.strip_prefix('V') {
+        Some(digit) if digit.len() == 1 => {
+            usize::from_str_radix(digit, 16).map_err(|_| format!("Unknown register: {}", name))
+        }
+        _ => Err(format!("Unknown register: {}", name)),
+    }
+}
+
+/// Parse a `0x`-prefixed hexadecimal or plain decimal number
+pub fn parse_number(text: &str) -> Result<u16, String> {
+    let parsed = match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number: {}", text))
+}
+
+/// Matches a class of opcodes, written the way opcodes are usually documented:
+/// hex digits must match exactly, any other letter is a wildcard nibble,
+/// so `DXYN` matches every draw and `FX33` every BCD store
+#[derive(Clone, Debug)]
+pub struct OpcodePattern {
+    text: String,
+    mask: u16,
+    value: u16,
+}
+
+impl OpcodePattern {
+    pub fn parse(text: &str) -> Result<OpcodePattern, String> {
+        let text = text.to_ascii_uppercase();
+        let pattern = match text.as_str() {
+            "CLS" => "00E0",
+            "RET" => "00EE",
+            "JP" | "JUMP" => "1NNN",
+            "CALL" => "2NNN",
+            "RND" => "CXNN",
+            "DRW" | "DRAW" => "DXYN",
+            "BCD" => "FX33",
+            other => other,
+        };
+        if pattern.len() != 4 || !pattern.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("Invalid opcode pattern: {}", text));
+        }
+
+        let mut mask = 0;
+        let mut value = 0;
+        for (i, c) in pattern.chars().enumerate() {
+            let shift = 12 - i * 4;
+            if let Some(nibble) = c.to_digit(16) {
+                mask |= 0xF << shift;
+                value |= (nibble as u16) << shift;
+            }
+        }
+        Ok(OpcodePattern { text, mask, value })
+    }
+
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+pub enum Breakpoint {
+    /// Stop before executing the instruction at this address
+    Address(u16),
+    /// Stop before executing the next instruction matching the pattern
+    Opcode(OpcodePattern),
+    /// Stop after the instruction that makes the condition become true
+    Condition {
+        source: String,
+        condition: Condition,
+        was_true: bool,
+    },
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Address(address) => write!(f, "at 0x{:03X}", address),
+            Breakpoint::Opcode(pattern) => write!(f, "on opcode {}", pattern.text),
+            Breakpoint::Condition { source, .. } => write!(f, "if {}", source),
+        }
+    }
+}
+
+pub enum StopReason {
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+}
+
+/// Why execution stopped, and the instruction responsible
+pub struct Stop {
+    pub reason: StopReason,
+    pub pc: u16,
+    pub opcode: u16,
+}
+
+pub enum Step {
+    Running,
+    Halted,
+    Stopped(Stop),
+}
+
+/// What the frontend should do after a debugger command
+pub enum Resume {
+    Prompt,
+    Continue,
+    Step(usize),
+    Quit,
+}
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    // Address we stopped at before executing, so resuming doesn't re-trigger it
+    resume_pc: Option<u16>,
+    // Snapshot before every executed instruction, for reverse-step, while
+    // history is on, as it is by default; taking them slows every step down
+    history: Option<Rewind>,
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            resume_pc: None,
+            history: Some(Rewind::new(1, HISTORY_BUDGET)),
+        }
+    }
+
+    /// Start or stop keeping history for reverse-step. Turning it off drops
+    /// the history kept so far.
+    pub fn set_history(&mut self, enabled: bool) {
+        match enabled {
+            true if self.history.is_none() => {
+                self.history = Some(Rewind::new(1, HISTORY_BUDGET));
+            }
+            true => {}
+            false => self.history = None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+
+    /// Add a condition breakpoint, starting from its current value so a
+    /// condition that already holds only fires once it becomes true again
+    pub fn add_condition(&mut self, source: &str, chip8: &Chip8) -> Result<usize, String> {
+        let condition = Condition::parse(source)?;
+        let was_true = condition.evaluate(chip8);
+        Ok(self.add_breakpoint(Breakpoint::Condition {
+            source: source.to_string(),
+            condition,
+            was_true,
+        }))
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        if index < self.breakpoints.len() {
+            Some(self.breakpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Don't stop on address or opcode breakpoints at `pc` on the next step,
+    /// so execution can resume from the instruction we stopped at
+    pub fn resume_at(&mut self, pc: u16) {
+        self.resume_pc = Some(pc);
+    }
+
+    /// Execute one instruction, checking breakpoints and watchpoints around it
+    pub fn step(&mut self, chip8: &mut Chip8) -> Step {
+        let pc = chip8.cpu().read_pc();
+        if pc as usize + 1 >= chip8.memory().len() {
+            // Let the CPU report the out of bounds program counter
+            chip8.step();
+            return Step::Halted;
+        }
+        let opcode = chip8.memory().fetch_opcode(pc as usize);
+
+        if self.resume_pc.take() != Some(pc) {
+            let hit = self.breakpoints.iter().position(|b| match b {
+                Breakpoint::Address(address) => *address == pc,
+                Breakpoint::Opcode(pattern) => pattern.matches(opcode),
+                Breakpoint::Condition { .. } => false,
+            });
+            if let Some(index) = hit {
+                self.resume_pc = Some(pc);
+                return Step::Stopped(Stop {
+                    reason: StopReason::Breakpoint(index),
+                    pc,
+                    opcode,
+                });
+            }
+        }
+
+        if let Some(history) = self.history.as_mut() {
+            history.record(chip8);
+        }
+
+        // Discard accesses made outside of instruction execution
+        chip8.memory().take_watch_hit();
+        if !chip8.step() {
+            return Step::Halted;
+        }
+
+        if let Some(hit) = chip8.memory().take_watch_hit() {
+            return Step::Stopped(Stop {
+                reason: StopReason::Watchpoint(hit),
+                pc,
+                opcode,
+            });
+        }
+
+        let mut stop = None;
+        for (index, breakpoint) in self.breakpoints.iter_mut().enumerate() {
+            if let Breakpoint::Condition {
+                condition,
+                was_true,
+                ..
+            } = breakpoint
+            {
+                let is_true = condition.evaluate(chip8);
+                if is_true && !*was_true && stop.is_none() {
+                    stop = Some(Stop {
+                        reason: StopReason::Breakpoint(index),
+                        pc,
+                        opcode,
+                    });
+                }
+                *was_true = is_true;
+            }
+        }
+
+        match stop {
+            Some(stop) => Step::Stopped(stop),
+            None => Step::Running,
+        }
+    }
+
+    /// Undo the last executed instruction. Returns false once the
+    /// history is exhausted, or if it is off.
+    pub fn reverse_step(&mut self, chip8: &mut Chip8) -> bool {
+        let stepped = self
+            .history
+            .as_mut()
+            .is_some_and(|history| history.step_back(chip8));
+        if !stepped {
+            return false;
+        }
+        // Don't stop on a breakpoint at the instruction we're going back to
+        self.resume_pc = Some(chip8.cpu().read_pc());
+        true
+    }
+
+    /// Describe a stop, including the faulting instruction
+    pub fn describe(&self, stop: &Stop) -> String {
+        let instruction = format!("0x{:03X}: {:04X}", stop.pc, stop.opcode);
+        match &stop.reason {
+            StopReason::Breakpoint(index) => match self.breakpoints.get(*index) {
+                Some(breakpoint) => {
+                    format!(
+                        "Breakpoint {} ({}) hit by {}",
+                        index, breakpoint, instruction
+                    )
+                }
+                None => format!("Breakpoint {} hit by {}", index, instruction),
+            },
+            StopReason::Watchpoint(hit) => {
+                let access = match hit.kind {
+                    AccessKind::Read => "read",
+                    AccessKind::Write => "wrote",
+                };
+                format!(
+                    "Watchpoint: {} {} 0x{:02X} at 0x{:03X}",
+                    instruction, access, hit.value, hit.address
+                )
+            }
+        }
+    }
+
+    /// Run a command typed at the debugger prompt
+    pub fn run_command(&mut self, chip8: &mut Chip8, line: &str) -> Result<Resume, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(Resume::Prompt),
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "c" | "continue" => return Ok(Resume::Continue),
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(count) => parse_number(count)? as usize,
+                    None => 1,
+                };
+                return Ok(Resume::Step(count));
+            }
+            "rs" | "reverse-step" => {
+                if self.history.is_none() {
+                    return Err("History is off; turn it on with 'history on'".into());
+                }
+                let count = match args.first() {
+                    Some(count) => parse_number(count)? as usize,
+                    None => 1,
+                };
+                for _ in 0..count {
+                    if !self.reverse_step(chip8) {
+                        println!("No more history");
+                        break;
+                    }
+                }
+                println!("{}", registers(chip8));
+            }
+            "history" => match args.as_slice() {
+                ["on"] => self.set_history(true),
+                ["off"] => self.set_history(false),
+                _ => return Err("Usage: history <on|off>".into()),
+            },
+            "b" | "break" => {
+                let index = match args.as_slice() {
+                    ["if", ..] => self.add_condition(&args[1..].join(" "), chip8)?,
+                    ["op", pattern] => {
+                        self.add_breakpoint(Breakpoint::Opcode(OpcodePattern::parse(pattern)?))
+                    }
+                    [address] => self.add_breakpoint(Breakpoint::Address(parse_number(address)?)),
+                    _ => {
+                        return Err("Usage: b <address> | b if <condition> | b op <pattern>".into())
+                    }
+                };
+                println!("Breakpoint {} {}", index, self.breakpoints[index]);
+            }
+            "w" | "watch" => {
+                let (range, mode) = match args.as_slice() {
+                    [range] => (*range, "w"),
+                    [range, mode] => (*range, *mode),
+                    _ => return Err("Usage: w <start>[-<end>] [r|w|rw]".into()),
+                };
+                let (on_read, on_write) = match mode {
+                    "r" => (true, false),
+                    "w" => (false, true),
+                    "rw" | "wr" => (true, true),
+                    _ => return Err(format!("Invalid watch mode: {}", mode)),
+                };
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (parse_number(start)?, parse_number(end)?),
+                    None => (parse_number(range)?, parse_number(range)?),
+                };
+                if start > end {
+                    return Err(format!(
+                        "Invalid watch range: {} ends before it starts",
+                        range
+                    ));
+                }
+                let watchpoint = Watchpoint {
+                    start,
+                    end,
+                    on_read,
+                    on_write,
+                };
+                chip8.memory_mut().add_watchpoint(watchpoint);
+                println!(
+                    "Watchpoint {} on 0x{:03X}-0x{:03X} ({})",
+                    chip8.memory().watchpoints().len() - 1,
+                    start,
+                    end,
+                    mode
+                );
+            }
+            "d" | "delete" => {
+                let index = parse_number(args.first().ok_or("Usage: d <breakpoint>")?)?;
+                self.remove_breakpoint(index as usize)
+                    .ok_or(format!("No breakpoint {}", index))?;
+            }
+            "dw" => {
+                let index = parse_number(args.first().ok_or("Usage: dw <watchpoint>")?)?;
+                chip8
+                    .memory_mut()
+                    .remove_watchpoint(index as usize)
+                    .ok_or(format!("No watchpoint {}", index))?;
+            }
+            "l" | "list" => {
+                for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+                    println!("Breakpoint {} {}", index, breakpoint);
+                }
+                for (index, watch) in chip8.memory().watchpoints().iter().enumerate() {
+                    println!(
+                        "Watchpoint {} on 0x{:03X}-0x{:03X} (read: {}, write: {})",
+                        index, watch.start, watch.end, watch.on_read, watch.on_write
+                    );
+                }
+            }
+            "r" | "regs" => println!("{}", registers(chip8)),
+            "x" => {
+                let address = parse_number(args.first().ok_or("Usage: x <address> [length]")?)?;
+                let length = match args.get(1) {
+                    Some(length) => parse_number(length)?,
+                    None => 16,
+                };
+                let memory = chip8.memory();
+                let bytes: Vec<String> = (address..address.saturating_add(length))
+                    .map(|a| format!("{:02X}", memory.peek_byte(a as usize % memory.len())))
+                    .collect();
+                println!("0x{:03X}: {}", address, bytes.join(" "));
+            }
+            "set" => match args.as_slice() {
+                [target, value] => {
+                    let value = parse_number(value)?;
+                    if target.eq_ignore_ascii_case("pc") {
+                        chip8.cpu_mut().write_pc(value);
+                    } else {
+                        chip8
+                            .cpu_mut()
+                            .write_register(parse_register(target)?, value as u8);
+                    }
+                }
+                _ => return Err("Usage: set <V0-VF|pc> <value>".into()),
+            },
+            "key" => match args.as_slice() {
+                [key, state] if *state == "down" || *state == "up" => {
+                    let key = parse_number(key)? as usize;
+                    if key > 0xF {
+                        return Err(format!("No such key: {}", key));
+                    }
+                    chip8.cpu_mut().set_key(key, *state == "down");
+                }
+                _ => return Err("Usage: key <0-F> <down|up>".into()),
+            },
+            "q" | "quit" => return Ok(Resume::Quit),
+            "h" | "help" => println!("{}", HELP),
+            _ => return Err(format!("Unknown command: {} (try 'help')", command)),
+        }
+
+        Ok(Resume::Prompt)
+    }
+}
+
+/// One line summary of the machine state
+pub fn registers(chip8: &Chip8) -> String {
+    let cpu = chip8.cpu();
+    let registers: Vec<String> = (0..16)
+        .map(|x| format!("V{:X}={:02X}", x, cpu.read_register(x)))
+        .collect();
+    format!(
+        "{} I={:03X} PC={:03X} SP={} DT={:02X} ST={:02X}",
+        registers.join(" "),
+        cpu.read_i(),
+        cpu.read_pc(),
+        cpu.read_sp(),
+        cpu.read_delay_timer(),
+        cpu.read_sound_timer()
+    )
+}
+
+const HELP: &str = "\
+c, continue              run until a breakpoint or watchpoint
+s, step [n]              execute n instructions (default 1)
+rs, reverse-step [n]     undo the last n instructions (default 1)
+history <on|off>         keep history for reverse-step (on by default)
+b <address>              break before executing the instruction at address
+b if <condition>         break when a condition becomes true, e.g. V5 == 0x3F && I > 0x300
+b op <pattern>           break before the next matching opcode, e.g. DXYN, 8XY4, CALL, RET
+w <start>[-<end>] [r|w]  watch memory for reads, writes or both (default w)
+d <n>, dw <n>            delete breakpoint or watchpoint n
+l, list                  list breakpoints and watchpoints
+r, regs                  show registers
+x <address> [length]     dump memory
+set <V0-VF|pc> <value>   change a register
+key <0-F> <down|up>      press or release a key
+q, quit                  exit";
@@ -0,0 +1,133 @@
+/// An opcode split into its operation and operands. X and Y are register
+/// indices, NN a byte and NNN an address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 0NNN, a machine code routine on the original hardware
+    System(u16),
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipIfEqual(u8, u8),
+    /// 4XNN
+    SkipIfNotEqual(u8, u8),
+    /// 5XY0
+    SkipIfRegistersEqual(u8, u8),
+    /// 6XNN
+    Set(u8, u8),
+    /// 7XNN
+    Add(u8, u8),
+    /// 8XY0
+    Copy(u8, u8),
+    /// 8XY1
+    Or(u8, u8),
+    /// 8XY2
+    And(u8, u8),
+    /// 8XY3
+    Xor(u8, u8),
+    /// 8XY4
+    AddRegister(u8, u8),
+    /// 8XY5
+    Subtract(u8, u8),
+    /// 8XY6
+    ShiftRight(u8, u8),
+    /// 8XY7
+    SubtractReversed(u8, u8),
+    /// 8XYE
+    ShiftLeft(u8, u8),
+    /// 9XY0
+    SkipIfRegistersNotEqual(u8, u8),
+    /// ANNN
+    SetI(u16),
+    /// BNNN
+    JumpOffset(u16),
+    /// CXNN
+    Random(u8, u8),
+    /// DXYN
+    Draw(u8, u8, u8),
+    /// EX9E
+    SkipIfKey(u8),
+    /// EXA1
+    SkipIfNotKey(u8),
+    /// FX07
+    ReadDelayTimer(u8),
+    /// FX0A
+    WaitForKey(u8),
+    /// FX15
+    SetDelayTimer(u8),
+    /// FX18
+    SetSoundTimer(u8),
+    /// FX1E
+    AddToI(u8),
+    /// FX29
+    FontCharacter(u8),
+    /// FX33
+    StoreBcd(u8),
+    /// FX55
+    StoreRegisters(u8),
+    /// FX65
+    LoadRegisters(u8),
+    Unknown(u16),
+}
+
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match nn {
+            0xE0 => Instruction::ClearScreen,
+            0xEE => Instruction::Return,
+            _ => Instruction::System(nnn),
+        },
+        0x1000 => Instruction::Jump(nnn),
+        0x2000 => Instruction::Call(nnn),
+        0x3000 => Instruction::SkipIfEqual(x, nn),
+        0x4000 => Instruction::SkipIfNotEqual(x, nn),
+        0x5000 => Instruction::SkipIfRegistersEqual(x, y),
+        0x6000 => Instruction::Set(x, nn),
+        0x7000 => Instruction::Add(x, nn),
+        0x8000 => match n {
+            0x0 => Instruction::Copy(x, y),
+            0x1 => Instruction::Or(x, y),
+            0x2 => Instruction::And(x, y),
+            0x3 => Instruction::Xor(x, y),
+            0x4 => Instruction::AddRegister(x, y),
+            0x5 => Instruction::Subtract(x, y),
+            0x6 => Instruction::ShiftRight(x, y),
+            0x7 => Instruction::SubtractReversed(x, y),
+            0xE => Instruction::ShiftLeft(x, y),
+            _ => Instruction::Unknown(opcode),
+        },
+        0x9000 => Instruction::SkipIfRegistersNotEqual(x, y),
+        0xA000 => Instruction::SetI(nnn),
+        0xB000 => Instruction::JumpOffset(nnn),
+        0xC000 => Instruction::Random(x, nn),
+        0xD000 => Instruction::Draw(x, y, n),
+        0xE000 => match nn {
+            0x9E => Instruction::SkipIfKey(x),
+            0xA1 => Instruction::SkipIfNotKey(x),
+            _ => Instruction::Unknown(opcode),
+        },
+        _ => match nn {
+            0x07 => Instruction::ReadDelayTimer(x),
+            0x0A => Instruction::WaitForKey(x),
+            0x15 => Instruction::SetDelayTimer(x),
+            0x18 => Instruction::SetSoundTimer(x),
+            0x1E => Instruction::AddToI(x),
+            0x29 => Instruction::FontCharacter(x),
+            0x33 => Instruction::StoreBcd(x),
+            0x55 => Instruction::StoreRegisters(x),
+            0x65 => Instruction::LoadRegisters(x),
+            _ => Instruction::Unknown(opcode),
+        },
+    }
+}
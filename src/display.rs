@@ -1,10 +1,60 @@
-struct Display {
+#[cfg(feature = "alloc")]
+use crate::state::StateWriter;
+use crate::state::{StateError, StateReader};
+use crate::trace::Trace;
 
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+
+pub struct Display {
+    pixels: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+}
+
+impl Default for Display {
+    fn default() -> Display {
+        Display::new()
+    }
 }
 
 impl Display {
-    fn new() -> Display {
-        Display {}
+    pub fn new() -> Display {
+        Display {
+            pixels: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+        }
+    }
+
+    /// Read the framebuffer, one byte (0 or 1) per pixel
+    pub fn pixels(&self) -> &[[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT] {
+        &self.pixels
+    }
+
+    /// Save the framebuffer packed to one bit per pixel
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        for row in self.pixels.iter() {
+            for chunk in row.chunks(8) {
+                writer.write_u8(chunk.iter().fold(0, |byte, &pixel| (byte << 1) | pixel));
+            }
+        }
+    }
+
+    pub fn from_state(reader: &mut StateReader) -> Result<Display, StateError> {
+        let mut display = Display::new();
+        for row in display.pixels.iter_mut() {
+            for chunk in row.chunks_mut(8) {
+                let byte = reader.read_u8()?;
+                for (bit, pixel) in chunk.iter_mut().enumerate() {
+                    *pixel = (byte >> (7 - bit)) & 1;
+                }
+            }
+        }
+        Ok(display)
+    }
+
+    pub fn clear(&mut self) {
+        for row in self.pixels.iter_mut() {
+            row.fill(0);
+        }
     }
 
     /// couple of questions
@@ -15,32 +65,40 @@ impl Display {
     /// why are we doing self.display[y][x] ?
     /// and why are we checking if the display[y][x] == 1?
     /// what is the purpose of the xor operation?
-    pub fn draw_sprite(&mut self, vx: u8, vy: u8, height: u8, memory: &Memory) -> bool {
-        let x = vx as usize;
-        let y = vy as usize;
+    pub fn draw_sprite(
+        &mut self,
+        vx: u8,
+        vy: u8,
+        rows: impl IntoIterator<Item = u8>,
+        clip: bool,
+        trace: Option<&dyn Trace>,
+    ) -> bool {
+        // The sprite starts on screen, and then either wraps or is clipped
+        let x = vx as usize % DISPLAY_WIDTH;
+        let y = vy as usize % DISPLAY_HEIGHT;
         let mut collision = false;
 
-        for byte in 0..height {
-            let sprite = memory.read_byte(self.i_register as usize + byte as usize);
+        for (byte, sprite) in rows.into_iter().enumerate() {
             for bit in 0..8 {
                 let pixel = (sprite >> (7 - bit)) & 1;
-                if pixel == 1 {
-                    let display_x = (x + bit) % 64;
-                    let display_y = (y + byte as usize) % 32;
-                    if self.display[display_y][display_x] == 1 {
+                let off_screen = x + bit >= DISPLAY_WIDTH || y + byte >= DISPLAY_HEIGHT;
+                if pixel == 1 && !(clip && off_screen) {
+                    let display_x = (x + bit) % DISPLAY_WIDTH;
+                    let display_y = (y + byte) % DISPLAY_HEIGHT;
+                    if self.pixels[display_y][display_x] == 1 {
                         collision = true;
                     }
-                    self.display[display_y][display_x] ^= 1;
-                    print!("{} ", self.display[display_y][display_x]);
+                    self.pixels[display_y][display_x] ^= 1;
+                    if let Some(trace) = trace {
+                        trace.write(format_args!("{} ", self.pixels[display_y][display_x]));
+                    }
                 }
             }
-            println!();
+            if let Some(trace) = trace {
+                trace.write(format_args!("\n"));
+            }
         }
 
         collision
     }
-
-    fn render(&self, _pixels: &Vec<u8>) {
-        // render the pixels
-    }
-}
\ No newline at end of file
+}
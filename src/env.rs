@@ -0,0 +1,460 @@
+//! A reinforcement-learning environment in the style of OpenAI Gym.
+//!
+//! An [`Env`] plays one ROM: [`Env::reset`] starts an episode and
+//! [`Env::step`] holds down the keys for an action for a few frames,
+//! returning what the screen shows, the reward, and whether the episode is
+//! over. What counts as score and lives is described per ROM by a [`Game`],
+//! as locations in the machine: a register, a byte of memory, or the BCD
+//! digits a game writes with FX33 to draw its score.
+//!
+//! ```
+//! use chip_8_emulator::env::{Env, EnvConfig, Game};
+//!
+//! let rom = std::fs::read("data/BRIX").unwrap();
+//! let config = EnvConfig {
+//!     max_frames: Some(600),
+//!     ..EnvConfig::default()
+//! };
+//! let mut env = Env::new(&rom, Game::brix(), config).unwrap();
+//! let _observation = env.reset(0);
+//! let mut total = 0.0;
+//! loop {
+//!     // A policy would look at the observation and pick one of
+//!     // env.action_count() actions
+//!     let (_observation, reward, done, _info) = env.step(1).unwrap();
+//!     total += reward;
+//!     if done {
+//!         break;
+//!     }
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::{panic, thread};
+
+use crate::chip8::{Chip8, Chip8Builder, Chip8Error, Framebuffer};
+use crate::rng::Rng;
+
+/// What the agent sees after each step: the display, one byte (0 or 1) per
+/// pixel
+pub type Observation = Framebuffer;
+
+/// Somewhere a game keeps a number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    /// VX
+    Register(u8),
+    /// A byte of memory
+    Byte(u16),
+    /// Decimal digits, one per byte and most significant first, as FX33
+    /// stores them
+    Bcd { address: u16, digits: u8 },
+}
+
+impl Location {
+    /// The number there now. Registers and addresses wrap to the machine's.
+    pub fn read(&self, chip8: &Chip8) -> u32 {
+        let memory = chip8.memory();
+        match *self {
+            Location::Register(x) => chip8.cpu().read_register((x & 0xF) as usize) as u32,
+            Location::Byte(address) => memory.peek_byte(address as usize & 0xFFF) as u32,
+            Location::Bcd { address, digits } => (0..digits as usize).fold(0, |value, digit| {
+                value * 10 + memory.peek_byte((address as usize + digit) & 0xFFF) as u32
+            }),
+        }
+    }
+}
+
+/// The keys held down for an action, bit N for key N
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keys(pub u16);
+
+impl Keys {
+    pub const NONE: Keys = Keys(0);
+
+    /// Keys 0-F; others are ignored
+    pub fn of(keys: &[u8]) -> Keys {
+        Keys(
+            keys.iter()
+                .filter(|&&key| key <= 0xF)
+                .fold(0, |mask, &key| mask | 1 << key),
+        )
+    }
+}
+
+/// How to play a ROM: its actions, and where it keeps score and lives
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    /// Action N holds down `actions[N]`
+    pub actions: Vec<Keys>,
+    /// The reward for a step is the change in each of these, times its
+    /// weight, added up
+    pub score: Vec<(Location, f64)>,
+    /// The episode ends when this drops to zero
+    pub lives: Option<Location>,
+}
+
+impl Default for Game {
+    /// Every key on its own, or none, and no reward
+    fn default() -> Game {
+        Game {
+            actions: (0..=0xF)
+                .map(|key| Keys::of(&[key]))
+                .chain([Keys::NONE])
+                .collect(),
+            score: Vec::new(),
+            lives: None,
+        }
+    }
+}
+
+impl Game {
+    /// Two-player PONG, playing the left paddle (1 up, 4 down). Scores are
+    /// kept in VE, the left player's in the tens, and drawn from FX33's
+    /// digits at 0x2F2; a point for the right player costs one.
+    pub fn pong() -> Game {
+        Game {
+            actions: vec![Keys::NONE, Keys::of(&[1]), Keys::of(&[4])],
+            score: vec![
+                (
+                    Location::Bcd {
+                        address: 0x2F3,
+                        digits: 1,
+                    },
+                    1.0,
+                ),
+                (
+                    Location::Bcd {
+                        address: 0x2F4,
+                        digits: 1,
+                    },
+                    -1.0,
+                ),
+            ],
+            lives: None,
+        }
+    }
+
+    /// BRIX (4 left, 6 right): a point per brick in V5, lives in VE
+    pub fn brix() -> Game {
+        Game {
+            actions: vec![Keys::NONE, Keys::of(&[4]), Keys::of(&[6])],
+            score: vec![(Location::Register(5), 1.0)],
+            lives: Some(Location::Register(0xE)),
+        }
+    }
+
+    fn score(&self, chip8: &Chip8) -> f64 {
+        self.score
+            .iter()
+            .map(|(location, weight)| location.read(chip8) as f64 * weight)
+            .sum()
+    }
+}
+
+/// Settings for an environment
+#[derive(Clone)]
+pub struct EnvConfig {
+    /// Frames each action is held for. Rewards over them are added up.
+    pub frame_skip: u32,
+    /// Episodes are cut off after this many frames, if set
+    pub max_frames: Option<u32>,
+    /// End the episode on the first life lost instead of the last
+    pub end_on_life_lost: bool,
+    /// The machine to play on. Its RNG is replaced on every reset.
+    pub chip8: Chip8Builder,
+}
+
+impl Default for EnvConfig {
+    fn default() -> EnvConfig {
+        EnvConfig {
+            frame_skip: 4,
+            max_frames: None,
+            end_on_life_lost: false,
+            chip8: Chip8Builder::new(),
+        }
+    }
+}
+
+/// Extra detail about a step
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Info {
+    /// Frames since the reset
+    pub frame: u32,
+    /// Lives left, if the game has any
+    pub lives: Option<u32>,
+    /// The episode was cut off by `max_frames` rather than ending
+    pub truncated: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnvError {
+    /// The ROM couldn't be loaded
+    Rom(Chip8Error),
+    /// There is no such action
+    InvalidAction { action: usize, actions: usize },
+    /// A batch step needs an action for every environment
+    WrongActionCount { given: usize, envs: usize },
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvError::Rom(e) => write!(f, "{}", e),
+            EnvError::InvalidAction { action, actions } => {
+                write!(f, "no action {}, there are {}", action, actions)
+            }
+            EnvError::WrongActionCount { given, envs } => {
+                write!(f, "{} actions given for {} environments", given, envs)
+            }
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+impl From<Chip8Error> for EnvError {
+    fn from(e: Chip8Error) -> EnvError {
+        EnvError::Rom(e)
+    }
+}
+
+/// One ROM, played an episode at a time
+pub struct Env {
+    chip8: Chip8,
+    rom: Vec<u8>,
+    game: Game,
+    config: EnvConfig,
+    frame: u32,
+    score: f64,
+    lives: Option<u32>,
+    stopped: bool,
+}
+
+impl Env {
+    /// The first episode is seeded with 0; `reset` starts another
+    pub fn new(rom: &[u8], game: Game, config: EnvConfig) -> Result<Env, EnvError> {
+        let mut chip8 = config.chip8.clone().build();
+        chip8.load_program(rom)?;
+        let mut env = Env {
+            chip8,
+            rom: rom.to_vec(),
+            game,
+            config,
+            frame: 0,
+            score: 0.0,
+            lives: None,
+            stopped: false,
+        };
+        env.reset(0);
+        Ok(env)
+    }
+
+    /// Start a new episode from power-on, with the RNG seeded from `seed`
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.chip8 = self.config.chip8.clone().rng(Rng::new(seed)).build();
+        self.chip8
+            .load_program(&self.rom)
+            .expect("ROM fitted when the environment was made");
+        self.frame = 0;
+        self.score = self.game.score(&self.chip8);
+        self.lives = self.read_lives();
+        self.stopped = false;
+        *self.chip8.framebuffer()
+    }
+
+    /// Hold down the keys for `action` for `frame_skip` frames. Returns the
+    /// screen after, the reward over the frames, whether the episode is
+    /// over, and more detail. The step stops early if the episode ends.
+    pub fn step(&mut self, action: usize) -> Result<(Observation, f64, bool, Info), EnvError> {
+        let Some(&keys) = self.game.actions.get(action) else {
+            return Err(EnvError::InvalidAction {
+                action,
+                actions: self.game.actions.len(),
+            });
+        };
+        for key in 0..=0xF {
+            self.chip8.set_key(key, keys.0 & 1 << key != 0)?;
+        }
+
+        let mut reward = 0.0;
+        let mut done = false;
+        let mut truncated = false;
+        for _ in 0..self.config.frame_skip.max(1) {
+            self.stopped |= !self.chip8.run_frame();
+            self.frame += 1;
+
+            let score = self.game.score(&self.chip8);
+            reward += score - self.score;
+            self.score = score;
+
+            // Lives only count once the game has set them up, so a
+            // register still zero from power-on doesn't end the episode
+            let lives = self.read_lives();
+            if let (Some(before), Some(now)) = (self.lives, lives) {
+                done |= before > 0 && now == 0;
+                done |= self.config.end_on_life_lost && now < before;
+            }
+            self.lives = lives;
+
+            done |= self.stopped;
+            if !done && self.config.max_frames.is_some_and(|max| self.frame >= max) {
+                done = true;
+                truncated = true;
+            }
+            if done {
+                break;
+            }
+        }
+
+        let info = Info {
+            frame: self.frame,
+            lives: self.lives,
+            truncated,
+        };
+        Ok((*self.chip8.framebuffer(), reward, done, info))
+    }
+
+    pub fn action_count(&self) -> usize {
+        self.game.actions.len()
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The machine, e.g. to look at memory or save state
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    fn read_lives(&self) -> Option<u32> {
+        self.game.lives.map(|lives| lives.read(&self.chip8))
+    }
+}
+
+/// What a batch of environments did in one step
+#[derive(Debug)]
+pub struct VecStep {
+    /// For an environment that finished, the first observation of its next
+    /// episode; the last one of the finished episode is in
+    /// `final_observations`
+    pub observations: Vec<Observation>,
+    pub rewards: Vec<f64>,
+    pub dones: Vec<bool>,
+    pub infos: Vec<Info>,
+    pub final_observations: Vec<Option<Observation>>,
+}
+
+/// Several copies of an environment stepped together, each reset as soon
+/// as its episode ends. They step in parallel, spread over the host's cores.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    next_seed: u64,
+}
+
+impl VecEnv {
+    pub fn new(
+        rom: &[u8],
+        game: Game,
+        config: EnvConfig,
+        count: usize,
+    ) -> Result<VecEnv, EnvError> {
+        let envs = (0..count)
+            .map(|_| Env::new(rom, game.clone(), config.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(VecEnv { envs, next_seed: 0 })
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Reset every environment, the Nth with seed `seed + N`. Later episodes
+    /// take the seeds after those.
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.next_seed = seed;
+        let mut observations = Vec::with_capacity(self.envs.len());
+        for env in &mut self.envs {
+            observations.push(env.reset(self.next_seed));
+            self.next_seed += 1;
+        }
+        observations
+    }
+
+    /// Step each environment with its action from `actions`, one each
+    pub fn step(&mut self, actions: &[usize]) -> Result<VecStep, EnvError> {
+        if actions.len() != self.envs.len() {
+            return Err(EnvError::WrongActionCount {
+                given: actions.len(),
+                envs: self.envs.len(),
+            });
+        }
+        // Check every action first, so a bad one steps no environment
+        for (env, &action) in self.envs.iter().zip(actions) {
+            let actions = env.action_count();
+            if action >= actions {
+                return Err(EnvError::InvalidAction { action, actions });
+            }
+        }
+
+        let mut step = VecStep {
+            observations: Vec::with_capacity(self.envs.len()),
+            rewards: Vec::with_capacity(self.envs.len()),
+            dones: Vec::with_capacity(self.envs.len()),
+            infos: Vec::with_capacity(self.envs.len()),
+            final_observations: Vec::with_capacity(self.envs.len()),
+        };
+        // Finished episodes are reset after every environment has stepped,
+        // in order, so the seeds they get don't depend on the threads
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = self.envs.len().div_ceil(threads).max(1);
+        let steps: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .envs
+                .chunks_mut(chunk)
+                .zip(actions.chunks(chunk))
+                .map(|(envs, actions)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .zip(actions)
+                            .map(|(env, &action)| env.step(action))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        for (env, result) in self.envs.iter_mut().zip(steps) {
+            let (mut observation, reward, done, info) = result?;
+            let mut final_observation = None;
+            if done {
+                final_observation = Some(observation);
+                observation = env.reset(self.next_seed);
+                self.next_seed += 1;
+            }
+            step.observations.push(observation);
+            step.rewards.push(reward);
+            step.dones.push(done);
+            step.infos.push(info);
+            step.final_observations.push(final_observation);
+        }
+        Ok(step)
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+}
@@ -0,0 +1,358 @@
+//! C bindings, built with the `ffi` feature as a static or shared library:
+//!
+//! ```text
+//! cargo rustc --lib --release --crate-type staticlib --features ffi
+//! cargo rustc --lib --release --crate-type cdylib --features ffi
+//! ```
+//!
+//! include/chip8.h is generated from this file by cbindgen, and checked in
+//! so C users don't need it. After changing the API, regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/chip8.h src/ffi.rs`.
+//!
+//! ```c
+//! chip8_t *chip8 = chip8_create(seed);
+//! if (chip8_load_rom(chip8, rom, rom_len) != CHIP8_OK) { ... }
+//! // every frame:
+//! chip8_run_frame(chip8);
+//! const uint8_t *pixels = chip8_framebuffer(chip8);
+//! ...
+//! chip8_destroy(chip8);
+//! ```
+//!
+//! Pointers passed in must be null or valid for the length given. A null
+//! machine is an error (`CHIP8_ERROR_NULL`) rather than a crash. Machines
+//! aren't thread-safe: use each one only on the thread that created it.
+
+// The names are what C sees
+#![allow(non_camel_case_types, clippy::missing_safety_doc)]
+
+use core::ffi::c_char;
+use core::{ptr, slice};
+
+use crate::chip8::{Chip8, Chip8Error};
+use crate::cpu::STACK_SIZE;
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::rng::Rng;
+use crate::state::StateError;
+
+/// Pixels per row of the framebuffer
+pub const CHIP8_DISPLAY_WIDTH: usize = 64;
+/// Rows in the framebuffer
+pub const CHIP8_DISPLAY_HEIGHT: usize = 32;
+/// Registers V0 to VF
+pub const CHIP8_REGISTER_COUNT: u8 = 16;
+/// Return addresses the stack holds
+pub const CHIP8_STACK_SIZE: u8 = 16;
+
+// cbindgen can't follow the constants into the display module
+const _: () = assert!(CHIP8_DISPLAY_WIDTH == DISPLAY_WIDTH);
+const _: () = assert!(CHIP8_DISPLAY_HEIGHT == DISPLAY_HEIGHT);
+const _: () = assert!(CHIP8_STACK_SIZE as usize == STACK_SIZE);
+
+/// A machine, from `chip8_create`
+pub struct chip8_t {
+    chip8: Chip8,
+}
+
+/// What went wrong. Mirrors `Chip8Error` and `StateError`, plus the ways a
+/// C caller can misuse the API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum chip8_error_t {
+    CHIP8_OK = 0,
+    /// A required pointer was null
+    CHIP8_ERROR_NULL,
+    /// The ROM doesn't fit between the program start and the end of memory
+    CHIP8_ERROR_ROM_TOO_LARGE,
+    /// Keypad keys are 0-F
+    CHIP8_ERROR_INVALID_KEY,
+    /// Registers are V0-VF
+    CHIP8_ERROR_INVALID_REGISTER,
+    /// The buffer can't hold the save state; its size has been written back
+    CHIP8_ERROR_BUFFER_TOO_SMALL,
+    CHIP8_ERROR_STATE_BAD_MAGIC,
+    CHIP8_ERROR_STATE_UNSUPPORTED_VERSION,
+    CHIP8_ERROR_STATE_BAD_CHECKSUM,
+    CHIP8_ERROR_STATE_TRUNCATED,
+    CHIP8_ERROR_STATE_INVALID,
+    /// The return stack holds at most `CHIP8_STACK_SIZE` addresses
+    CHIP8_ERROR_INVALID_STACK_DEPTH,
+}
+
+use chip8_error_t::*;
+
+impl From<Chip8Error> for chip8_error_t {
+    fn from(error: Chip8Error) -> chip8_error_t {
+        match error {
+            Chip8Error::RomTooLarge { .. } => CHIP8_ERROR_ROM_TOO_LARGE,
+            Chip8Error::InvalidKey(_) => CHIP8_ERROR_INVALID_KEY,
+        }
+    }
+}
+
+impl From<StateError> for chip8_error_t {
+    fn from(error: StateError) -> chip8_error_t {
+        match error {
+            StateError::BadMagic => CHIP8_ERROR_STATE_BAD_MAGIC,
+            StateError::UnsupportedVersion(_) => CHIP8_ERROR_STATE_UNSUPPORTED_VERSION,
+            StateError::BadChecksum => CHIP8_ERROR_STATE_BAD_CHECKSUM,
+            StateError::Truncated => CHIP8_ERROR_STATE_TRUNCATED,
+            StateError::Invalid(_) => CHIP8_ERROR_STATE_INVALID,
+        }
+    }
+}
+
+fn status<E: Into<chip8_error_t>>(result: Result<(), E>) -> chip8_error_t {
+    match result {
+        Ok(()) => CHIP8_OK,
+        Err(e) => e.into(),
+    }
+}
+
+/// A description of the error, as a static string
+#[no_mangle]
+pub extern "C" fn chip8_error_message(error: chip8_error_t) -> *const c_char {
+    let message = match error {
+        CHIP8_OK => c"no error",
+        CHIP8_ERROR_NULL => c"null pointer",
+        CHIP8_ERROR_ROM_TOO_LARGE => c"ROM doesn't fit in memory",
+        CHIP8_ERROR_INVALID_KEY => c"no such keypad key",
+        CHIP8_ERROR_INVALID_REGISTER => c"no such register",
+        CHIP8_ERROR_BUFFER_TOO_SMALL => c"buffer too small",
+        CHIP8_ERROR_STATE_BAD_MAGIC => c"not a save state",
+        CHIP8_ERROR_STATE_UNSUPPORTED_VERSION => c"unsupported save state version",
+        CHIP8_ERROR_STATE_BAD_CHECKSUM => c"save state checksum mismatch",
+        CHIP8_ERROR_STATE_TRUNCATED => c"save state is truncated",
+        CHIP8_ERROR_STATE_INVALID => c"invalid save state",
+        CHIP8_ERROR_INVALID_STACK_DEPTH => c"return stack too deep",
+    };
+    message.as_ptr()
+}
+
+/// A new machine with its RNG seeded from `seed` and tracing off. Free it
+/// with `chip8_destroy`.
+#[no_mangle]
+pub extern "C" fn chip8_create(seed: u64) -> *mut chip8_t {
+    let chip8 = Chip8::builder().rng(Rng::new(seed)).build();
+    Box::into_raw(Box::new(chip8_t { chip8 }))
+}
+
+/// Free a machine. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut chip8_t) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(
+    chip8: *mut chip8_t,
+    rom: *const u8,
+    len: usize,
+) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if rom.is_null() {
+        return CHIP8_ERROR_NULL;
+    }
+    status(chip8.chip8.load_program(slice::from_raw_parts(rom, len)))
+}
+
+/// Run one instruction. Returns false if the program stopped.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut chip8_t) -> bool {
+    match chip8.as_mut() {
+        Some(chip8) => chip8.chip8.step(),
+        None => false,
+    }
+}
+
+/// Run one 60 Hz frame. Returns false if the program stopped.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut chip8_t) -> bool {
+    match chip8.as_mut() {
+        Some(chip8) => chip8.chip8.run_frame(),
+        None => false,
+    }
+}
+
+/// The `CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT` pixels, one byte (0 or 1)
+/// each, row by row. The pointer stays valid for the life of the machine.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const chip8_t) -> *const u8 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.chip8.framebuffer().as_ptr() as *const u8,
+        None => ptr::null(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(
+    chip8: *mut chip8_t,
+    key: u8,
+    pressed: bool,
+) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    status(chip8.chip8.set_key(key, pressed))
+}
+
+/// Read VX into `value`
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_register(
+    chip8: *const chip8_t,
+    x: u8,
+    value: *mut u8,
+) -> chip8_error_t {
+    let (Some(chip8), Some(value)) = (chip8.as_ref(), value.as_mut()) else {
+        return CHIP8_ERROR_NULL;
+    };
+    if x >= CHIP8_REGISTER_COUNT {
+        return CHIP8_ERROR_INVALID_REGISTER;
+    }
+    *value = chip8.chip8.cpu().read_register(x as usize);
+    CHIP8_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_register(
+    chip8: *mut chip8_t,
+    x: u8,
+    value: u8,
+) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if x >= CHIP8_REGISTER_COUNT {
+        return CHIP8_ERROR_INVALID_REGISTER;
+    }
+    chip8.chip8.cpu_mut().write_register(x as usize, value);
+    CHIP8_OK
+}
+
+/// The program counter, or 0 for a null machine
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_pc(chip8: *const chip8_t) -> u16 {
+    chip8
+        .as_ref()
+        .map_or(0, |chip8| chip8.chip8.cpu().read_pc())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_pc(chip8: *mut chip8_t, pc: u16) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    chip8.chip8.cpu_mut().write_pc(pc);
+    CHIP8_OK
+}
+
+/// The I register, or 0 for a null machine
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_index(chip8: *const chip8_t) -> u16 {
+    chip8.as_ref().map_or(0, |chip8| chip8.chip8.cpu().read_i())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_index(chip8: *mut chip8_t, index: u16) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    chip8.chip8.cpu_mut().write_i(index);
+    CHIP8_OK
+}
+
+/// The depth of the return stack, or 0 for a null machine
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_sp(chip8: *const chip8_t) -> u8 {
+    chip8
+        .as_ref()
+        .map_or(0, |chip8| chip8.chip8.cpu().read_sp() as u8)
+}
+
+/// Set the depth of the return stack; the addresses on it are kept
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_sp(chip8: *mut chip8_t, sp: u8) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if sp > CHIP8_STACK_SIZE {
+        return CHIP8_ERROR_INVALID_STACK_DEPTH;
+    }
+    chip8.chip8.cpu_mut().write_sp(sp as usize);
+    CHIP8_OK
+}
+
+/// The delay timer, or 0 for a null machine
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_delay_timer(chip8: *const chip8_t) -> u8 {
+    chip8
+        .as_ref()
+        .map_or(0, |chip8| chip8.chip8.cpu().read_delay_timer())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_delay_timer(chip8: *mut chip8_t, value: u8) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    chip8.chip8.cpu_mut().write_delay_timer(value);
+    CHIP8_OK
+}
+
+/// The sound timer, or 0 for a null machine
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_sound_timer(chip8: *const chip8_t) -> u8 {
+    chip8
+        .as_ref()
+        .map_or(0, |chip8| chip8.chip8.cpu().read_sound_timer())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_sound_timer(chip8: *mut chip8_t, value: u8) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    chip8.chip8.cpu_mut().write_sound_timer(value);
+    CHIP8_OK
+}
+
+/// Save state into `buffer`, which holds `*len` bytes. `*len` is set to the
+/// size of the state; if the buffer is null or too small nothing is copied
+/// and `CHIP8_ERROR_BUFFER_TOO_SMALL` is returned, so callers can ask for
+/// the size first.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(
+    chip8: *const chip8_t,
+    buffer: *mut u8,
+    len: *mut usize,
+) -> chip8_error_t {
+    let (Some(chip8), Some(len)) = (chip8.as_ref(), len.as_mut()) else {
+        return CHIP8_ERROR_NULL;
+    };
+    let state = chip8.chip8.save_state();
+    let capacity = core::mem::replace(len, state.len());
+    if buffer.is_null() || capacity < state.len() {
+        return CHIP8_ERROR_BUFFER_TOO_SMALL;
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+    CHIP8_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut chip8_t,
+    state: *const u8,
+    len: usize,
+) -> chip8_error_t {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR_NULL;
+    };
+    if state.is_null() {
+        return CHIP8_ERROR_NULL;
+    }
+    status(chip8.chip8.load_state(slice::from_raw_parts(state, len)))
+}
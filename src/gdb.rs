@@ -0,0 +1,407 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chip8::Chip8;
+use crate::debugger::{Breakpoint, Debugger, Step, StopReason};
+use crate::memory::Watchpoint;
+use crate::pacer::FRAME_RATE;
+
+/// Register layout of the `g`/`G` packets, in order: V0-VF (8 bit), I and PC
+/// (16 bit, little endian), then SP (stack depth), DT and ST (8 bit)
+const REGISTERS_LEN: usize = 16 + 2 + 2 + 1 + 1 + 1;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>"#;
+
+/// GDB remote serial protocol server exposing the CPU registers and the
+/// 4 KiB memory as the target's address space
+pub struct GdbServer {
+    listener: TcpListener,
+}
+
+impl GdbServer {
+    /// Listen on localhost; port 0 picks a free port
+    pub fn bind(port: u16) -> io::Result<GdbServer> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        Ok(GdbServer { listener })
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Wait for a debugger to connect and serve it until it detaches
+    pub fn serve(&self, chip8: &mut Chip8) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        // GDB has no reverse-step here, so don't pay for history
+        let mut debugger = Debugger::new();
+        debugger.set_history(false);
+        let mut session = Session {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+            debugger,
+            chip8,
+        };
+        session.run()
+    }
+}
+
+enum Reply {
+    Packet(String),
+    Detach(String),
+}
+
+struct Session<'a> {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+    debugger: Debugger,
+    chip8: &'a mut Chip8,
+}
+
+impl Session<'_> {
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            match self.handle(&packet) {
+                Reply::Packet(reply) => self.write_packet(&reply)?,
+                Reply::Detach(reply) => return self.write_packet(&reply),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the next `$data#checksum` packet, acknowledging it.
+    /// Returns None when the client disconnects.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            // Acks and interrupts outside of `c` are ignored
+            if byte[0] != b'$' {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok());
+            if expected == Some(checksum_of(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+
+    fn handle(&mut self, packet: &str) -> Reply {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "s" => self.resume(Some(1)),
+            "c" => self.resume(None),
+            "Z" => self.insert_point(args),
+            "z" => self.remove_point(args),
+            "q" => self.query(args),
+            "H" | "T" => "OK".to_string(),
+            "k" => return Reply::Detach(String::new()),
+            "D" => return Reply::Detach("OK".to_string()),
+            // Empty reply: not supported
+            _ => String::new(),
+        };
+        Reply::Packet(reply)
+    }
+
+    fn query(&self, args: &str) -> String {
+        if args.starts_with("Supported") {
+            "PacketSize=1000;qXfer:features:read+".to_string()
+        } else if args == "Attached" {
+            "1".to_string()
+        } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            match parse_address_length(range) {
+                Some((offset, length)) => {
+                    let start = offset.min(TARGET_XML.len());
+                    match start.checked_add(length) {
+                        Some(end) => {
+                            let end = end.min(TARGET_XML.len());
+                            let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+                            format!("{}{}", prefix, &TARGET_XML[start..end])
+                        }
+                        None => "E01".to_string(),
+                    }
+                }
+                None => "E01".to_string(),
+            }
+        } else {
+            String::new()
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        let cpu = self.chip8.cpu();
+        let mut bytes = Vec::with_capacity(REGISTERS_LEN);
+        bytes.extend((0..16).map(|x| cpu.read_register(x)));
+        bytes.extend(cpu.read_i().to_le_bytes());
+        bytes.extend(cpu.read_pc().to_le_bytes());
+        bytes.push(cpu.read_sp() as u8);
+        bytes.push(cpu.read_delay_timer());
+        bytes.push(cpu.read_sound_timer());
+        encode_hex(&bytes)
+    }
+
+    /// SP is read-only: the return stack contents can't be set through it
+    fn write_registers(&mut self, args: &str) -> String {
+        let bytes = match decode_hex(args) {
+            Some(bytes) if bytes.len() >= REGISTERS_LEN => bytes,
+            _ => return "E01".to_string(),
+        };
+        let cpu = self.chip8.cpu_mut();
+        for (x, value) in bytes[..16].iter().enumerate() {
+            cpu.write_register(x, *value);
+        }
+        cpu.write_i(u16::from_le_bytes([bytes[16], bytes[17]]));
+        cpu.write_pc(u16::from_le_bytes([bytes[18], bytes[19]]));
+        cpu.write_delay_timer(bytes[21]);
+        cpu.write_sound_timer(bytes[22]);
+        "OK".to_string()
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let memory = self.chip8.memory();
+        match memory_range(parse_address_length(args), memory.len()) {
+            Some((address, end)) => {
+                let bytes: Vec<u8> = (address..end).map(|a| memory.peek_byte(a)).collect();
+                encode_hex(&bytes)
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let (range, data) = match args.split_once(':') {
+            Some(parts) => parts,
+            None => return "E01".to_string(),
+        };
+        let memory = self.chip8.memory_mut();
+        let length = memory.len();
+        match (
+            memory_range(parse_address_length(range), length),
+            decode_hex(data),
+        ) {
+            (Some((address, end)), Some(bytes)) if bytes.len() == end - address => {
+                for (offset, value) in bytes.into_iter().enumerate() {
+                    memory.write_byte(address + offset, value);
+                }
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    /// `Z0,addr,kind` software breakpoint, `Z2,addr,len` write watchpoint
+    fn insert_point(&mut self, args: &str) -> String {
+        match parse_point(args) {
+            Some((0, address, _)) => {
+                if self.find_breakpoint(address).is_none() {
+                    self.debugger.add_breakpoint(Breakpoint::Address(address));
+                }
+                "OK".to_string()
+            }
+            Some((2, address, length)) if length > 0 => {
+                self.chip8.memory_mut().add_watchpoint(Watchpoint {
+                    start: address,
+                    end: address.saturating_add(length - 1),
+                    on_read: false,
+                    on_write: true,
+                });
+                "OK".to_string()
+            }
+            Some(_) => String::new(),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn remove_point(&mut self, args: &str) -> String {
+        match parse_point(args) {
+            Some((0, address, _)) => {
+                if let Some(index) = self.find_breakpoint(address) {
+                    self.debugger.remove_breakpoint(index);
+                }
+                "OK".to_string()
+            }
+            Some((2, address, length)) if length > 0 => {
+                let memory = self.chip8.memory_mut();
+                let index = memory.watchpoints().iter().position(|w| {
+                    w.start == address && w.end == address.saturating_add(length - 1)
+                });
+                if let Some(index) = index {
+                    memory.remove_watchpoint(index);
+                }
+                "OK".to_string()
+            }
+            Some(_) => String::new(),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn find_breakpoint(&self, address: u16) -> Option<usize> {
+        self.debugger
+            .breakpoints()
+            .iter()
+            .position(|b| matches!(b, Breakpoint::Address(a) if *a == address))
+    }
+
+    /// Run `count` instructions, or until a breakpoint when None, then
+    /// build the stop reply. The timers tick once a frame's instructions
+    /// have run; running freely keeps to real time, a frame at a time.
+    fn resume(&mut self, count: Option<usize>) -> String {
+        let mut executed = 0;
+        let per_frame = self.chip8.instructions_per_frame().max(1) as usize;
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut next_frame = Instant::now() + frame;
+        self.debugger.resume_at(self.chip8.cpu().read_pc());
+
+        loop {
+            if count == Some(executed) {
+                return "S05".to_string();
+            }
+            match self.debugger.step(self.chip8) {
+                Step::Running => executed += 1,
+                // The program stopped on a fault, reported as a segfault
+                Step::Halted => return "S0B".to_string(),
+                Step::Stopped(stop) => {
+                    return match stop.reason {
+                        StopReason::Watchpoint(hit) => format!("T05watch:{:x};", hit.address),
+                        StopReason::Breakpoint(_) => "S05".to_string(),
+                    };
+                }
+            }
+
+            if executed % per_frame == 0 {
+                self.chip8.tick_timers();
+                if count.is_none() {
+                    // Checking for a Ctrl-C once a frame is soon enough
+                    if self.interrupted() {
+                        return "S02".to_string();
+                    }
+                    let now = Instant::now();
+                    if next_frame > now {
+                        thread::sleep(next_frame - now);
+                    }
+                    next_frame = next_frame.max(now) + frame;
+                }
+            }
+        }
+    }
+
+    /// Check whether the client sent a Ctrl-C (0x03) without blocking
+    fn interrupted(&mut self) -> bool {
+        if !self.reader.buffer().is_empty() {
+            return self.reader.buffer()[0] == 0x03 && self.reader.read(&mut [0]).is_ok();
+        }
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let mut byte = [0];
+        let interrupted = matches!(self.stream.peek(&mut byte), Ok(1) if byte[0] == 0x03);
+        let _ = self.stream.set_nonblocking(false);
+        if interrupted {
+            let _ = self.reader.read(&mut byte);
+        }
+        interrupted
+    }
+}
+
+/// The `address..end` a parsed `addr,length` covers, if it is all in memory
+fn memory_range(range: Option<(usize, usize)>, memory_len: usize) -> Option<(usize, usize)> {
+    let (address, length) = range?;
+    let end = address.checked_add(length)?;
+    (end <= memory_len).then_some((address, end))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parse `addr,length` (both hex)
+fn parse_address_length(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+/// Parse the `type,addr,kind` arguments of Z/z packets
+fn parse_point(text: &str) -> Option<(u8, u16, u16)> {
+    let mut parts = text.splitn(3, ',');
+    let kind = parts.next()?.parse().ok()?;
+    let address = u16::from_str_radix(parts.next()?, 16).ok()?;
+    // The kind/length may be followed by `;cond_list` extensions we don't support
+    let length = parts.next()?.split(';').next()?;
+    Some((kind, address, u16::from_str_radix(length, 16).ok()?))
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use chip_8_emulator::keymap::HostKey;
+
+pub enum KeyEvent {
+    Key(HostKey),
+    /// Ctrl-C, delivered as a key since the terminal doesn't send signals
+    Interrupt,
+}
+
+/// Reads single key presses from the terminal.
+///
+/// Terminals only report presses (repeated while a key is held), never
+/// releases, so callers treat a key as held until it stops repeating.
+pub struct Keyboard {
+    events: Receiver<KeyEvent>,
+    saved_mode: Option<String>,
+}
+
+impl Keyboard {
+    /// Put the terminal in unbuffered, no-echo mode and start reading keys
+    pub fn open() -> io::Result<Keyboard> {
+        let saved_mode = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            let mut bytes = io::stdin().lock().bytes().map_while(Result::ok);
+            while let Some(byte) = bytes.next() {
+                let event = match byte {
+                    0x03 => Some(KeyEvent::Interrupt),
+                    0x1B => escape_sequence(&mut bytes).map(KeyEvent::Key),
+                    b'\r' | b'\n' => Some(KeyEvent::Key(HostKey::Enter)),
+                    b'\t' => Some(KeyEvent::Key(HostKey::Tab)),
+                    b' ' => Some(KeyEvent::Key(HostKey::Space)),
+                    0x7F | 0x08 => Some(KeyEvent::Key(HostKey::Backspace)),
+                    b if b.is_ascii_graphic() => Some(KeyEvent::Key(HostKey::Char(
+                        (b as char).to_ascii_lowercase(),
+                    ))),
+                    0xC0.. => utf8_char(byte, &mut bytes).map(|c| KeyEvent::Key(HostKey::Char(c))),
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Keyboard {
+            events,
+            saved_mode: Some(saved_mode.trim().to_string()),
+        })
+    }
+
+    /// Key presses since the last call
+    pub fn poll(&self) -> Vec<KeyEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        if let Some(mode) = self.saved_mode.take() {
+            let _ = stty(&[&mode]);
+        }
+    }
+}
+
+/// Decode the rest of an `ESC [ ...` or `ESC O ...` sequence
+// A character typed as several bytes of UTF-8, such as é on an AZERTY
+// keyboard, lowercased
+fn utf8_char(first: u8, bytes: &mut impl Iterator<Item = u8>) -> Option<char> {
+    let length = match first {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    };
+    let mut buffer = [first, 0, 0, 0];
+    for byte in &mut buffer[1..length] {
+        *byte = bytes.next()?;
+    }
+    let c = std::str::from_utf8(&buffer[..length])
+        .ok()?
+        .chars()
+        .next()?;
+    c.to_lowercase().next()
+}
+
+fn escape_sequence(bytes: &mut impl Iterator<Item = u8>) -> Option<HostKey> {
+    let kind = bytes.next()?;
+    if kind != b'[' && kind != b'O' {
+        return None;
+    }
+    let mut parameter = String::new();
+    let last = loop {
+        match bytes.next()? {
+            b if b.is_ascii_digit() || b == b';' => parameter.push(b as char),
+            b => break b,
+        }
+    };
+
+    match (last, parameter.as_str()) {
+        (b'A', _) => Some(HostKey::Up),
+        (b'B', _) => Some(HostKey::Down),
+        (b'C', _) => Some(HostKey::Right),
+        (b'D', _) => Some(HostKey::Left),
+        (b'P', _) => Some(HostKey::F(1)),
+        (b'Q', _) => Some(HostKey::F(2)),
+        (b'R', _) => Some(HostKey::F(3)),
+        (b'S', _) => Some(HostKey::F(4)),
+        (b'~', "15") => Some(HostKey::F(5)),
+        (b'~', "17") => Some(HostKey::F(6)),
+        (b'~', "18") => Some(HostKey::F(7)),
+        (b'~', "19") => Some(HostKey::F(8)),
+        (b'~', "20") => Some(HostKey::F(9)),
+        (b'~', "21") => Some(HostKey::F(10)),
+        (b'~', "23") => Some(HostKey::F(11)),
+        (b'~', "24") => Some(HostKey::F(12)),
+        _ => None,
+    }
+}
+
+/// Run stty on the terminal attached to stdin
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stdin is not a terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
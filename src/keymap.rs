@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// A key on the host keyboard, as seen through the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HostKey {
+    /// A printable character, letters always lowercase
+    Char(char),
+    Space,
+    Enter,
+    Tab,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    F(u8),
+}
+
+impl HostKey {
+    /// Parse a key name from a keymap file: a single character, or one of
+    /// space, enter, tab, backspace, up, down, left, right, f1-f12
+    pub fn parse(name: &str) -> Result<HostKey, String> {
+        let lower = name.to_lowercase();
+        let mut chars = lower.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if !c.is_whitespace() && !c.is_control() {
+                return Ok(HostKey::Char(c));
+            }
+        }
+        match lower.as_str() {
+            "space" => Ok(HostKey::Space),
+            "enter" | "return" => Ok(HostKey::Enter),
+            "tab" => Ok(HostKey::Tab),
+            "backspace" => Ok(HostKey::Backspace),
+            "up" => Ok(HostKey::Up),
+            "down" => Ok(HostKey::Down),
+            "left" => Ok(HostKey::Left),
+            "right" => Ok(HostKey::Right),
+            _ => match lower.strip_prefix('f').map(str::parse::<u8>) {
+                Some(Ok(n)) if (1..=12).contains(&n) => Ok(HostKey::F(n)),
+                _ => Err(format!("unknown host key '{}'", name)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for HostKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostKey::Char(c) => write!(f, "{}", c),
+            HostKey::Space => write!(f, "space"),
+            HostKey::Enter => write!(f, "enter"),
+            HostKey::Tab => write!(f, "tab"),
+            HostKey::Backspace => write!(f, "backspace"),
+            HostKey::Up => write!(f, "up"),
+            HostKey::Down => write!(f, "down"),
+            HostKey::Left => write!(f, "left"),
+            HostKey::Right => write!(f, "right"),
+            HostKey::F(n) => write!(f, "f{}", n),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// The conventional 1234/QWER/ASDF/ZXCV block
+    Qwerty,
+    /// The keys in the same places on an AZERTY keyboard: &é"'/AZER/QSDF/WXCV.
+    /// The top row also answers to 1234, with Shift or Caps Lock.
+    Azerty,
+    /// The numeric keypad, laid out like the hex keypad
+    Numpad,
+}
+
+// Host keys for the CHIP-8 keypad, row by row as it appears on the COSMAC VIP:
+//   1 2 3 C
+//   4 5 6 D
+//   7 8 9 E
+//   A 0 B F
+const KEYPAD: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+impl Layout {
+    pub fn parse(name: &str) -> Result<Layout, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "numpad" => Ok(Layout::Numpad),
+            _ => Err(format!("unknown layout '{}'", name)),
+        }
+    }
+
+    // Host keys and the CHIP-8 keys they're bound to
+    fn bindings(&self) -> impl Iterator<Item = (HostKey, u8)> {
+        let (keys, shifted) = match self {
+            Layout::Qwerty => ("1234qwerasdfzxcv", ""),
+            Layout::Azerty => ("&é\"'azerqsdfwxcv", "1234"),
+            Layout::Numpad => ("789/456*123-0.\n+", ""),
+        };
+        let keys = keys.chars().zip(KEYPAD);
+        let shifted = shifted.chars().zip(KEYPAD);
+        keys.chain(shifted).map(|(c, key)| match c {
+            '\n' => (HostKey::Enter, key),
+            c => (HostKey::Char(c), key),
+        })
+    }
+}
+
+/// Maps host keys onto the 16-key hex keypad
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: HashMap<HostKey, u8>,
+}
+
+impl Keymap {
+    pub fn new(layout: Layout) -> Keymap {
+        let bindings = layout.bindings().collect();
+        Keymap { bindings }
+    }
+
+    pub fn bind(&mut self, host: HostKey, key: u8) {
+        self.bindings.insert(host, key);
+    }
+
+    /// The CHIP-8 key (0-F) bound to a host key
+    pub fn key_for(&self, host: HostKey) -> Option<u8> {
+        self.bindings.get(&host).copied()
+    }
+
+    /// Every binding, in no particular order
+    pub fn bindings(&self) -> impl Iterator<Item = (HostKey, u8)> + '_ {
+        self.bindings.iter().map(|(&host, &key)| (host, key))
+    }
+
+    /// Load a keymap file for a ROM. The file is a small subset of TOML
+    /// (also readable as INI):
+    ///
+    /// ```toml
+    /// layout = "azerty"     # base layout, qwerty if omitted
+    ///
+    /// [keys]                # host key = CHIP-8 key, for every ROM
+    /// space = "5"
+    ///
+    /// [rom.PONG]            # only when the ROM file is named PONG
+    /// layout = "qwerty"
+    /// up = "1"
+    /// down = "4"
+    /// ```
+    pub fn parse(text: &str, rom_name: &str) -> Result<Keymap, String> {
+        let mut layout = Layout::Qwerty;
+        let mut rom_layout = None;
+        let mut bindings = Vec::new();
+        let mut rom_bindings = Vec::new();
+        let mut seen: Vec<(String, HostKey)> = Vec::new();
+        let mut section = String::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = unquote(name.trim()).to_string();
+                if section != "keys" && !section.starts_with("rom.") {
+                    return Err(error(format!("unknown section [{}]", section)));
+                }
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected 'key = value', found '{}'", line)))?;
+            let (name, value) = (unquote(name.trim()), unquote(value.trim()));
+
+            if name == "layout" && section != "keys" {
+                let parsed = Layout::parse(value).map_err(error)?;
+                if section.is_empty() {
+                    layout = parsed;
+                } else if rom_matches(&section, rom_name) {
+                    rom_layout = Some(parsed);
+                }
+                continue;
+            }
+            if section.is_empty() {
+                return Err(error(format!("unknown setting '{}'", name)));
+            }
+
+            let host = HostKey::parse(name).map_err(error)?;
+            let key = parse_keypad_key(value).map_err(error)?;
+            if seen.contains(&(section.clone(), host)) {
+                return Err(error(format!("duplicate binding for '{}'", host)));
+            }
+            seen.push((section.clone(), host));
+
+            if section == "keys" {
+                bindings.push((host, key));
+            } else if rom_matches(&section, rom_name) {
+                rom_bindings.push((host, key));
+            }
+        }
+
+        let mut keymap = Keymap::new(rom_layout.unwrap_or(layout));
+        for (host, key) in bindings.into_iter().chain(rom_bindings) {
+            keymap.bind(host, key);
+        }
+        Ok(keymap)
+    }
+}
+
+fn rom_matches(section: &str, rom_name: &str) -> bool {
+    section
+        .strip_prefix("rom.")
+        .is_some_and(|name| unquote(name).eq_ignore_ascii_case(rom_name))
+}
+
+/// Parse a keypad key, 0-F with an optional 0x prefix
+pub fn parse_keypad_key(value: &str) -> Result<u8, String> {
+    let digits = value
+        .strip_prefix("0x")
+        .or(value.strip_prefix("0X"))
+        .unwrap_or(value);
+    match u8::from_str_radix(digits, 16) {
+        Ok(key) if key <= 0xF => Ok(key),
+        _ => Err(format!("unknown CHIP-8 key '{}', expected 0-F", value)),
+    }
+}
+
+/// Drop a `#` or `;` comment, unless it's inside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+}
@@ -0,0 +1,92 @@
+//! A CHIP-8 emulator core and the tools built around it.
+//!
+//! The API most frontends need is re-exported here: build a [`Chip8`],
+//! load a ROM, then call [`Chip8::run_frame`] 60 times a second, feeding
+//! keys in with [`Chip8::set_key`] and drawing [`Chip8::framebuffer`].
+//!
+//! ```
+//! use chip_8_emulator::{Chip8, Rng};
+//!
+//! let mut chip8 = Chip8::builder().rng(Rng::new(0)).build();
+//! // Draw the font's 0 at (0, 0)
+//! chip8.load_program(&[0xD0, 0x05]).unwrap();
+//! chip8.run_frame();
+//! assert_eq!(chip8.framebuffer()[0][..4], [1, 1, 1, 1]);
+//! ```
+//!
+//! Without the default `std` feature the crate is `no_std`: the core
+//! ([`Chip8`], [`cpu`], [`memory`], [`display`]) runs in fixed memory, with
+//! randomness, trace output and frame timing supplied by the host through
+//! [`RandomSource`], [`Trace`] and [`Clock`]. The `alloc` feature adds save
+//! states, the recompiler, the decode cache and watchpoints back.
+//!
+//! The `wasm` feature adds JavaScript bindings (see `wasm`), the `ffi`
+//! feature a C API (see `ffi` and include/chip8.h), the `libretro` feature a
+//! libretro core (see `libretro`) and the `python` feature a Python module
+//! (see `python`), built with maturin. The library itself is only an rlib,
+//! so `no_std` users can link it; the C and JavaScript builds ask for a
+//! cdylib or staticlib with `cargo rustc --crate-type`, as each module shows.
+//!
+//! No ROM or save state can panic the emulator: bad programs stop with a
+//! [`Fault`] and bad states fail to load. The cargo-fuzz targets in fuzz/
+//! check this, with `cargo +nightly fuzz run load_rom` (or `execute`, or
+//! `load_state`).
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod bench;
+pub mod chip8;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod debugger;
+pub mod decode;
+pub mod display;
+#[cfg(feature = "std")]
+pub mod env;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod gdb;
+#[cfg(feature = "std")]
+pub mod keymap;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "std")]
+pub mod lockstep;
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod movie;
+pub mod pacer;
+pub mod platform;
+#[cfg(feature = "alloc")]
+pub mod png;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod recompiler;
+#[cfg(feature = "alloc")]
+pub mod rewind;
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod script;
+pub mod state;
+pub mod timing;
+pub mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use chip8::{
+    Chip8, Chip8Builder, Chip8Error, Framebuffer, DEFAULT_INSTRUCTIONS_PER_FRAME, STATE_SIZE,
+};
+pub use cpu::Fault;
+pub use display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+pub use pacer::Clock;
+pub use platform::{Platform, Quirks};
+pub use recompiler::Engine;
+pub use rng::{RandomSource, Rng};
+pub use state::StateError;
+pub use timing::Timing;
+pub use trace::Trace;
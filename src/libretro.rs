@@ -0,0 +1,577 @@
+//! A libretro core, built with the `libretro` feature as a shared library so
+//! RetroArch and other libretro frontends can load it:
+//!
+//! ```text
+//! cargo rustc --lib --release --crate-type cdylib --features libretro
+//! retroarch -L target/release/libchip_8_emulator.so data/PONG
+//! ```
+//!
+//! The display goes out as XRGB8888 in the chosen palette, and the buzzer as
+//! a square wave. The keypad is on the keyboard's 1234/QWER/ASDF/ZXCV block,
+//! and partly on the joypad: the D-pad is 2/8/4/6 and A is 5. The RNG has a
+//! fixed seed, so runs are repeatable for netplay and run-ahead.
+//!
+//! The core lives in a static behind a mutex, so it doesn't matter which
+//! thread the frontend calls it from.
+
+// The signatures are libretro's, documented in its libretro.h
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::{ptr, slice};
+
+use crate::chip8::{Chip8, DEFAULT_INSTRUCTIONS_PER_FRAME, STATE_SIZE};
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::keymap::{HostKey, Keymap, Layout};
+use crate::pacer::FRAME_RATE;
+use crate::platform::{Platform, Quirks};
+use crate::rng::Rng;
+
+const API_VERSION: c_uint = 1;
+
+const ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const DEVICE_JOYPAD: c_uint = 1;
+const DEVICE_KEYBOARD: c_uint = 3;
+
+const REGION_NTSC: c_uint = 0;
+
+// RETRO_DEVICE_ID_JOYPAD_* and the keypad key each is bound to
+const JOYPAD: [(c_uint, u8); 12] = [
+    (4, 0x2),  // up
+    (5, 0x8),  // down
+    (6, 0x4),  // left
+    (7, 0x6),  // right
+    (8, 0x5),  // A
+    (0, 0x0),  // B
+    (9, 0x1),  // X
+    (1, 0xC),  // Y
+    (10, 0xA), // L
+    (11, 0xB), // R
+    (2, 0xE),  // select
+    (3, 0xF),  // start
+];
+
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FRAME_RATE) as usize;
+const TONE_HZ: u32 = 440;
+const VOLUME: i16 = 4000;
+
+// Core options, as "description; default|other values". The keys are
+// looked up again with GET_VARIABLE.
+const OPTIONS: [(&CStr, &CStr); 9] = [
+    (c"chip8_platform", c"Platform; chip-8|cosmac-vip"),
+    (
+        c"chip8_vf_reset",
+        c"VF reset quirk; platform|enabled|disabled",
+    ),
+    (
+        c"chip8_memory_increment",
+        c"Memory increment quirk; platform|enabled|disabled",
+    ),
+    (
+        c"chip8_display_wait",
+        c"Display wait quirk; platform|enabled|disabled",
+    ),
+    (
+        c"chip8_clipping",
+        c"Clipping quirk; platform|enabled|disabled",
+    ),
+    (
+        c"chip8_shift_in_place",
+        c"Shift in place quirk; platform|enabled|disabled",
+    ),
+    (
+        c"chip8_jump_with_vx",
+        c"Jump with VX quirk; platform|enabled|disabled",
+    ),
+    (
+        c"chip8_ips",
+        c"Instructions per second; 960|300|480|600|720|1200|1500|1800|3000|6000",
+    ),
+    (c"chip8_palette", c"Palette; white|green|amber|lcd"),
+];
+
+type QuirkFlag = fn(&mut Quirks) -> &mut bool;
+
+// The quirk options and the flag each one sets
+const QUIRKS: [(&CStr, QuirkFlag); 6] = [
+    (c"chip8_vf_reset", |quirks| &mut quirks.vf_reset),
+    (c"chip8_memory_increment", |quirks| {
+        &mut quirks.memory_increment
+    }),
+    (c"chip8_display_wait", |quirks| &mut quirks.display_wait),
+    (c"chip8_clipping", |quirks| &mut quirks.clipping),
+    (c"chip8_shift_in_place", |quirks| &mut quirks.shift_in_place),
+    (c"chip8_jump_with_vx", |quirks| &mut quirks.jump_with_vx),
+];
+
+/// Lit and unlit colours, as 0x00RRGGBB
+fn palette(name: &str) -> (u32, u32) {
+    match name {
+        "green" => (0x33FF66, 0x001A08),
+        "amber" => (0xFFB000, 0x1A0F00),
+        "lcd" => (0x0F380F, 0x8BAC0F),
+        _ => (0xFFFFFF, 0x000000),
+    }
+}
+
+type EnvironmentFn = extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn =
+    extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = extern "C" fn();
+type InputStateFn = extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    geometry: GameGeometry,
+    timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct Variable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+/// Settings from the core options
+struct Options {
+    platform: Platform,
+    // In the order of QUIRKS; None keeps the platform's setting
+    quirks: [Option<bool>; 6],
+    instructions_per_frame: u16,
+    colours: (u32, u32),
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            platform: Platform::Chip8,
+            quirks: [None; 6],
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            colours: palette("white"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Core {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    options: Options,
+    rom: Vec<u8>,
+    chip8: Option<Chip8>,
+    keyboard: Vec<(c_uint, u8)>,
+    video: Vec<u32>,
+    audio: Vec<i16>,
+    // Samples into the current cycle of the tone, so it doesn't click
+    // between frames
+    phase: u32,
+}
+
+static CORE: LazyLock<Mutex<Core>> = LazyLock::new(Mutex::default);
+
+fn with_core<T>(f: impl FnOnce(&mut Core) -> T) -> T {
+    f(&mut CORE.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+impl Core {
+    fn variable(&self, key: &CStr) -> Option<String> {
+        let environment = self.environment?;
+        let mut variable = Variable {
+            key: key.as_ptr(),
+            value: ptr::null(),
+        };
+        if !environment(
+            ENVIRONMENT_GET_VARIABLE,
+            &mut variable as *mut _ as *mut c_void,
+        ) || variable.value.is_null()
+        {
+            return None;
+        }
+        // SAFETY: the frontend hands back a C string it owns
+        let value = unsafe { CStr::from_ptr(variable.value) };
+        Some(value.to_string_lossy().into_owned())
+    }
+
+    fn read_options(&mut self) {
+        let mut options = Options::default();
+        if let Some(platform) = self.variable(c"chip8_platform") {
+            options.platform = Platform::parse(&platform).unwrap_or(Platform::Chip8);
+        }
+        for (setting, (key, _)) in options.quirks.iter_mut().zip(QUIRKS) {
+            *setting = match self.variable(key).as_deref() {
+                Some("enabled") => Some(true),
+                Some("disabled") => Some(false),
+                _ => None,
+            };
+        }
+        if let Some(ips) = self
+            .variable(c"chip8_ips")
+            .and_then(|ips| ips.parse::<u32>().ok())
+        {
+            options.instructions_per_frame = (ips / FRAME_RATE).clamp(1, u16::MAX as u32) as u16;
+        }
+        if let Some(name) = self.variable(c"chip8_palette") {
+            options.colours = palette(&name);
+        }
+        self.options = options;
+    }
+
+    // Options apply to a running game without resetting it
+    fn apply_options(&mut self) {
+        let Some(chip8) = &mut self.chip8 else {
+            return;
+        };
+        let options = &self.options;
+        chip8.set_platform(options.platform);
+        let mut quirks = options.platform.quirks();
+        for (setting, (_, flag)) in options.quirks.iter().zip(QUIRKS) {
+            if let Some(enabled) = *setting {
+                *flag(&mut quirks) = enabled;
+            }
+        }
+        chip8.set_quirks(quirks);
+        chip8.set_instructions_per_frame(options.instructions_per_frame);
+    }
+
+    fn power_on(&mut self) -> bool {
+        let mut chip8 = Chip8::builder().rng(Rng::new(0)).build();
+        if chip8.load_program(&self.rom).is_err() {
+            return false;
+        }
+        self.chip8 = Some(chip8);
+        self.phase = 0;
+        self.apply_options();
+        true
+    }
+
+    fn poll_keys(&mut self) {
+        let (Some(input_poll), Some(input_state), Some(chip8)) =
+            (self.input_poll, self.input_state, &mut self.chip8)
+        else {
+            return;
+        };
+        input_poll();
+
+        let mut keys = 0u16;
+        for &(id, key) in &JOYPAD {
+            if input_state(0, DEVICE_JOYPAD, 0, id) != 0 {
+                keys |= 1 << key;
+            }
+        }
+        for &(id, key) in &self.keyboard {
+            if input_state(0, DEVICE_KEYBOARD, 0, id) != 0 {
+                keys |= 1 << key;
+            }
+        }
+        for key in 0..=0xF {
+            chip8
+                .set_key(key, keys & 1 << key != 0)
+                .expect("keys are 0-F");
+        }
+    }
+
+    fn run(&mut self) {
+        let Some(environment) = self.environment else {
+            return;
+        };
+        let mut updated = false;
+        if environment(
+            ENVIRONMENT_GET_VARIABLE_UPDATE,
+            &mut updated as *mut bool as *mut c_void,
+        ) && updated
+        {
+            self.read_options();
+            self.apply_options();
+        }
+
+        self.poll_keys();
+        let Some(chip8) = &mut self.chip8 else {
+            return;
+        };
+        chip8.run_frame();
+
+        let (lit, unlit) = self.options.colours;
+        for (out, &pixel) in self
+            .video
+            .iter_mut()
+            .zip(chip8.framebuffer().iter().flatten())
+        {
+            *out = if pixel != 0 { lit } else { unlit };
+        }
+        if let Some(video_refresh) = self.video_refresh {
+            video_refresh(
+                self.video.as_ptr() as *const c_void,
+                DISPLAY_WIDTH as c_uint,
+                DISPLAY_HEIGHT as c_uint,
+                DISPLAY_WIDTH * 4,
+            );
+        }
+
+        // The buzzer sounds while the sound timer is above zero
+        let sounding = chip8.cpu().read_sound_timer() > 0;
+        let period = SAMPLE_RATE / TONE_HZ;
+        for frame in self.audio.chunks_exact_mut(2) {
+            let sample = match sounding {
+                true if self.phase < period / 2 => VOLUME,
+                true => -VOLUME,
+                false => 0,
+            };
+            frame.fill(sample);
+            self.phase = (self.phase + 1) % period;
+        }
+        if let Some(audio_sample_batch) = self.audio_sample_batch {
+            audio_sample_batch(self.audio.as_ptr(), SAMPLES_PER_FRAME);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {
+    with_core(|core| {
+        core.video = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        core.audio = vec![0; SAMPLES_PER_FRAME * 2];
+        // Keyboard ids are ASCII for letters and digits
+        core.keyboard = Keymap::new(Layout::Qwerty)
+            .bindings()
+            .filter_map(|(host, key)| match host {
+                HostKey::Char(c) => Some((c as c_uint, key)),
+                _ => None,
+            })
+            .collect();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    with_core(|core| *core = Core::default());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: c"chip-8-in-rust".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"ch8|c8|rom".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: DISPLAY_WIDTH as c_uint,
+            base_height: DISPLAY_HEIGHT as c_uint,
+            max_width: DISPLAY_WIDTH as c_uint,
+            max_height: DISPLAY_HEIGHT as c_uint,
+            aspect_ratio: DISPLAY_WIDTH as f32 / DISPLAY_HEIGHT as f32,
+        },
+        timing: SystemTiming {
+            fps: FRAME_RATE as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(environment: EnvironmentFn) {
+    let variables: Vec<Variable> = OPTIONS
+        .iter()
+        .map(|(key, value)| Variable {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .chain([Variable {
+            key: ptr::null(),
+            value: ptr::null(),
+        }])
+        .collect();
+    environment(ENVIRONMENT_SET_VARIABLES, variables.as_ptr() as *mut c_void);
+    with_core(|core| core.environment = Some(environment));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: VideoRefreshFn) {
+    with_core(|core| core.video_refresh = Some(video_refresh));
+}
+
+/// Unused: audio goes out a frame at a time through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: AudioSampleBatchFn) {
+    with_core(|core| core.audio_sample_batch = Some(audio_sample_batch));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: InputPollFn) {
+    with_core(|core| core.input_poll = Some(input_poll));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: InputStateFn) {
+    with_core(|core| core.input_state = Some(input_state));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core(|core| {
+        core.power_on();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    with_core(Core::run);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let Some(state) = with_core(|core| core.chip8.as_ref().map(Chip8::save_state)) else {
+        return false;
+    };
+    if data.is_null() || size < state.len() {
+        return false;
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+    true
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state = slice::from_raw_parts(data as *const u8, size);
+    with_core(|core| {
+        core.chip8
+            .as_mut()
+            .is_some_and(|chip8| chip8.load_state(state).is_ok())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let rom = slice::from_raw_parts(game.data as *const u8, game.size).to_vec();
+
+    with_core(|core| {
+        let Some(environment) = core.environment else {
+            return false;
+        };
+        let mut format = PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        ) {
+            return false;
+        }
+        core.rom = rom;
+        core.read_options();
+        core.power_on()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    with_core(|core| {
+        core.chip8 = None;
+        core.rom.clear();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+/// No memory is exposed: writes from the frontend would go around the
+/// decode cache
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
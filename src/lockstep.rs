@@ -0,0 +1,578 @@
+//! Differential testing: run a machine in lockstep with a reference, either a
+//! second machine (another engine or platform, say) or a trace recorded
+//! earlier, and stop at the first instruction where the two disagree.
+//!
+//! ```no_run
+//! use chip_8_emulator::lockstep::{Lockstep, Reference};
+//! use chip_8_emulator::{Chip8, Engine, Rng};
+//!
+//! let rom = std::fs::read("data/BRIX").unwrap();
+//! let machine = |engine| {
+//!     let mut chip8 = Chip8::builder().engine(engine).rng(Rng::new(0)).build();
+//!     chip8.load_program(&rom).unwrap();
+//!     chip8
+//! };
+//! let reference = Reference::Machine(Box::new(machine(Engine::Interpreter)));
+//! let mut lockstep = Lockstep::new(machine(Engine::Recompiler), reference);
+//! if let Err(divergence) = lockstep.run(600) {
+//!     println!("{}", divergence);
+//! }
+//! ```
+
+use std::fmt;
+
+use crate::chip8::{Chip8, Chip8Error};
+use crate::state::crc32;
+
+/// The state compared after every instruction. Memory and the framebuffer
+/// are kept as hashes, so traces stay small.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: u16,
+    /// A draw is waiting for the vertical blank
+    pub waiting: bool,
+    /// crc32 of the 4 KiB of memory
+    pub memory: u32,
+    /// crc32 of the framebuffer, a byte per pixel
+    pub framebuffer: u32,
+}
+
+impl Snapshot {
+    pub fn of(chip8: &Chip8) -> Snapshot {
+        snapshot(chip8, true)
+    }
+
+    /// Parse a snapshot written by `Display`:
+    ///
+    /// ```text
+    /// pc=204 v=05000000000000000000000000000000 i=300 stack=- dt=00 st=00 keys=0000 wait=0 mem=1C2E5A09 fb=2144DF1C
+    /// ```
+    pub fn parse(line: &str) -> Result<Snapshot, String> {
+        let mut snapshot = Snapshot {
+            pc: 0,
+            registers: [0; 16],
+            i: 0,
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            keys: 0,
+            waiting: false,
+            memory: 0,
+            framebuffer: 0,
+        };
+        let mut seen = Vec::new();
+        for field in line.split_whitespace() {
+            let (name, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected name=value, found '{}'", field))?;
+            match name {
+                "pc" => snapshot.pc = hex(value)? as u16,
+                "v" => snapshot.registers = registers(value)?,
+                "i" => snapshot.i = hex(value)? as u16,
+                "stack" if value == "-" => snapshot.stack.clear(),
+                "stack" => {
+                    snapshot.stack = value
+                        .split(',')
+                        .map(|address| hex(address).map(|a| a as u16))
+                        .collect::<Result<_, _>>()?
+                }
+                "dt" => snapshot.delay_timer = hex(value)? as u8,
+                "st" => snapshot.sound_timer = hex(value)? as u8,
+                "keys" => snapshot.keys = hex(value)? as u16,
+                "wait" => snapshot.waiting = value == "1",
+                "mem" => snapshot.memory = hex(value)?,
+                "fb" => snapshot.framebuffer = hex(value)?,
+                _ => return Err(format!("unknown field '{}'", name)),
+            }
+            seen.push(name);
+        }
+        for name in [
+            "pc", "v", "i", "stack", "dt", "st", "keys", "wait", "mem", "fb",
+        ] {
+            if !seen.contains(&name) {
+                return Err(format!("missing field '{}'", name));
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pc={:03X} v=", self.pc)?;
+        for value in self.registers.iter() {
+            write!(f, "{:02X}", value)?;
+        }
+        write!(f, " i={:03X} stack=", self.i)?;
+        if self.stack.is_empty() {
+            write!(f, "-")?;
+        }
+        for (depth, address) in self.stack.iter().enumerate() {
+            let separator = if depth > 0 { "," } else { "" };
+            write!(f, "{}{:03X}", separator, address)?;
+        }
+        write!(
+            f,
+            " dt={:02X} st={:02X} keys={:04X} wait={} mem={:08X} fb={:08X}",
+            self.delay_timer,
+            self.sound_timer,
+            self.keys,
+            self.waiting as u8,
+            self.memory,
+            self.framebuffer
+        )
+    }
+}
+
+// Hashing memory is slow next to running an instruction, so machines
+// compared with each other skip it and compare the bytes
+fn snapshot(chip8: &Chip8, hashes: bool) -> Snapshot {
+    let cpu = chip8.cpu();
+    Snapshot {
+        pc: cpu.read_pc(),
+        registers: *cpu.registers(),
+        i: cpu.read_i(),
+        stack: cpu.stack().to_vec(),
+        delay_timer: cpu.read_delay_timer(),
+        sound_timer: cpu.read_sound_timer(),
+        keys: chip8.keys(),
+        waiting: cpu.waiting_for_vblank(),
+        memory: if hashes {
+            crc32(chip8.memory().bytes())
+        } else {
+            0
+        },
+        framebuffer: if hashes {
+            crc32(chip8.framebuffer().as_flattened())
+        } else {
+            0
+        },
+    }
+}
+
+fn hex(text: &str) -> Result<u32, String> {
+    u32::from_str_radix(text, 16).map_err(|_| format!("invalid hex '{}'", text))
+}
+
+fn registers(text: &str) -> Result<[u8; 16], String> {
+    if text.len() != 32 || !text.is_ascii() {
+        return Err(format!("expected 16 registers in hex, found '{}'", text));
+    }
+    let mut registers = [0; 16];
+    for (x, register) in registers.iter_mut().enumerate() {
+        *register = hex(&text[2 * x..2 * x + 2])? as u8;
+    }
+    Ok(registers)
+}
+
+/// One line of a recorded trace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// The state after an instruction ran
+    Instruction(Snapshot),
+    /// The end of a frame, where the timers tick
+    VerticalBlank,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Instruction(snapshot) => write!(f, "{}", snapshot),
+            Step::VerticalBlank => write!(f, "vblank"),
+        }
+    }
+}
+
+/// Run a machine for `frames` frames and record what it does, calling
+/// `input` before every frame to set the keys. Stops early if the program
+/// stops.
+pub fn record(chip8: &mut Chip8, frames: u32, mut input: impl FnMut(u32, &mut Chip8)) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for frame in 0..frames {
+        input(frame, chip8);
+        for _ in 0..chip8.instructions_per_frame() {
+            if chip8.cpu().waiting_for_vblank() {
+                break;
+            }
+            if !chip8.step_engine() {
+                return steps;
+            }
+            steps.push(Step::Instruction(Snapshot::of(chip8)));
+        }
+        chip8.tick_timers();
+        steps.push(Step::VerticalBlank);
+    }
+    steps
+}
+
+/// A trace as text, one step per line
+pub fn format_trace(steps: &[Step]) -> String {
+    let mut text = String::from("# chip-8 lockstep trace, one line per instruction\n");
+    for step in steps {
+        text.push_str(&step.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+/// Read a trace written by `format_trace`
+pub fn parse_trace(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let step = match line {
+            "vblank" => Step::VerticalBlank,
+            _ => Step::Instruction(
+                Snapshot::parse(line).map_err(|e| format!("line {}: {}", number + 1, e))?,
+            ),
+        };
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+/// What the machine under test is checked against
+pub enum Reference {
+    /// Another machine, stepped alongside
+    Machine(Box<Chip8>),
+    /// A trace from `record`
+    Trace(Vec<Step>),
+}
+
+/// The first point where the machine and its reference disagree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions both ran before this one
+    pub instruction: u64,
+    pub frame: u32,
+    pub pc: u16,
+    pub opcode: u16,
+    /// What differs, as (what, machine, reference)
+    pub differences: Vec<(String, String, String)>,
+    /// Save state of the machine just before the instruction: load it and
+    /// step once to reproduce
+    pub state: Vec<u8>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "diverged at instruction {} (frame {}): {:04X} at 0x{:03X}, {:?}",
+            self.instruction,
+            self.frame,
+            self.opcode,
+            self.pc,
+            crate::decode::decode(self.opcode)
+        )?;
+        for (what, machine, reference) in self.differences.iter() {
+            writeln!(f, "  {}: {} (reference {})", what, machine, reference)?;
+        }
+        write!(
+            f,
+            "  reproduce by loading the {}-byte state before it and stepping once",
+            self.state.len()
+        )
+    }
+}
+
+// Every field that differs between two snapshots. With both machines at
+// hand, a differing memory or framebuffer hash is narrowed to the first byte
+// or pixel.
+fn differences(
+    machine: &Snapshot,
+    reference: &Snapshot,
+    machines: Option<(&Chip8, &Chip8)>,
+) -> Vec<(String, String, String)> {
+    let mut found = Vec::new();
+    let mut differ = |what: String, a: String, b: String| {
+        if a != b {
+            found.push((what, a, b));
+        }
+    };
+    differ(
+        "pc".to_string(),
+        format!("0x{:03X}", machine.pc),
+        format!("0x{:03X}", reference.pc),
+    );
+    for x in 0..16 {
+        differ(
+            format!("V{:X}", x),
+            format!("0x{:02X}", machine.registers[x]),
+            format!("0x{:02X}", reference.registers[x]),
+        );
+    }
+    differ(
+        "I".to_string(),
+        format!("0x{:03X}", machine.i),
+        format!("0x{:03X}", reference.i),
+    );
+    differ(
+        "stack".to_string(),
+        format!("{:03X?}", machine.stack),
+        format!("{:03X?}", reference.stack),
+    );
+    differ(
+        "delay timer".to_string(),
+        machine.delay_timer.to_string(),
+        reference.delay_timer.to_string(),
+    );
+    differ(
+        "sound timer".to_string(),
+        machine.sound_timer.to_string(),
+        reference.sound_timer.to_string(),
+    );
+    differ(
+        "keys".to_string(),
+        format!("{:04X}", machine.keys),
+        format!("{:04X}", reference.keys),
+    );
+    differ(
+        "waiting for vblank".to_string(),
+        machine.waiting.to_string(),
+        reference.waiting.to_string(),
+    );
+
+    match machines {
+        Some((a, b)) => {
+            let (a_memory, b_memory) = (a.memory().bytes(), b.memory().bytes());
+            if let Some(address) = (0..a_memory.len()).find(|&n| a_memory[n] != b_memory[n]) {
+                differ(
+                    format!("memory[0x{:03X}]", address),
+                    format!("0x{:02X}", a_memory[address]),
+                    format!("0x{:02X}", b_memory[address]),
+                );
+            }
+            let (a_pixels, b_pixels) = (a.framebuffer(), b.framebuffer());
+            let mut pixels = a_pixels.iter().flatten().zip(b_pixels.iter().flatten());
+            if let Some(n) = pixels.position(|(a, b)| a != b) {
+                let (x, y) = (n % a_pixels[0].len(), n / a_pixels[0].len());
+                differ(
+                    format!("pixel ({}, {})", x, y),
+                    a_pixels[y][x].to_string(),
+                    b_pixels[y][x].to_string(),
+                );
+            }
+        }
+        None => {
+            differ(
+                "memory hash".to_string(),
+                format!("{:08X}", machine.memory),
+                format!("{:08X}", reference.memory),
+            );
+            differ(
+                "framebuffer hash".to_string(),
+                format!("{:08X}", machine.framebuffer),
+                format!("{:08X}", reference.framebuffer),
+            );
+        }
+    }
+    found
+}
+
+/// A machine and its reference, run an instruction at a time with the same
+/// input. Frames run a flat `instructions_per_frame` like
+/// `Timing::Instructions`.
+pub struct Lockstep {
+    machine: Chip8,
+    reference: Reference,
+    // Next step of a trace reference
+    position: usize,
+    instruction: u64,
+    frame: u32,
+}
+
+impl Lockstep {
+    /// Both should be loaded with the same ROM, and a reference machine
+    /// seeded like the machine
+    pub fn new(machine: Chip8, reference: Reference) -> Lockstep {
+        Lockstep {
+            machine,
+            reference,
+            position: 0,
+            instruction: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn machine(&self) -> &Chip8 {
+        &self.machine
+    }
+
+    pub fn reference(&self) -> &Reference {
+        &self.reference
+    }
+
+    /// Instructions run so far
+    pub fn instructions(&self) -> u64 {
+        self.instruction
+    }
+
+    /// Press or release a key on both machines. A trace has its keys
+    /// recorded, so they must be pressed as they were then.
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), Chip8Error> {
+        self.machine.set_key(key, pressed)?;
+        if let Reference::Machine(reference) = &mut self.reference {
+            reference.set_key(key, pressed)?;
+        }
+        Ok(())
+    }
+
+    /// Run up to `frames` frames. Returns the number run, fewer if the
+    /// program stopped or the trace ran out.
+    pub fn run(&mut self, frames: u32) -> Result<u32, Divergence> {
+        for frame in 0..frames {
+            if !self.run_frame()? {
+                return Ok(frame);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Run a frame, comparing after every instruction. Returns false if the
+    /// program stopped or the trace ran out.
+    pub fn run_frame(&mut self) -> Result<bool, Divergence> {
+        // The state before a divergence is replayed from here
+        let frame_state = self.machine.save_state();
+        for ran in 0..self.machine.instructions_per_frame() {
+            if self.machine.cpu().waiting_for_vblank() {
+                break;
+            }
+            let pc = self.machine.cpu().read_pc();
+            // Read before the instruction can overwrite itself
+            let opcode = self.opcode_at(pc);
+            let running = self.machine.step_engine();
+
+            let differences = match &mut self.reference {
+                Reference::Machine(reference) => {
+                    let reference_running = reference.step_engine();
+                    if running != reference_running {
+                        vec![stopped(running, reference_running)]
+                    } else if !running {
+                        return Ok(false);
+                    } else {
+                        let (machine, reference) = (&self.machine, &**reference);
+                        let (a, b) = (snapshot(machine, false), snapshot(reference, false));
+                        if a == b
+                            && machine.memory().bytes() == reference.memory().bytes()
+                            && machine.framebuffer() == reference.framebuffer()
+                        {
+                            Vec::new()
+                        } else {
+                            differences(&a, &b, Some((machine, reference)))
+                        }
+                    }
+                }
+                Reference::Trace(steps) => {
+                    let step = steps.get(self.position);
+                    self.position += 1;
+                    match step {
+                        None => return Ok(false),
+                        Some(Step::VerticalBlank) => vec![(
+                            "frame".to_string(),
+                            "still running".to_string(),
+                            "ended".to_string(),
+                        )],
+                        Some(Step::Instruction(_)) if !running => vec![stopped(false, true)],
+                        Some(Step::Instruction(expected)) => {
+                            let actual = Snapshot::of(&self.machine);
+                            if actual == *expected {
+                                Vec::new()
+                            } else {
+                                differences(&actual, expected, None)
+                            }
+                        }
+                    }
+                }
+            };
+            if !differences.is_empty() {
+                let state = self.state_before(&frame_state, ran);
+                return Err(self.divergence(pc, opcode, state, differences));
+            }
+            self.instruction += 1;
+        }
+
+        self.machine.tick_timers();
+        match &mut self.reference {
+            Reference::Machine(reference) => reference.tick_timers(),
+            Reference::Trace(steps) => {
+                let step = steps.get(self.position);
+                self.position += 1;
+                match step {
+                    None => return Ok(false),
+                    Some(Step::VerticalBlank) => {}
+                    Some(Step::Instruction(_)) => {
+                        let pc = self.machine.cpu().read_pc();
+                        let opcode = self.opcode_at(pc);
+                        let state = self.machine.save_state();
+                        let differences = vec![(
+                            "frame".to_string(),
+                            "ended".to_string(),
+                            "still running".to_string(),
+                        )];
+                        return Err(self.divergence(pc, opcode, state, differences));
+                    }
+                }
+            }
+        }
+        self.frame += 1;
+        Ok(true)
+    }
+
+    // The machine's state before the instruction at `ran` this frame,
+    // replayed from the state at the start of the frame
+    fn state_before(&self, frame_state: &[u8], ran: u16) -> Vec<u8> {
+        let mut replay = Chip8::builder().engine(self.machine.engine()).build();
+        replay
+            .load_state(frame_state)
+            .expect("the machine's own state");
+        for _ in 0..ran {
+            replay.step_engine();
+        }
+        replay.save_state()
+    }
+
+    fn opcode_at(&self, pc: u16) -> u16 {
+        let memory = self.machine.memory();
+        if (pc as usize) + 1 < memory.len() {
+            memory.fetch_opcode(pc as usize)
+        } else {
+            0
+        }
+    }
+
+    fn divergence(
+        &self,
+        pc: u16,
+        opcode: u16,
+        state: Vec<u8>,
+        differences: Vec<(String, String, String)>,
+    ) -> Divergence {
+        Divergence {
+            instruction: self.instruction,
+            frame: self.frame,
+            pc,
+            opcode,
+            differences,
+            state,
+        }
+    }
+}
+
+fn stopped(machine: bool, reference: bool) -> (String, String, String) {
+    let describe = |running: bool| if running { "running" } else { "stopped" }.to_string();
+    (
+        "program".to_string(),
+        describe(machine),
+        describe(reference),
+    )
+}
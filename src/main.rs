@@ -1,44 +1,497 @@
-use std::{fs::File, io::Read};
-use std::time::{Duration, Instant};
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
 use std::thread;
+use std::{env, fs};
 
-mod chip8;
-mod cpu;
-mod memory;
-mod display;
+use chip_8_emulator::debugger::{Debugger, Resume, Step};
+use chip_8_emulator::keymap::{HostKey, Keymap, Layout};
+use chip_8_emulator::movie::{Movie, MovieHeader, Player, Recorder};
+use chip_8_emulator::pacer::FramePacer;
+use chip_8_emulator::rewind::Rewind;
+use chip_8_emulator::rng::{Rng, RngKind};
+use chip_8_emulator::script::Script;
+use chip_8_emulator::{debugger, gdb, rng, Chip8, Engine, Platform, Timing};
+
+mod keyboard;
+
+use keyboard::{KeyEvent, Keyboard};
+
+// Memory kept for rewinding while running freely
+const REWIND_BUDGET: usize = 4 << 20;
+
+// Terminals don't report key releases, so a key is released once it hasn't
+// repeated for this many frames (longer than the usual auto-repeat delay)
+const KEY_HOLD_FRAMES: u8 = 20;
 
 fn main() {
-    let mut file = File::open("data/INVADERS").unwrap();
-    let mut data = Vec::<u8>::new();
-    let _ = file.read_to_end(&mut data);
+    let mut rom = String::from("data/INVADERS");
+    let mut debug = false;
+    let mut gdb_port = None;
+    let mut start_slot = None;
+    let mut rewind_interval = 1;
+    let mut seed = None;
+    let mut vip_rng = false;
+    let mut record = None;
+    let mut play = None;
+    let mut keymap = None;
+    let mut script = None;
+    let mut instructions_per_frame = chip_8_emulator::DEFAULT_INSTRUCTIONS_PER_FRAME;
+    let mut turbo = false;
+    let mut vip_timing = false;
+    let mut platform = Platform::Chip8;
+    let mut trace = true;
+    let mut engine = Engine::Interpreter;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--debug" => debug = true,
+            "--gdb" => {
+                let port = args.next().unwrap_or_default();
+                gdb_port = Some(port.parse::<u16>().unwrap_or_else(|_| {
+                    eprintln!(
+                        "Usage: --gdb <port>, with a port from 0 to 65535, not '{}'",
+                        port
+                    );
+                    std::process::exit(1);
+                }));
+            }
+            "--load-state" => start_slot = args.next(),
+            "--seed" => seed = args.next().and_then(|seed| seed.parse::<u64>().ok()),
+            "--vip-rng" => vip_rng = true,
+            "--record" => record = args.next(),
+            "--play" => play = args.next(),
+            "--keymap" => keymap = args.next(),
+            "--script" => script = args.next(),
+            "--ipf" => {
+                instructions_per_frame = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(instructions_per_frame)
+            }
+            "--turbo" => turbo = true,
+            "--no-trace" => trace = false,
+            "--engine" => {
+                let name = args.next().unwrap_or_default();
+                engine = Engine::parse(&name).unwrap_or_else(|message| {
+                    eprintln!("{}", message);
+                    std::process::exit(1);
+                });
+            }
+            "--vip-timing" => vip_timing = true,
+            "--platform" => {
+                let name = args.next().unwrap_or_default();
+                platform = Platform::parse(&name).unwrap_or_else(|message| {
+                    eprintln!("{}", message);
+                    std::process::exit(1);
+                });
+            }
+            "--rewind-interval" => {
+                rewind_interval = args.next().and_then(|n| n.parse().ok()).unwrap_or(1)
+            }
+            _ => rom = arg,
+        }
+    }
+
+    let data = fs::read(&rom).unwrap_or_else(|e| {
+        eprintln!("{}: {}", rom, e);
+        std::process::exit(1);
+    });
+
+    let keymap = keymap.map(|keymap| load_keymap(&keymap, &rom));
+
+    let mut chip8 = Chip8::new();
+    if let Err(e) = chip8.load_program(&data) {
+        eprintln!("{}: {}", rom, e);
+        std::process::exit(1);
+    }
+    chip8.set_platform(platform);
+    chip8.set_trace(trace);
+    chip8.set_engine(engine);
+    chip8.set_instructions_per_frame(instructions_per_frame);
+    if vip_timing {
+        chip8.set_timing(Timing::CosmacVip);
+    }
+    match (seed, vip_rng) {
+        (Some(seed), false) => chip8.set_rng(Rng::new(seed)),
+        (seed, true) => chip8.set_rng(Rng::cosmac_vip(seed.unwrap_or(0))),
+        (None, false) => {}
+    }
 
-    let mut chip8 = chip8::Chip8::new();
-    chip8.load_program(&data);
+    if let Some(slot) = start_slot {
+        if let Some(Ok(message) | Err(message)) =
+            quick_slot(&mut chip8, &rom, &format!("load {}", slot))
+        {
+            println!("{}", message);
+        }
+    }
 
-    let mut last_update = Instant::now();
+    if let Some(path) = script {
+        // Scripts are reproducible unless asked for a particular seed
+        if seed.is_none() && !vip_rng {
+            chip8.set_rng(Rng::new(0));
+        }
+        run_script(&mut chip8, &path);
+        return;
+    }
+    if let Some(path) = play {
+        play_movie(&mut chip8, &data, &path);
+        return;
+    }
+    if let Some(path) = record {
+        let kind = if vip_rng {
+            RngKind::CosmacVip
+        } else {
+            RngKind::Xorshift
+        };
+        let header = MovieHeader::new(
+            &data,
+            chip8.platform(),
+            kind,
+            seed.unwrap_or_else(rng::seed_from_time),
+            instructions_per_frame,
+        );
+        record_movie(&mut chip8, header, &path);
+        return;
+    }
 
+    // The debugger and the GDB server step one instruction at a time, and
+    // count frames in instructions or wall-clock time, never in VIP cycles
+    if vip_timing && (debug || gdb_port.is_some()) {
+        eprintln!("--vip-timing is ignored with --debug and --gdb");
+    }
+
+    if let Some(port) = gdb_port {
+        let server = gdb::GdbServer::bind(port).unwrap();
+        println!(
+            "GDB server listening on 127.0.0.1:{}",
+            server.port().unwrap()
+        );
+        server.serve(&mut chip8).unwrap();
+        return;
+    }
+
+    // With --debug we start stopped at the prompt; None means running freely
+    let mut debugger = if debug { Some(Debugger::new()) } else { None };
+    let mut steps_left = if debug { Some(0) } else { None };
+
+    // With a keymap, stdin is read key by key to drive the keypad: backspace
+    // rewinds, tab toggles turbo, F5/F9 quick-save/load slot 0 and Ctrl-C quits.
+    // Otherwise `save <n>`/`load <n>` typed on stdin use the quick-save slots,
+    // `rewind [n]` steps back n snapshots, taken every `rewind_interval` frames,
+    // `turbo` toggles unthrottled running and `key <k> <down|up>` presses keys.
+    let keyboard = match keymap {
+        Some(_) if !debug => match Keyboard::open() {
+            Ok(keyboard) => Some(keyboard),
+            Err(e) => {
+                eprintln!("Could not read the keyboard: {}", e);
+                return;
+            }
+        },
+        _ => None,
+    };
+    let commands = if debug || keyboard.is_some() {
+        None
+    } else {
+        Some(spawn_stdin_reader())
+    };
+    let mut held = [0u8; 16];
+
+    let mut rewind = Rewind::new(rewind_interval, REWIND_BUDGET);
+    let mut pacer = FramePacer::new();
+    pacer.set_turbo(turbo);
+
+    'running: loop {
+        for _ in 0..pacer.wait() {
+            if let Some(debugger) = debugger.as_mut() {
+                // Always --ipf instructions a frame, whatever the timing
+                for _ in 0..chip8.instructions_per_frame() {
+                    if chip8.cpu().waiting_for_vblank() {
+                        break;
+                    }
+                    if steps_left == Some(0) {
+                        match debug_prompt(debugger, &mut chip8, &rom) {
+                            Resume::Continue => steps_left = None,
+                            Resume::Step(count) => steps_left = Some(count),
+                            Resume::Prompt | Resume::Quit => break 'running,
+                        }
+                        pacer.reset();
+                    }
+                    match debugger.step(&mut chip8) {
+                        Step::Running => steps_left = steps_left.map(|n| n.saturating_sub(1)),
+                        Step::Halted => break 'running,
+                        Step::Stopped(stop) => {
+                            println!("{}", debugger.describe(&stop));
+                            steps_left = Some(0);
+                        }
+                    }
+                }
+                chip8.tick_timers();
+            } else {
+                if !chip8.run_frame() {
+                    break 'running;
+                }
+                rewind.tick(&chip8);
+            }
+
+            for (key, frames) in held.iter_mut().enumerate() {
+                if *frames > 0 {
+                    *frames -= 1;
+                    if *frames == 0 {
+                        chip8.cpu_mut().set_key(key, false);
+                    }
+                }
+            }
+        }
+
+        if let (Some(keyboard), Some(keymap)) = (keyboard.as_ref(), keymap.as_ref()) {
+            for event in keyboard.poll() {
+                let host = match event {
+                    KeyEvent::Interrupt => break 'running,
+                    KeyEvent::Key(host) => host,
+                };
+                if let Some(key) = keymap.key_for(host) {
+                    chip8.cpu_mut().set_key(key as usize, true);
+                    held[key as usize] = KEY_HOLD_FRAMES;
+                    continue;
+                }
+                let command = match host {
+                    HostKey::Backspace => {
+                        rewind.step_back(&mut chip8);
+                        continue;
+                    }
+                    HostKey::Tab => {
+                        pacer.set_turbo(!pacer.turbo());
+                        continue;
+                    }
+                    HostKey::F(5) => "save 0",
+                    HostKey::F(9) => "load 0",
+                    _ => continue,
+                };
+                if let Some(Ok(message) | Err(message)) = quick_slot(&mut chip8, &rom, command) {
+                    println!("{}", message);
+                }
+            }
+        }
+
+        if let Some(commands) = commands.as_ref() {
+            while let Ok(line) = commands.try_recv() {
+                if let Some(count) = line.trim().strip_prefix("rewind") {
+                    let count = count.trim().parse().unwrap_or(1);
+                    let stepped = (0..count).take_while(|_| rewind.step_back(&mut chip8));
+                    println!(
+                        "Rewound {} snapshots, {} left",
+                        stepped.count(),
+                        rewind.len()
+                    );
+                    continue;
+                }
+                if line.trim() == "turbo" {
+                    pacer.set_turbo(!pacer.turbo());
+                    println!("Turbo {}", if pacer.turbo() { "on" } else { "off" });
+                    continue;
+                }
+                match quick_slot(&mut chip8, &rom, &line).or_else(|| key_command(&mut chip8, &line))
+                {
+                    Some(Ok(message)) | Some(Err(message)) => println!("{}", message),
+                    None => println!("Unknown command: {}", line.trim()),
+                }
+            }
+        }
+    }
+
+    if let Some(fault) = chip8.fault() {
+        println!("{}", fault);
+    }
+}
+
+/// Read debugger commands from stdin until one of them resumes execution
+fn debug_prompt(debugger: &mut Debugger, chip8: &mut Chip8, rom: &str) -> Resume {
+    println!("{}", debugger::registers(chip8));
+    let stdin = io::stdin();
     loop {
-        if !chip8.decode_and_execute() {
+        print!("(chip8) ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            return Resume::Quit;
+        }
+        if let Some(Ok(message) | Err(message)) = quick_slot(chip8, rom, &line) {
+            println!("{}", message);
+            continue;
+        }
+        match debugger.run_command(chip8, &line) {
+            Ok(Resume::Prompt) => {}
+            Ok(resume) => return resume,
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+/// Handle `save <n>` and `load <n>`, storing slot n next to the ROM.
+/// Returns None for any other command.
+fn quick_slot(chip8: &mut Chip8, rom: &str, line: &str) -> Option<Result<String, String>> {
+    let mut words = line.split_whitespace();
+    let command = words.next()?;
+    if command != "save" && command != "load" {
+        return None;
+    }
+    let slot = match words.next().map(|slot| slot.parse::<u8>()) {
+        Some(Ok(slot)) if slot <= 9 => slot,
+        _ => return Some(Err(format!("Usage: {} <0-9>", command))),
+    };
+    let path = format!("{}.state{}", rom, slot);
+
+    Some(if command == "save" {
+        fs::write(&path, chip8.save_state())
+            .map(|_| format!("Saved slot {} to {}", slot, path))
+            .map_err(|e| format!("Could not write {}: {}", path, e))
+    } else {
+        fs::read(&path)
+            .map_err(|e| format!("Could not read {}: {}", path, e))
+            .and_then(|state| {
+                chip8
+                    .load_state(&state)
+                    .map_err(|e| format!("Could not load {}: {}", path, e))
+            })
+            .map(|_| format!("Loaded slot {} from {}", slot, path))
+    })
+}
+
+/// Build the keymap named on the command line: a layout name
+/// (qwerty, azerty, numpad) or a keymap file, with overrides for this ROM
+fn load_keymap(name: &str, rom: &str) -> Keymap {
+    if let Ok(layout) = Layout::parse(name) {
+        return Keymap::new(layout);
+    }
+    let rom_name = std::path::Path::new(rom)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let keymap = fs::read_to_string(name)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Keymap::parse(&text, &rom_name));
+    match keymap {
+        Ok(keymap) => keymap,
+        Err(message) => {
+            eprintln!("Invalid keymap {}: {}", name, message);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handle `key <0-F> <down|up>`. Returns None for any other command.
+fn key_command(chip8: &mut Chip8, line: &str) -> Option<Result<String, String>> {
+    let args: Vec<&str> = line.split_whitespace().collect();
+    match args.as_slice() {
+        ["key", key, state] if *state == "down" || *state == "up" => {
+            Some(match u8::from_str_radix(key, 16) {
+                Ok(key) if key <= 0xF => {
+                    chip8.cpu_mut().set_key(key as usize, *state == "down");
+                    Ok(format!("Key {:X} {}", key, state))
+                }
+                _ => Err(format!("No such key: {}", key)),
+            })
+        }
+        ["key", ..] => Some(Err("Usage: key <0-F> <down|up>".to_string())),
+        _ => None,
+    }
+}
+
+/// Record a movie of keypad changes from `key` commands until `quit` is typed
+/// or the program stops
+fn record_movie(chip8: &mut Chip8, header: MovieHeader, path: &str) {
+    println!(
+        "Recording to {} with seed {}, type 'quit' to stop",
+        path, header.seed
+    );
+    let commands = spawn_stdin_reader();
+    let mut recorder = Recorder::new(header, chip8);
+    let mut pacer = FramePacer::new();
+
+    'recording: loop {
+        while let Ok(line) = commands.try_recv() {
+            if line.trim() == "quit" {
+                break 'recording;
+            }
+            match key_command(chip8, &line) {
+                Some(Ok(message)) | Some(Err(message)) => println!("{}", message),
+                None => println!("Unknown command: {}", line.trim()),
+            }
+        }
+        pacer.wait();
+        if !recorder.run_frame(chip8) {
             break;
         }
+    }
 
-        // Simulate 60Hz update rate for timers and display
-        if last_update.elapsed() >= Duration::from_millis(16) {
-            // Update timers and display here
-            last_update = Instant::now();
+    let movie = recorder.finish();
+    match fs::write(path, movie.to_bytes()) {
+        Ok(()) => println!("Saved movie to {}", path),
+        Err(e) => println!("Could not write {}: {}", path, e),
+    }
+}
+
+/// Play a movie back, exiting with an error on the first desync
+fn play_movie(chip8: &mut Chip8, rom: &[u8], path: &str) {
+    let player = fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| Movie::from_bytes(&bytes).map_err(|e| e.to_string()))
+        .and_then(|movie| Player::new(movie, rom, chip8).map_err(|e| e.to_string()));
+    let mut player = match player {
+        Ok(player) => player,
+        Err(message) => {
+            eprintln!("Could not play {}: {}", path, message);
+            std::process::exit(1);
         }
+    };
+    if player.header().emulator_version != env!("CARGO_PKG_VERSION") {
+        eprintln!(
+            "Warning: movie was recorded with emulator version {}",
+            player.header().emulator_version
+        );
+    }
 
-        // Handle key inputs here
-        // Example: chip8.cpu.set_key(0, true); // Set key 0 as pressed
+    let mut pacer = FramePacer::new();
+    loop {
+        pacer.wait();
+        match player.run_frame(chip8) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                eprintln!("Playback of {} failed: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    println!("Finished playing {}", path);
+}
 
-        // Sleep to limit the emulation speed
-        thread::sleep(Duration::from_millis(1));
+/// Run a play-testing script headless, exiting with an error if it fails
+fn run_script(chip8: &mut Chip8, path: &str) {
+    let result = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Script::parse(&text))
+        .and_then(|script| script.run(chip8));
+    match result {
+        Ok(frames) => println!("Script {} passed after {} frames", path, frames),
+        Err(message) => {
+            eprintln!("Script {} failed: {}", path, message);
+            std::process::exit(1);
+        }
     }
 }
 
-fn fetch_opcode(memory: &[u8; 4096], pc: usize) -> u16 {
-    // Combine two consecutive bytes to form a 16-bit opcode
-    let high_byte = memory[pc] as u16;
-    let low_byte = memory[pc + 1] as u16;
-    (high_byte << 8) | low_byte
+/// Read debugger commands from stdin on a thread, one line per message, so
+/// the emulator keeps running while it waits for input
+fn spawn_stdin_reader() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
 }
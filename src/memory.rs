@@ -1,10 +1,78 @@
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::cell::Cell;
+
+use crate::decode::{decode, Instruction};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A range of memory (inclusive on both ends) that stops the debugger
+/// when an instruction reads from it, writes to it, or both
+#[derive(Clone, Copy, Debug)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub on_read: bool,
+    pub on_write: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: usize, kind: AccessKind) -> bool {
+        let wanted = match kind {
+            AccessKind::Read => self.on_read,
+            AccessKind::Write => self.on_write,
+        };
+        wanted && (self.start as usize..=self.end as usize).contains(&address)
+    }
+}
+
+/// The first watched access made since the last call to `take_watch_hit`
+#[derive(Clone, Copy, Debug)]
+pub struct WatchHit {
+    pub address: u16,
+    pub kind: AccessKind,
+    pub value: u8,
+}
+
 pub struct Memory {
     data: [u8; 4096],
+    #[cfg(feature = "alloc")]
+    watchpoints: Vec<Watchpoint>,
+    // read_byte only borrows immutably, so hits are recorded through a Cell
+    watch_hit: Cell<Option<WatchHit>>,
+    // Opcode and decoded instruction for each address that has run, dropped
+    // when either of its bytes is written
+    #[cfg(feature = "alloc")]
+    decoded: Vec<Option<(u16, Instruction)>>,
+    #[cfg(feature = "alloc")]
+    decode_cache: bool,
+    // Range of addresses written since the last call to `take_written`
+    written: Option<(u16, u16)>,
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
 }
 
 impl Memory {
     pub fn new() -> Memory {
-        let mut memory = Memory { data: [0; 4096] };
+        let mut memory = Memory {
+            data: [0; 4096],
+            #[cfg(feature = "alloc")]
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+            #[cfg(feature = "alloc")]
+            decoded: vec![None; 4096],
+            #[cfg(feature = "alloc")]
+            decode_cache: true,
+            written: None,
+        };
 
         let sprites: [[u8; 5]; 16] = [
             [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
@@ -36,15 +104,38 @@ impl Memory {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     /// Load a program into memory starting at specified address
     /// self.data[start_address..start_address + program.len()].copy_from_slice(program);
     pub fn load_program(&mut self, program: &[u8], start_address: usize) {
         for (i, &byte) in program.iter().enumerate() {
             self.data[start_address + i] = byte;
         }
+        self.clear_decoded();
+    }
+
+    /// The whole address space, e.g. for save states
+    pub fn bytes(&self) -> &[u8; 4096] {
+        &self.data
+    }
+
+    /// Replace the whole address space, keeping watchpoints
+    pub fn restore(&mut self, data: &[u8; 4096]) {
+        self.data = *data;
+        self.clear_decoded();
     }
 
     pub fn read_byte(&self, address: usize) -> u8 {
+        let value = self.data[address];
+        self.record_access(address, AccessKind::Read, value);
+        value
+    }
+
+    /// Read a byte without triggering watchpoints (used by the debugger)
+    pub fn peek_byte(&self, address: usize) -> u8 {
         self.data[address]
     }
 
@@ -56,13 +147,101 @@ impl Memory {
         (high_byte << 8) | low_byte
     }
 
+    /// Fetch and decode the instruction at an address, reusing the last
+    /// decode unless the code there has been overwritten since
+    pub fn fetch_instruction(&mut self, address: usize) -> (u16, Instruction) {
+        #[cfg(feature = "alloc")]
+        if let Some(decoded) = self.decoded[address] {
+            return decoded;
+        }
+        let opcode = self.fetch_opcode(address);
+        let decoded = (opcode, decode(opcode));
+        #[cfg(feature = "alloc")]
+        if self.decode_cache {
+            self.decoded[address] = Some(decoded);
+        }
+        decoded
+    }
+
+    /// Cache decoded instructions. Without `alloc` there is no cache and
+    /// this does nothing.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        #[cfg(feature = "alloc")]
+        {
+            self.decode_cache = enabled;
+            self.clear_decoded();
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = enabled;
+    }
+
     /// Write a byte to a memory address
     pub fn write_byte(&mut self, address: usize, value: u8) {
         self.data[address] = value;
+        // The byte is the start of one opcode and the end of another
+        #[cfg(feature = "alloc")]
+        {
+            self.decoded[address] = None;
+            if address > 0 {
+                self.decoded[address - 1] = None;
+            }
+        }
+        let written = address as u16;
+        self.written = Some(match self.written {
+            Some((start, end)) => (start.min(written), end.max(written)),
+            None => (written, written),
+        });
+        self.record_access(address, AccessKind::Write, value);
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        if index < self.watchpoints.len() {
+            Some(self.watchpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        #[cfg(feature = "alloc")]
+        return &self.watchpoints;
+        #[cfg(not(feature = "alloc"))]
+        return &[];
     }
 
-    /// Reset memory
-    pub fn reset(&mut self) {
-        self.data = [0; 4096];
+    /// Take the first watched access recorded since the last call
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// The range of addresses (inclusive) written since the last call,
+    /// for anything that keeps code derived from memory
+    pub fn take_written(&mut self) -> Option<(u16, u16)> {
+        self.written.take()
+    }
+
+    fn clear_decoded(&mut self) {
+        #[cfg(feature = "alloc")]
+        self.decoded.fill(None);
+        self.written = Some((0, self.data.len() as u16 - 1));
+    }
+
+    fn record_access(&self, address: usize, kind: AccessKind, value: u8) {
+        if self.watchpoints().is_empty() || self.watch_hit.get().is_some() {
+            return;
+        }
+        if self.watchpoints().iter().any(|w| w.matches(address, kind)) {
+            self.watch_hit.set(Some(WatchHit {
+                address: address as u16,
+                kind,
+                value,
+            }));
+        }
     }
 }
@@ -0,0 +1,389 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::chip8::Chip8;
+use crate::platform::Platform;
+use crate::rng::{Rng, RngKind};
+use crate::state::crc32;
+use crate::timing::Timing;
+
+/// Movies start with this magic and format version, then the header and a
+/// list of events ordered by frame
+pub const MAGIC: &[u8; 4] = b"C8MV";
+pub const VERSION: u16 = 1;
+
+// Frames between two framebuffer/memory hashes
+const CHECKPOINT_INTERVAL: u32 = 60;
+
+const INPUT: u8 = 0;
+const CHECKPOINT: u8 = 1;
+const END: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    Invalid(&'static str),
+    WrongRom {
+        expected: u32,
+        actual: u32,
+    },
+    Desync {
+        frame: u32,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie file"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {}", version)
+            }
+            MovieError::Truncated => write!(f, "movie file is truncated"),
+            MovieError::Invalid(what) => write!(f, "invalid movie: {}", what),
+            MovieError::WrongRom { expected, actual } => write!(
+                f,
+                "movie was recorded with ROM {:08X}, this ROM is {:08X}",
+                expected, actual
+            ),
+            MovieError::Desync {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "desync at frame {}: expected state hash {:08X}, got {:08X}",
+                frame, expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub emulator_version: String,
+    pub rom_hash: u32,
+    pub platform: Platform,
+    pub rng: RngKind,
+    pub seed: u64,
+    pub instructions_per_frame: u16,
+}
+
+impl MovieHeader {
+    /// Movies run a fixed number of instructions per frame so playback is exact
+    pub fn new(
+        rom: &[u8],
+        platform: Platform,
+        rng: RngKind,
+        seed: u64,
+        instructions_per_frame: u16,
+    ) -> MovieHeader {
+        MovieHeader {
+            emulator_version: env!("CARGO_PKG_VERSION").to_string(),
+            rom_hash: crc32(rom),
+            platform,
+            rng,
+            seed,
+            instructions_per_frame,
+        }
+    }
+
+    /// The random number generator the movie was recorded with
+    pub fn rng(&self) -> Rng {
+        match self.rng {
+            RngKind::Xorshift => Rng::new(self.seed),
+            RngKind::CosmacVip => Rng::cosmac_vip(self.seed),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieEvent {
+    /// The whole keypad state (bit k = key k) from this frame on
+    Input { frame: u32, keys: u16 },
+    /// Hash of the framebuffer and memory at the end of this frame
+    Checkpoint { frame: u32, hash: u32 },
+    /// Number of frames recorded
+    End { frame: u32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    pub events: Vec<MovieEvent>,
+}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = &self.header;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        write_string(&mut bytes, &header.emulator_version);
+        bytes.extend_from_slice(&header.rom_hash.to_le_bytes());
+        write_string(&mut bytes, header.platform.name());
+        bytes.push(match header.rng {
+            RngKind::Xorshift => 0,
+            RngKind::CosmacVip => 1,
+        });
+        bytes.extend_from_slice(&header.seed.to_le_bytes());
+        bytes.extend_from_slice(&header.instructions_per_frame.to_le_bytes());
+
+        for event in self.events.iter() {
+            match *event {
+                MovieEvent::Input { frame, keys } => {
+                    bytes.push(INPUT);
+                    bytes.extend_from_slice(&frame.to_le_bytes());
+                    bytes.extend_from_slice(&keys.to_le_bytes());
+                }
+                MovieEvent::Checkpoint { frame, hash } => {
+                    bytes.push(CHECKPOINT);
+                    bytes.extend_from_slice(&frame.to_le_bytes());
+                    bytes.extend_from_slice(&hash.to_le_bytes());
+                }
+                MovieEvent::End { frame } => {
+                    bytes.push(END);
+                    bytes.extend_from_slice(&frame.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Movie, MovieError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(MovieError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let header = MovieHeader {
+            emulator_version: reader.string()?,
+            rom_hash: reader.u32()?,
+            platform: Platform::parse(&reader.string()?)
+                .map_err(|_| MovieError::Invalid("unknown platform"))?,
+            rng: match reader.u8()? {
+                0 => RngKind::Xorshift,
+                1 => RngKind::CosmacVip,
+                _ => return Err(MovieError::Invalid("unknown random number generator")),
+            },
+            seed: u64::from_le_bytes(reader.take(8)?.try_into().unwrap()),
+            instructions_per_frame: reader.u16()?,
+        };
+
+        let mut events = Vec::new();
+        while reader.position < bytes.len() {
+            let event = match reader.u8()? {
+                INPUT => MovieEvent::Input {
+                    frame: reader.u32()?,
+                    keys: reader.u16()?,
+                },
+                CHECKPOINT => MovieEvent::Checkpoint {
+                    frame: reader.u32()?,
+                    hash: reader.u32()?,
+                },
+                END => MovieEvent::End {
+                    frame: reader.u32()?,
+                },
+                _ => return Err(MovieError::Invalid("unknown event")),
+            };
+            events.push(event);
+        }
+        if !matches!(events.last(), Some(MovieEvent::End { .. })) {
+            return Err(MovieError::Truncated);
+        }
+
+        Ok(Movie { header, events })
+    }
+}
+
+/// Hash of everything a movie checks for desyncs: framebuffer and memory
+pub fn state_hash(chip8: &Chip8) -> u32 {
+    let mut bytes = chip8.memory().bytes().to_vec();
+    for row in chip8.cpu().display().pixels().iter() {
+        bytes.extend_from_slice(row);
+    }
+    crc32(&bytes)
+}
+
+/// Records keypad changes, sampled at the start of every frame
+pub struct Recorder {
+    movie: Movie,
+    frame: u32,
+    keys: u16,
+}
+
+impl Recorder {
+    /// Start recording; the machine must be freshly loaded with the ROM.
+    /// Movies always run a flat number of instructions per frame.
+    pub fn new(header: MovieHeader, chip8: &mut Chip8) -> Recorder {
+        chip8.set_rng(header.rng());
+        chip8.set_platform(header.platform);
+        chip8.set_timing(Timing::Instructions);
+        chip8.set_instructions_per_frame(header.instructions_per_frame);
+        Recorder {
+            movie: Movie {
+                header,
+                events: Vec::new(),
+            },
+            frame: 0,
+            keys: 0,
+        }
+    }
+
+    /// Run one frame, recording input changes and checkpoints
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> bool {
+        let keys = chip8.cpu().read_keys();
+        if keys != self.keys {
+            self.movie.events.push(MovieEvent::Input {
+                frame: self.frame,
+                keys,
+            });
+            self.keys = keys;
+        }
+
+        let running = chip8.run_frame();
+        if self.frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.movie.events.push(MovieEvent::Checkpoint {
+                frame: self.frame,
+                hash: state_hash(chip8),
+            });
+        }
+        self.frame += 1;
+        running
+    }
+
+    pub fn finish(mut self) -> Movie {
+        self.movie
+            .events
+            .push(MovieEvent::End { frame: self.frame });
+        self.movie
+    }
+}
+
+/// Replays a movie, failing on the first checkpoint that doesn't match
+pub struct Player {
+    movie: Movie,
+    next_event: usize,
+    frame: u32,
+}
+
+impl Player {
+    /// Start playback; the machine must be freshly loaded with `rom`
+    pub fn new(movie: Movie, rom: &[u8], chip8: &mut Chip8) -> Result<Player, MovieError> {
+        let actual = crc32(rom);
+        if movie.header.rom_hash != actual {
+            return Err(MovieError::WrongRom {
+                expected: movie.header.rom_hash,
+                actual,
+            });
+        }
+        chip8.set_rng(movie.header.rng());
+        chip8.set_platform(movie.header.platform);
+        chip8.set_timing(Timing::Instructions);
+        chip8.set_instructions_per_frame(movie.header.instructions_per_frame);
+        Ok(Player {
+            movie,
+            next_event: 0,
+            frame: 0,
+        })
+    }
+
+    pub fn header(&self) -> &MovieHeader {
+        &self.movie.header
+    }
+
+    /// Play one frame. Returns Ok(false) once the movie is over.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<bool, MovieError> {
+        while let Some(&event) = self.movie.events.get(self.next_event) {
+            match event {
+                MovieEvent::Input { frame, keys } if frame == self.frame => {
+                    let changed = keys ^ chip8.cpu().read_keys();
+                    for key in (0..16).filter(|k| changed & (1 << k) != 0) {
+                        chip8.cpu_mut().set_key(key, keys & (1 << key) != 0);
+                    }
+                }
+                MovieEvent::Checkpoint { frame, .. } if frame < self.frame => {}
+                MovieEvent::End { frame } if frame == self.frame => return Ok(false),
+                _ => break,
+            }
+            self.next_event += 1;
+        }
+
+        if !chip8.run_frame() {
+            return Ok(false);
+        }
+
+        if let Some(&MovieEvent::Checkpoint { frame, hash }) =
+            self.movie.events.get(self.next_event)
+        {
+            if frame == self.frame {
+                let actual = state_hash(chip8);
+                if actual != hash {
+                    return Err(MovieError::Desync {
+                        frame,
+                        expected: hash,
+                        actual,
+                    });
+                }
+                self.next_event += 1;
+            }
+        }
+        self.frame += 1;
+        Ok(true)
+    }
+}
+
+// Strings are at most 255 bytes; longer ones are cut short at a character
+// boundary so they still read back as UTF-8
+fn write_string(bytes: &mut Vec<u8>, text: &str) {
+    let mut length = text.len().min(255);
+    while !text.is_char_boundary(length) {
+        length -= 1;
+    }
+    let text = &text.as_bytes()[..length];
+    bytes.push(text.len() as u8);
+    bytes.extend_from_slice(text);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MovieError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or(MovieError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MovieError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MovieError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, MovieError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, MovieError> {
+        let length = self.u8()? as usize;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| MovieError::Invalid("header text is not UTF-8"))
+    }
+}
@@ -0,0 +1,129 @@
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Frames per second of the CHIP-8 timers, and so of the emulation
+pub const FRAME_RATE: u32 = 60;
+
+// When the host falls further behind than this, the missed time is dropped
+// instead of being caught up
+const MAX_FRAME_SKIP: u32 = 5;
+
+/// A monotonic clock for the pacer to read and sleep on. With `std` this is
+/// `SystemClock`; embedded hosts can use a hardware timer.
+pub trait Clock {
+    /// Time since some fixed point, never going backwards
+    fn now(&mut self) -> Duration;
+
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// The host's monotonic clock and `thread::sleep`
+#[cfg(feature = "std")]
+pub struct SystemClock {
+    start: Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Paces emulated frames against a monotonic clock.
+///
+/// Frame deadlines are fixed multiples of the frame period from the start,
+/// so oversleeping on one frame is made up on the next instead of adding up.
+pub struct FramePacer<C> {
+    clock: C,
+    period: Duration,
+    next_frame: Duration,
+    turbo: bool,
+}
+
+#[cfg(feature = "std")]
+impl Default for FramePacer<SystemClock> {
+    fn default() -> FramePacer<SystemClock> {
+        FramePacer::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl FramePacer<SystemClock> {
+    pub fn new() -> FramePacer<SystemClock> {
+        FramePacer::with_clock(SystemClock::new())
+    }
+}
+
+impl<C: Clock> FramePacer<C> {
+    pub fn with_clock(mut clock: C) -> FramePacer<C> {
+        FramePacer {
+            next_frame: clock.now(),
+            clock,
+            period: Duration::from_secs(1) / FRAME_RATE,
+            turbo: false,
+        }
+    }
+
+    /// In turbo mode frames run back to back, as fast as the host allows
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+        self.reset();
+    }
+
+    pub fn turbo(&self) -> bool {
+        self.turbo
+    }
+
+    /// Start pacing from now, e.g. after blocking on a prompt
+    pub fn reset(&mut self) {
+        self.next_frame = self.clock.now();
+    }
+
+    /// Sleep until the next frame is due and return how many frames to run.
+    /// This is more than one when the host has fallen behind; the extra
+    /// frames should be emulated without presenting them.
+    pub fn wait(&mut self) -> u32 {
+        if self.turbo {
+            return 1;
+        }
+
+        let now = self.clock.now();
+        if now < self.next_frame {
+            self.clock.sleep(self.next_frame - now);
+        }
+
+        let now = self.clock.now();
+        let behind = now.saturating_sub(self.next_frame).as_nanos() / self.period.as_nanos();
+        let frames = behind.min(MAX_FRAME_SKIP as u128 + 1) as u32 + 1;
+        if frames > MAX_FRAME_SKIP + 1 {
+            self.next_frame = now + self.period;
+            return MAX_FRAME_SKIP + 1;
+        }
+        self.next_frame += self.period * frames;
+        frames
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String};
+
+#[cfg(feature = "alloc")]
+use crate::state::StateWriter;
+use crate::state::{StateError, StateReader};
+
+/// A machine whose CHIP-8 interpreter the emulator can behave like
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    /// Common modern behaviour, what most ROMs written today expect
+    Chip8,
+    /// The original interpreter on the RCA COSMAC VIP
+    CosmacVip,
+}
+
+/// Behaviours that differ between interpreters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// FX55 and FX65 leave I pointing past the last register stored or
+    /// loaded, instead of where it was
+    pub memory_increment: bool,
+    /// DXYN waits for the next vertical blank before drawing, so at most
+    /// one sprite is drawn per frame
+    pub display_wait: bool,
+    /// Sprites are cut off at the edges of the screen instead of wrapping
+    /// round to the other side
+    pub clipping: bool,
+    /// 8XY6 and 8XYE shift VX in place, as CHIP-48 does, instead of
+    /// shifting VY into VX
+    pub shift_in_place: bool,
+    /// BNNN jumps to NNN + VX, X being the top digit of NNN, as CHIP-48
+    /// does, instead of NNN + V0
+    pub jump_with_vx: bool,
+}
+
+impl Platform {
+    #[cfg(feature = "alloc")]
+    pub fn parse(name: &str) -> Result<Platform, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip-8" | "chip8" => Ok(Platform::Chip8),
+            "cosmac-vip" | "vip" => Ok(Platform::CosmacVip),
+            _ => Err(format!("unknown platform '{}'", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip-8",
+            Platform::CosmacVip => "cosmac-vip",
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(match self {
+            Platform::Chip8 => 0,
+            Platform::CosmacVip => 1,
+        });
+    }
+
+    pub fn from_state(reader: &mut StateReader) -> Result<Platform, StateError> {
+        match reader.read_u8()? {
+            0 => Ok(Platform::Chip8),
+            1 => Ok(Platform::CosmacVip),
+            _ => Err(StateError::Invalid("unknown platform")),
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                display_wait: false,
+                clipping: false,
+                shift_in_place: true,
+                jump_with_vx: false,
+            },
+            Platform::CosmacVip => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                display_wait: true,
+                clipping: true,
+                shift_in_place: false,
+                jump_with_vx: false,
+            },
+        }
+    }
+}
+
+impl Quirks {
+    /// Save the quirks as one byte of flags
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        let flags = [
+            self.vf_reset,
+            self.memory_increment,
+            self.display_wait,
+            self.clipping,
+            self.shift_in_place,
+            self.jump_with_vx,
+        ];
+        let byte = flags
+            .iter()
+            .enumerate()
+            .fold(0, |byte, (bit, &on)| byte | (on as u8) << bit);
+        writer.write_u8(byte);
+    }
+
+    pub fn from_state(reader: &mut StateReader) -> Result<Quirks, StateError> {
+        let byte = reader.read_u8()?;
+        if byte >> 6 != 0 {
+            return Err(StateError::Invalid("unknown quirks"));
+        }
+        let flag = |bit: u8| byte & (1 << bit) != 0;
+        Ok(Quirks {
+            vf_reset: flag(0),
+            memory_increment: flag(1),
+            display_wait: flag(2),
+            clipping: flag(3),
+            shift_in_place: flag(4),
+            jump_with_vx: flag(5),
+        })
+    }
+}
@@ -0,0 +1,264 @@
+//! Python bindings, built with the `python` feature by maturin:
+//!
+//! ```text
+//! maturin develop --features python
+//! pytest tests/python
+//! ```
+//!
+//! ```python
+//! import numpy as np
+//! from chip8 import Chip8
+//!
+//! chip8 = Chip8(seed=0)
+//! chip8.load_rom(open("data/PONG", "rb").read())
+//! chip8.run_frame()
+//! frame = np.array(chip8.screen)  # (32, 64) uint8, 0 or 1
+//! ```
+//!
+//! `screen`, `memory` and `registers` are live read-only views: they follow
+//! the machine as it runs, so copy them to keep a snapshot.
+
+use std::ffi::{c_int, c_void};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyMemoryView};
+
+use crate::chip8::Chip8;
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::platform::Platform;
+use crate::rng::Rng;
+
+fn value_error<E: ToString>(error: E) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// A CHIP-8 machine
+#[pyclass(name = "Chip8", module = "chip8")]
+pub struct PyChip8 {
+    // Python objects can be shared between threads, and the machine isn't
+    // Sync
+    chip8: Mutex<Chip8>,
+}
+
+impl PyChip8 {
+    fn machine(&self) -> MutexGuard<'_, Chip8> {
+        self.chip8.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn machine_mut(&mut self) -> &mut Chip8 {
+        self.chip8.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[pymethods]
+impl PyChip8 {
+    /// Without a seed the RNG is seeded from the time
+    #[new]
+    #[pyo3(signature = (seed = None, platform = "chip-8"))]
+    fn new(seed: Option<u64>, platform: &str) -> PyResult<PyChip8> {
+        let mut builder =
+            Chip8::builder().platform(Platform::parse(platform).map_err(value_error)?);
+        if let Some(seed) = seed {
+            builder = builder.rng(Rng::new(seed));
+        }
+        Ok(PyChip8 {
+            chip8: Mutex::new(builder.build()),
+        })
+    }
+
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.machine_mut().load_program(rom).map_err(value_error)
+    }
+
+    /// Run one instruction. Returns False if the program stopped.
+    fn step(&mut self) -> bool {
+        self.machine_mut().step()
+    }
+
+    /// Run one 60 Hz frame. Returns False if the program stopped.
+    fn run_frame(&mut self) -> bool {
+        self.machine_mut().run_frame()
+    }
+
+    fn set_key(&mut self, key: u8, pressed: bool) -> PyResult<()> {
+        self.machine_mut()
+            .set_key(key, pressed)
+            .map_err(value_error)
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.machine().save_state())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.machine_mut().load_state(state).map_err(value_error)
+    }
+
+    #[getter]
+    fn instructions_per_frame(&self) -> u16 {
+        self.machine().instructions_per_frame()
+    }
+
+    #[setter]
+    fn set_instructions_per_frame(&mut self, instructions: u16) {
+        self.machine_mut().set_instructions_per_frame(instructions);
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.machine().cpu().read_pc()
+    }
+
+    /// The I register
+    #[getter]
+    fn index(&self) -> u16 {
+        self.machine().cpu().read_i()
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.machine().cpu().read_sound_timer()
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.machine().cpu().read_delay_timer()
+    }
+
+    fn set_register(&mut self, x: usize, value: u8) -> PyResult<()> {
+        if x >= 16 {
+            return Err(PyValueError::new_err(format!("no register V{:X}", x)));
+        }
+        self.machine_mut().cpu_mut().write_register(x, value);
+        Ok(())
+    }
+
+    /// The display, 32 rows of 64 pixels
+    #[getter]
+    fn screen(slf: Py<PyChip8>) -> View {
+        View::new(slf, Region::Screen, &[DISPLAY_HEIGHT, DISPLAY_WIDTH])
+    }
+
+    /// The 4 KiB address space
+    #[getter]
+    fn memory(slf: Py<PyChip8>, py: Python) -> View {
+        let len = slf.borrow(py).machine().memory().len();
+        View::new(slf, Region::Memory, &[len])
+    }
+
+    /// V0 to VF
+    #[getter]
+    fn registers(slf: Py<PyChip8>) -> View {
+        View::new(slf, Region::Registers, &[16])
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Region {
+    Screen,
+    Memory,
+    Registers,
+}
+
+/// A read-only buffer over part of a machine, for `numpy.asarray` or
+/// `memoryview`
+#[pyclass(module = "chip8", frozen)]
+pub struct View {
+    chip8: Py<PyChip8>,
+    region: Region,
+    // The buffer protocol points into these, so they live as long as the view
+    shape: Vec<ffi::Py_ssize_t>,
+    strides: Vec<ffi::Py_ssize_t>,
+}
+
+impl View {
+    fn new(chip8: Py<PyChip8>, region: Region, shape: &[usize]) -> View {
+        let shape: Vec<_> = shape.iter().map(|&n| n as ffi::Py_ssize_t).collect();
+        // Bytes, row by row
+        let mut strides = vec![1; shape.len()];
+        for i in (0..shape.len() - 1).rev() {
+            strides[i] = strides[i + 1] * shape[i + 1];
+        }
+        View {
+            chip8,
+            region,
+            shape,
+            strides,
+        }
+    }
+}
+
+#[pymethods]
+impl View {
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("view is read-only"));
+        }
+
+        let this = slf.get();
+        let chip8 = this.chip8.borrow(slf.py());
+        let chip8 = chip8.machine();
+        let data: &[u8] = match this.region {
+            Region::Screen => chip8.framebuffer().as_flattened(),
+            Region::Memory => chip8.memory().bytes(),
+            Region::Registers => chip8.cpu().registers(),
+        };
+
+        // The machine can't move while the view holds it, so the pointer
+        // stays good after the borrow and the lock end
+        let view = &mut *view;
+        view.buf = data.as_ptr() as *mut c_void;
+        view.len = data.len() as ffi::Py_ssize_t;
+        view.readonly = 1;
+        view.itemsize = 1;
+        view.format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+            c"B".as_ptr() as *mut _
+        } else {
+            ptr::null_mut()
+        };
+        view.ndim = this.shape.len() as c_int;
+        view.shape = if flags & ffi::PyBUF_ND == ffi::PyBUF_ND {
+            this.shape.as_ptr() as *mut _
+        } else {
+            ptr::null_mut()
+        };
+        view.strides = if flags & ffi::PyBUF_STRIDES == ffi::PyBUF_STRIDES {
+            this.strides.as_ptr() as *mut _
+        } else {
+            ptr::null_mut()
+        };
+        view.suboffsets = ptr::null_mut();
+        view.internal = ptr::null_mut();
+        view.obj = slf.clone().into_any().into_ptr();
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.shape[0] as usize
+    }
+
+    /// Indexing and slicing as on a `memoryview`
+    fn __getitem__<'py>(
+        slf: &Bound<'py, Self>,
+        index: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        PyMemoryView::from(slf.as_any())?.get_item(index)
+    }
+}
+
+#[pymodule]
+fn chip8(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChip8>()?;
+    module.add_class::<View>()?;
+    module.add("DISPLAY_WIDTH", DISPLAY_WIDTH)?;
+    module.add("DISPLAY_HEIGHT", DISPLAY_HEIGHT)?;
+    Ok(())
+}
@@ -0,0 +1,182 @@
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, vec, vec::Vec};
+
+#[cfg(feature = "alloc")]
+use crate::cpu::{is_straight_line, Cpu};
+#[cfg(feature = "alloc")]
+use crate::decode::{decode, Instruction};
+#[cfg(feature = "alloc")]
+use crate::memory::Memory;
+#[cfg(feature = "alloc")]
+use crate::rng::RandomSource;
+
+// Longest run of instructions compiled into one block
+#[cfg(feature = "alloc")]
+const MAX_BLOCK_LENGTH: usize = 64;
+
+/// How `Chip8` runs instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    /// Fetch, decode and execute one instruction at a time
+    Interpreter,
+    /// Compile straight-line runs of instructions into blocks and run
+    /// those, for bulk headless runs. Traced runs, watchpoints and VIP
+    /// timing fall back to the interpreter, as does everything without
+    /// `alloc`.
+    Recompiler,
+}
+
+impl Engine {
+    #[cfg(feature = "alloc")]
+    pub fn parse(name: &str) -> Result<Engine, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "interpreter" => Ok(Engine::Interpreter),
+            "recompiler" => Ok(Engine::Recompiler),
+            _ => Err(format!("unknown engine '{}'", name)),
+        }
+    }
+}
+
+/// Instructions from `start` up to the first one that branches, skips,
+/// waits, writes memory or can stop the program
+#[cfg(feature = "alloc")]
+struct Block {
+    start: u16,
+    // Decoded already, and run by `Cpu::execute_straight_line`, so they
+    // never branch and leave the program counter for the block to set
+    ops: Vec<Instruction>,
+    // The instruction that ends the block, run by the interpreter
+    exit: Option<Instruction>,
+}
+
+#[cfg(feature = "alloc")]
+impl Block {
+    // One past the last byte of code the block was compiled from
+    fn end(&self) -> usize {
+        let length = self.ops.len() + self.exit.is_some() as usize;
+        self.start as usize + 2 * length
+    }
+}
+
+/// Compiled blocks by start address. Writing to memory a block was compiled
+/// from throws the block away, so self-modifying code is recompiled.
+#[cfg(feature = "alloc")]
+pub struct Recompiler {
+    blocks: Vec<Option<Block>>,
+    // Number of blocks compiled from each byte of memory
+    coverage: Vec<u16>,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for Recompiler {
+    fn default() -> Recompiler {
+        Recompiler::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Recompiler {
+    pub fn new() -> Recompiler {
+        Recompiler {
+            blocks: (0..4096).map(|_| None).collect(),
+            coverage: vec![0; 4096],
+        }
+    }
+
+    /// Run compiled blocks one after another from the program counter,
+    /// for at most `budget` instructions. Stops early when a draw waits for
+    /// the vertical blank or the program counter runs off the end of memory.
+    /// Returns the number of instructions run, counting one that stopped the
+    /// program, and whether the program is still running.
+    pub fn run<R: RandomSource + ?Sized>(
+        &mut self,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+        rng: &mut R,
+        budget: usize,
+    ) -> (usize, bool) {
+        let mut count = 0;
+        while count < budget && !cpu.waiting_for_vblank() {
+            // Before every lookup, so loading a program or a state, or a
+            // write from outside, never runs a block compiled from old code
+            if let Some((start, end)) = memory.take_written() {
+                self.invalidate(start as usize, end as usize);
+            }
+            let pc = cpu.read_pc() as usize;
+            if pc + 1 >= memory.len() {
+                break;
+            }
+            if self.blocks[pc].is_none() {
+                self.insert(compile(pc, memory));
+            }
+
+            let block = self.blocks[pc].as_ref().unwrap();
+            let mut ran = 0;
+            for &op in block.ops.iter().take(budget - count) {
+                rng.on_fetch();
+                cpu.execute_straight_line(op, rng);
+                ran += 1;
+            }
+            cpu.write_pc((pc + 2 * ran) as u16);
+            count += ran;
+            if count < budget {
+                if let Some(exit) = block.exit {
+                    rng.on_fetch();
+                    count += 1;
+                    if !cpu.execute(exit, memory, rng) {
+                        return (count, false);
+                    }
+                }
+            }
+        }
+        (count, true)
+    }
+
+    fn insert(&mut self, block: Block) {
+        for covered in self.coverage[block.start as usize..block.end()].iter_mut() {
+            *covered += 1;
+        }
+        let start = block.start as usize;
+        self.blocks[start] = Some(block);
+    }
+
+    // Drop every block compiled from bytes start..=end
+    fn invalidate(&mut self, start: usize, end: usize) {
+        if !self.coverage[start..=end].iter().any(|&count| count > 0) {
+            return;
+        }
+        // A block reaches at most this far back from the bytes it covers
+        let first = start.saturating_sub(2 * (MAX_BLOCK_LENGTH + 1));
+        for address in first..=end {
+            let overlaps = matches!(&self.blocks[address], Some(block) if block.end() > start);
+            if overlaps {
+                let block = self.blocks[address].take().unwrap();
+                for covered in self.coverage[address..block.end()].iter_mut() {
+                    *covered -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn compile(start: usize, memory: &Memory) -> Block {
+    let mut ops = Vec::new();
+    let mut exit = None;
+    let mut address = start;
+    while ops.len() < MAX_BLOCK_LENGTH && address + 1 < memory.len() {
+        let instruction = decode(memory.fetch_opcode(address));
+        if is_straight_line(instruction) {
+            ops.push(instruction);
+        } else {
+            exit = Some(instruction);
+            break;
+        }
+        address += 2;
+    }
+    Block {
+        start: start as u16,
+        ops,
+        exit,
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::chip8::Chip8;
+
+/// Ring buffer of machine snapshots for stepping back in time.
+///
+/// Only the newest snapshot is kept whole; every older one is stored as a
+/// delta against the snapshot that followed it, since most of memory doesn't
+/// change between frames. The oldest deltas are dropped once the buffer
+/// grows past its byte budget.
+pub struct Rewind {
+    interval: usize,
+    budget: usize,
+    ticks: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    delta_bytes: usize,
+}
+
+impl Rewind {
+    /// Snapshot every `interval` ticks, using at most about `budget` bytes
+    pub fn new(interval: usize, budget: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+            ticks: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    /// Count a tick (usually a frame), taking a snapshot every `interval` ticks
+    pub fn tick(&mut self, chip8: &Chip8) {
+        if self.ticks.is_multiple_of(self.interval) {
+            self.record(chip8);
+        }
+        self.ticks += 1;
+    }
+
+    /// Take a snapshot now
+    pub fn record(&mut self, chip8: &Chip8) {
+        let snapshot = chip8.save_state();
+        if let Some(previous) = self.latest.take() {
+            let delta = diff(&snapshot, &previous);
+            self.delta_bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(snapshot);
+
+        let latest_bytes = self.latest.as_ref().map_or(0, Vec::len);
+        while self.delta_bytes + latest_bytes > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.delta_bytes -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Restore the most recent snapshot and drop it from the buffer, so
+    /// repeated calls walk further back. Returns false when there is
+    /// nothing left to rewind to.
+    pub fn step_back(&mut self, chip8: &mut Chip8) -> bool {
+        let latest = match self.latest.take() {
+            Some(latest) => latest,
+            None => return false,
+        };
+        chip8
+            .load_state(&latest)
+            .expect("rewind snapshots are always valid save states");
+
+        if let Some(delta) = self.deltas.pop_back() {
+            self.delta_bytes -= delta.len();
+            self.latest = Some(patch(&latest, &delta));
+        }
+        self.ticks = 0;
+        true
+    }
+
+    /// Number of snapshots that can be stepped back through
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.latest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+}
+
+/// Encode the bytes that differ between `from` and `to` as runs of
+/// (gap since the previous run, run length, new bytes), preceded by the
+/// length of `to`
+fn diff(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut delta = (to.len() as u32).to_le_bytes().to_vec();
+    let mut end_of_last_run = 0;
+    let mut i = 0;
+
+    while i < to.len() {
+        if from.get(i) == Some(&to[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < to.len() && from.get(i) != Some(&to[i]) {
+            i += 1;
+        }
+        delta.extend_from_slice(&((start - end_of_last_run) as u32).to_le_bytes());
+        delta.extend_from_slice(&((i - start) as u32).to_le_bytes());
+        delta.extend_from_slice(&to[start..i]);
+        end_of_last_run = i;
+    }
+
+    delta
+}
+
+/// Apply a delta produced by `diff(from, to)` to `from`, giving `to`
+fn patch(from: &[u8], delta: &[u8]) -> Vec<u8> {
+    let read_u32 = |at: usize| u32::from_le_bytes(delta[at..at + 4].try_into().unwrap()) as usize;
+
+    let mut to = from.to_vec();
+    to.resize(read_u32(0), 0);
+    let mut cursor = 4;
+    let mut position = 0;
+
+    while cursor < delta.len() {
+        position += read_u32(cursor);
+        let length = read_u32(cursor + 4);
+        cursor += 8;
+        to[position..position + length].copy_from_slice(&delta[cursor..cursor + length]);
+        cursor += length;
+        position += length;
+    }
+
+    to
+}
@@ -0,0 +1,123 @@
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "alloc")]
+use crate::state::StateWriter;
+use crate::state::{StateError, StateReader};
+
+/// Random bytes for CXNN. `Rng` is the built-in one; embedded hosts can
+/// plug in a hardware generator instead.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    /// Called for every instruction fetched
+    fn on_fetch(&mut self) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RngKind {
+    /// xorshift64*, the default
+    Xorshift,
+    /// Modelled on the COSMAC VIP interpreter, which built its random bytes
+    /// from R9, a counter advanced by every instruction fetch. Results depend
+    /// on exactly how many instructions ran between two CXNN.
+    CosmacVip,
+}
+
+/// Random number generator for CXNN, owned by the machine so runs can be
+/// reproduced from a seed
+#[derive(Clone, Debug)]
+pub struct Rng {
+    kind: RngKind,
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            kind: RngKind::Xorshift,
+            // xorshift gets stuck on zero, so spread the seed over the state first
+            state: splitmix64(seed) | 1,
+        }
+    }
+
+    pub fn cosmac_vip(seed: u64) -> Rng {
+        Rng {
+            kind: RngKind::CosmacVip,
+            state: seed & 0xFFFF,
+        }
+    }
+
+    /// Seed from the clock, for when runs don't need to be reproducible
+    #[cfg(feature = "std")]
+    pub fn from_time() -> Rng {
+        Rng::new(seed_from_time())
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(match self.kind {
+            RngKind::Xorshift => 0,
+            RngKind::CosmacVip => 1,
+        });
+        writer.write_bytes(&self.state.to_le_bytes());
+    }
+
+    pub fn from_state(reader: &mut StateReader) -> Result<Rng, StateError> {
+        let kind = match reader.read_u8()? {
+            0 => RngKind::Xorshift,
+            1 => RngKind::CosmacVip,
+            _ => return Err(StateError::Invalid("unknown random number generator")),
+        };
+        let state = u64::from_le_bytes(reader.read_array()?);
+        if kind == RngKind::Xorshift && state == 0 {
+            // xorshift would return zero forever
+            return Err(StateError::Invalid("xorshift state is zero"));
+        }
+        Ok(Rng { kind, state })
+    }
+}
+
+impl RandomSource for Rng {
+    fn on_fetch(&mut self) {
+        if self.kind == RngKind::CosmacVip {
+            self.state = self.state.wrapping_add(1) & 0xFFFF;
+        }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        match self.kind {
+            RngKind::Xorshift => {
+                let mut x = self.state;
+                x ^= x >> 12;
+                x ^= x << 25;
+                x ^= x >> 27;
+                self.state = x;
+                (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+            }
+            RngKind::CosmacVip => {
+                // R9.1 accumulates R9.0 and is the result
+                let low = self.state as u8;
+                let high = ((self.state >> 8) as u8).wrapping_add(low);
+                self.state = ((high as u64) << 8) | low as u64;
+                high
+            }
+        }
+    }
+}
+
+/// A seed that differs from run to run
+#[cfg(feature = "std")]
+pub fn seed_from_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,247 @@
+use std::fs;
+
+use crate::chip8::Chip8;
+use crate::debugger::{parse_number, Condition};
+use crate::keymap::parse_keypad_key;
+use crate::png;
+
+// Frames `wait-until` waits before failing, unless the script says otherwise
+const DEFAULT_TIMEOUT: u32 = 600;
+
+// Screenshots are scaled up so they can be looked at
+const SCREENSHOT_SCALE: u32 = 8;
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Run this many frames
+    Wait(u32),
+    Press(u8),
+    Release(u8),
+    ReleaseAll,
+    /// Press a key, run this many frames, then release it
+    Hold {
+        key: u8,
+        frames: u32,
+    },
+    /// Run frames until the condition holds, failing after `timeout` frames
+    WaitUntil {
+        source: String,
+        condition: Condition,
+        timeout: u32,
+    },
+    /// Save the framebuffer as a PNG
+    Screenshot(String),
+    /// Write a save state of the whole machine
+    SaveState(String),
+    Assert {
+        source: String,
+        condition: Condition,
+    },
+}
+
+/// A play-testing script, one command per line:
+///
+/// ```text
+/// # comments start with '#'
+/// wait 120                  # run 120 frames
+/// press 5                   # key down until released
+/// release 5
+/// hold 4 for 30             # key down for 30 frames
+/// release all
+/// wait-until pixel(10,20)==1
+/// wait-until V3==0 within 300
+/// screenshot out.png
+/// savestate out.state
+/// assert mem[0x300]==7
+/// ```
+///
+/// Conditions use the debugger's breakpoint condition syntax.
+pub struct Script {
+    commands: Vec<(usize, Command)>,
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Script, String> {
+        let mut commands = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let command = parse_command(line)
+                .map_err(|message| format!("line {}: {}", number + 1, message))?;
+            commands.push((number + 1, command));
+        }
+        Ok(Script { commands })
+    }
+
+    /// Run the script against a loaded machine, one frame at a time.
+    /// Returns the number of frames run, or the first failure.
+    pub fn run(&self, chip8: &mut Chip8) -> Result<u32, String> {
+        self.run_for(chip8, u32::MAX)
+    }
+
+    /// Run the script, stopping once `limit` frames have run
+    pub fn run_for(&self, chip8: &mut Chip8, limit: u32) -> Result<u32, String> {
+        self.run_with(chip8, limit, &mut |_, _| {})
+    }
+
+    /// Run the script like `run_for`, calling `on_frame` with the number of
+    /// frames run so far after each one
+    pub fn run_with(
+        &self,
+        chip8: &mut Chip8,
+        limit: u32,
+        on_frame: &mut dyn FnMut(u32, &Chip8),
+    ) -> Result<u32, String> {
+        let mut runner = Runner {
+            chip8,
+            frame: 0,
+            limit,
+            on_frame,
+        };
+        for (line, command) in self.commands.iter() {
+            if runner.frame == limit {
+                break;
+            }
+            runner
+                .execute(command)
+                .map_err(|message| format!("line {}: {}", line, message))?;
+        }
+        Ok(runner.frame)
+    }
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let (name, rest) = line
+        .split_once(char::is_whitespace)
+        .map(|(name, rest)| (name, rest.trim()))
+        .unwrap_or((line, ""));
+    let words: Vec<&str> = rest.split_whitespace().collect();
+
+    match (name, words.as_slice()) {
+        ("wait", [frames]) => Ok(Command::Wait(parse_frames(frames)?)),
+        ("press", [key]) => Ok(Command::Press(parse_keypad_key(key)?)),
+        ("release", ["all"]) => Ok(Command::ReleaseAll),
+        ("release", [key]) => Ok(Command::Release(parse_keypad_key(key)?)),
+        ("hold", [key, "for", frames]) => Ok(Command::Hold {
+            key: parse_keypad_key(key)?,
+            frames: parse_frames(frames)?,
+        }),
+        ("wait-until", [_, ..]) => {
+            let (source, timeout) = match rest.rsplit_once(" within ") {
+                Some((source, frames)) => (source.trim(), parse_frames(frames.trim())?),
+                None => (rest, DEFAULT_TIMEOUT),
+            };
+            Ok(Command::WaitUntil {
+                source: source.to_string(),
+                condition: Condition::parse(source)?,
+                timeout,
+            })
+        }
+        ("screenshot", [path]) => Ok(Command::Screenshot(path.to_string())),
+        ("savestate", [path]) => Ok(Command::SaveState(path.to_string())),
+        ("assert", [_, ..]) => Ok(Command::Assert {
+            source: rest.to_string(),
+            condition: Condition::parse(rest)?,
+        }),
+        ("wait", _) => Err("usage: wait <frames>".to_string()),
+        ("press", _) => Err("usage: press <0-F>".to_string()),
+        ("release", _) => Err("usage: release <0-F|all>".to_string()),
+        ("hold", _) => Err("usage: hold <0-F> for <frames>".to_string()),
+        ("wait-until", _) => Err("usage: wait-until <condition> [within <frames>]".to_string()),
+        ("screenshot", _) => Err("usage: screenshot <file.png>".to_string()),
+        ("savestate", _) => Err("usage: savestate <file>".to_string()),
+        ("assert", _) => Err("usage: assert <condition>".to_string()),
+        _ => Err(format!("unknown command '{}'", name)),
+    }
+}
+
+fn parse_frames(text: &str) -> Result<u32, String> {
+    parse_number(text).map(u32::from).or_else(|_| {
+        text.parse::<u32>()
+            .map_err(|_| format!("invalid frame count '{}'", text))
+    })
+}
+
+struct Runner<'a> {
+    chip8: &'a mut Chip8,
+    frame: u32,
+    limit: u32,
+    on_frame: &'a mut dyn FnMut(u32, &Chip8),
+}
+
+impl Runner<'_> {
+    fn execute(&mut self, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Wait(frames) => self.run_frames(*frames),
+            Command::Press(key) => {
+                self.chip8.cpu_mut().set_key(*key as usize, true);
+                Ok(())
+            }
+            Command::Release(key) => {
+                self.chip8.cpu_mut().set_key(*key as usize, false);
+                Ok(())
+            }
+            Command::ReleaseAll => {
+                for key in 0..16 {
+                    self.chip8.cpu_mut().set_key(key, false);
+                }
+                Ok(())
+            }
+            Command::Hold { key, frames } => {
+                self.chip8.cpu_mut().set_key(*key as usize, true);
+                let result = self.run_frames(*frames);
+                self.chip8.cpu_mut().set_key(*key as usize, false);
+                result
+            }
+            Command::WaitUntil {
+                source,
+                condition,
+                timeout,
+            } => {
+                for _ in 0..*timeout {
+                    if condition.evaluate(self.chip8) || self.frame == self.limit {
+                        return Ok(());
+                    }
+                    self.run_frames(1)?;
+                }
+                if condition.evaluate(self.chip8) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "timed out after {} frames waiting for {}",
+                        timeout, source
+                    ))
+                }
+            }
+            Command::Screenshot(path) => {
+                let image = png::encode_display(self.chip8.cpu().display(), SCREENSHOT_SCALE);
+                fs::write(path, image).map_err(|e| format!("could not write {}: {}", path, e))
+            }
+            Command::SaveState(path) => fs::write(path, self.chip8.save_state())
+                .map_err(|e| format!("could not write {}: {}", path, e)),
+            Command::Assert { source, condition } => {
+                if condition.evaluate(self.chip8) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "assertion failed at frame {}: {}",
+                        self.frame, source
+                    ))
+                }
+            }
+        }
+    }
+
+    fn run_frames(&mut self, frames: u32) -> Result<(), String> {
+        for _ in 0..frames.min(self.limit - self.frame) {
+            if !self.chip8.run_frame() {
+                return Err(format!("program stopped at frame {}", self.frame));
+            }
+            self.frame += 1;
+            (self.on_frame)(self.frame, self.chip8);
+        }
+        Ok(())
+    }
+}
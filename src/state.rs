@@ -0,0 +1,170 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+/// Save states start with this magic, followed by the format version,
+/// the payload length, the payload and a CRC-32 of everything before it
+pub const MAGIC: &[u8; 4] = b"C8SS";
+pub const VERSION: u16 = 5;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    BadChecksum,
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::BadChecksum => write!(f, "save state checksum mismatch"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "invalid save state: {}", what),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// Builds the payload of a save state
+#[cfg(feature = "alloc")]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for StateWriter {
+    fn default() -> StateWriter {
+        StateWriter::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter { data: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Wrap the payload with the header and checksum
+    pub fn finish(self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(HEADER_LEN + self.data.len() + 4);
+        state.extend_from_slice(MAGIC);
+        state.extend_from_slice(&VERSION.to_le_bytes());
+        state.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        state.extend_from_slice(&self.data);
+        let checksum = crc32(&state);
+        state.extend_from_slice(&checksum.to_le_bytes());
+        state
+    }
+}
+
+/// Reads the payload of a save state after its header and checksum are verified
+pub struct StateReader<'a> {
+    version: u16,
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn open(state: &'a [u8]) -> Result<StateReader<'a>, StateError> {
+        if state.len() < MAGIC.len() || &state[..MAGIC.len()] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        if state.len() < HEADER_LEN + 4 {
+            return Err(StateError::Truncated);
+        }
+
+        let version = u16::from_le_bytes([state[4], state[5]]);
+        if version == 0 || version > VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let length = u32::from_le_bytes([state[6], state[7], state[8], state[9]]) as usize;
+        // Compared this way round so a huge length can't overflow
+        if length != state.len() - HEADER_LEN - 4 {
+            return Err(StateError::Truncated);
+        }
+
+        let (body, checksum) = state.split_at(HEADER_LEN + length);
+        if crc32(body).to_le_bytes() != checksum {
+            return Err(StateError::BadChecksum);
+        }
+
+        Ok(StateReader {
+            version,
+            data: &body[HEADER_LEN..],
+            position: 0,
+        })
+    }
+
+    /// Format version the state was written with, for reading older states
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or(StateError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    /// Check that the whole payload was consumed
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.position == self.data.len() {
+            Ok(())
+        } else {
+            Err(StateError::Invalid("trailing data"))
+        }
+    }
+}
+
+/// CRC-32 (IEEE), as used by zip and PNG
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,103 @@
+use crate::cpu::Cpu;
+use crate::decode::Instruction;
+
+/// How much work the machine does in a 60 Hz frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+    /// A flat number of instructions per frame, whatever they are
+    Instructions,
+    /// Charge every instruction what it cost on the COSMAC VIP and run as
+    /// many as fit in a frame of VIP machine cycles
+    CosmacVip,
+}
+
+// The VIP's 1.76 MHz clock runs a machine cycle every 8 clocks,
+// giving 3668 machine cycles per 60 Hz frame
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668;
+
+// Every frame the CDP1861 video chip steals 8 cycles of DMA for each of its
+// 128 lines, and its interrupt routine (which also ticks the timers) takes
+// the rest
+pub const VIP_DISPLAY_INTERRUPT_CYCLES: u32 = 128 * 8 + 46;
+
+// Fetching and decoding any instruction in the interpreter's main loop
+const FETCH_CYCLES: u32 = 40;
+
+/// Extra cost of a skip instruction when it skips
+pub const VIP_SKIP_CYCLES: u32 = 4;
+
+/// Machine cycles the COSMAC VIP interpreter spends on an instruction,
+/// worked out from the machine state before it runs, including the extra
+/// `VIP_SKIP_CYCLES` of a skip that will be taken.
+///
+/// The costs follow disassemblies of the original interpreter. Loops whose
+/// length depends on data (sprites, BCD, register stores) are charged per
+/// iteration, so they're close rather than exact.
+pub fn vip_cycles(instruction: Instruction, cpu: &Cpu) -> u32 {
+    let register = |x: u8| cpu.read_register(x as usize);
+    let key = |x: u8| cpu.read_keys() & (1 << (register(x) & 0xF)) != 0;
+    let skip = |taken: bool| if taken { VIP_SKIP_CYCLES } else { 0 };
+
+    let cycles = match instruction {
+        // Clearing 256 bytes of display memory
+        Instruction::ClearScreen => 3078,
+        Instruction::Return => 10,
+        Instruction::System(_) | Instruction::Jump(_) => 12,
+        Instruction::Call(_) => 26,
+        Instruction::SkipIfEqual(x, nn) => 10 + skip(register(x) == nn),
+        Instruction::SkipIfNotEqual(x, nn) => 10 + skip(register(x) != nn),
+        Instruction::SkipIfRegistersEqual(x, y) => 14 + skip(register(x) == register(y)),
+        Instruction::SkipIfRegistersNotEqual(x, y) => 14 + skip(register(x) != register(y)),
+        Instruction::Set(..) => 6,
+        Instruction::Add(..) => 10,
+        Instruction::Copy(..) => 12,
+        Instruction::Or(..)
+        | Instruction::And(..)
+        | Instruction::Xor(..)
+        | Instruction::AddRegister(..)
+        | Instruction::Subtract(..)
+        | Instruction::ShiftRight(..)
+        | Instruction::SubtractReversed(..)
+        | Instruction::ShiftLeft(..) => 44,
+        Instruction::SetI(_) => 12,
+        Instruction::JumpOffset(address) => {
+            // Crossing into the next page costs another two cycles. The
+            // offset comes from the register `Cpu::execute` jumps with.
+            let x = if cpu.quirks().jump_with_vx {
+                (address >> 8) as u8
+            } else {
+                0
+            };
+            let target = address + register(x) as u16;
+            if target & 0xFF00 != address & 0xFF00 {
+                24
+            } else {
+                22
+            }
+        }
+        Instruction::Random(..) => 36,
+        Instruction::Draw(x, _, n) => {
+            // A sprite row straddles two display bytes unless X is
+            // a multiple of 8, which doubles the shifting and XOR work
+            let aligned = register(x).is_multiple_of(8);
+            let row = if aligned { 46 } else { 66 };
+            26 + n as u32 * row
+        }
+        Instruction::SkipIfKey(x) => 14 + skip(key(x)),
+        Instruction::SkipIfNotKey(x) => 14 + skip(!key(x)),
+        Instruction::ReadDelayTimer(_)
+        | Instruction::SetDelayTimer(_)
+        | Instruction::SetSoundTimer(_) => 10,
+        Instruction::WaitForKey(_) => 12,
+        Instruction::AddToI(_) | Instruction::FontCharacter(_) => 16,
+        Instruction::StoreBcd(x) => {
+            // One subtraction loop per unit in each decimal digit
+            let value = register(x) as u32;
+            84 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+        }
+        Instruction::StoreRegisters(x) | Instruction::LoadRegisters(x) => 14 + 14 * (x as u32 + 1),
+        Instruction::Unknown(_) => 12,
+    };
+
+    FETCH_CYCLES + cycles
+}
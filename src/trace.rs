@@ -0,0 +1,27 @@
+use core::fmt;
+
+/// Where the instruction trace goes. The CPU holds a `&'static` one, so
+/// embedded hosts can trace to a UART from a static without allocating.
+pub trait Trace: Sync {
+    /// Write part of a line; lines end with '\n'
+    fn write(&self, args: fmt::Arguments);
+}
+
+/// Trace to standard output, the default with `std`
+#[cfg(feature = "std")]
+pub struct Stdout;
+
+#[cfg(feature = "std")]
+impl Trace for Stdout {
+    fn write(&self, args: fmt::Arguments) {
+        print!("{}", args);
+    }
+}
+
+/// The trace a new CPU starts with: standard output with `std`, none without
+pub fn default_tracer() -> Option<&'static dyn Trace> {
+    #[cfg(feature = "std")]
+    return Some(&Stdout);
+    #[cfg(not(feature = "std"))]
+    return None;
+}
@@ -0,0 +1,115 @@
+//! JavaScript bindings, built with the `wasm` feature:
+//!
+//! ```text
+//! cargo rustc --lib --release --crate-type cdylib \
+//!     --target wasm32-unknown-unknown --features wasm
+//! wasm-bindgen --target web --out-dir pkg \
+//!     target/wasm32-unknown-unknown/release/chip_8_emulator.wasm
+//! wasm-pack test --node -- --features wasm
+//! ```
+//!
+//! ```js
+//! const chip8 = new Chip8(seed);
+//! chip8.loadRom(new Uint8Array(await rom.arrayBuffer()));
+//! // every animation frame:
+//! chip8.runFrame();
+//! const pixels = new Uint8Array(memory.buffer, chip8.framebufferPtr(), chip8.framebufferLen());
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::chip8::Chip8;
+use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::platform::Platform;
+use crate::rng::Rng;
+
+/// A machine for JavaScript to drive. The framebuffer is read straight out
+/// of wasm memory, one byte (0 or 1) per pixel, row by row.
+#[wasm_bindgen(js_name = Chip8)]
+pub struct WasmChip8 {
+    chip8: Chip8,
+}
+
+#[wasm_bindgen(js_class = Chip8)]
+impl WasmChip8 {
+    /// There is no clock to seed from in wasm, so the seed is required
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> WasmChip8 {
+        WasmChip8 {
+            chip8: Chip8::builder().rng(Rng::new(seed as u64)).build(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        Ok(self.chip8.load_program(rom)?)
+    }
+
+    /// Behave like another interpreter: "chip-8" or "cosmac-vip"
+    #[wasm_bindgen(js_name = setPlatform)]
+    pub fn set_platform(&mut self, name: &str) -> Result<(), JsError> {
+        self.chip8
+            .set_platform(Platform::parse(name).map_err(|e| JsError::new(&e))?);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setInstructionsPerFrame)]
+    pub fn set_instructions_per_frame(&mut self, instructions: u16) {
+        self.chip8.set_instructions_per_frame(instructions);
+    }
+
+    /// Run one 60 Hz frame. Returns false if the program stopped.
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) -> bool {
+        self.chip8.run_frame()
+    }
+
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), JsError> {
+        Ok(self.chip8.set_key(key, pressed)?)
+    }
+
+    /// Address of the framebuffer in wasm memory. It stays put for the
+    /// life of the machine.
+    #[wasm_bindgen(js_name = framebufferPtr)]
+    pub fn framebuffer_ptr(&self) -> *const u8 {
+        self.chip8.framebuffer().as_ptr() as *const u8
+    }
+
+    #[wasm_bindgen(js_name = framebufferLen)]
+    pub fn framebuffer_len(&self) -> usize {
+        DISPLAY_WIDTH * DISPLAY_HEIGHT
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        DISPLAY_WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        DISPLAY_HEIGHT
+    }
+
+    #[wasm_bindgen(getter, js_name = soundTimer)]
+    pub fn sound_timer(&self) -> u8 {
+        self.chip8.cpu().read_sound_timer()
+    }
+
+    /// The buzzer sounds while the sound timer is above zero
+    #[wasm_bindgen(getter, js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        self.chip8.cpu().read_sound_timer() > 0
+    }
+
+    /// Save state as a Uint8Array
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self) -> Vec<u8> {
+        self.chip8.save_state()
+    }
+
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsError> {
+        Ok(self.chip8.load_state(state)?)
+    }
+}
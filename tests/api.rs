@@ -0,0 +1,203 @@
+use chip_8_emulator::bench::{self, BenchConfig};
+use chip_8_emulator::script::Script;
+use chip_8_emulator::state::crc32;
+use chip_8_emulator::{Chip8, Chip8Error, Engine, Platform, Rng, StateError, Timing, STATE_SIZE};
+use common::machine;
+
+mod common;
+
+// 0x200: 7001  V0 += 1
+// 0x202: 1200  loop
+const COUNT_LOOP: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+// 0x200: D015  draw the font's 0 at (V0, V1)
+// 0x202: 7008  V0 += 8
+// 0x204: 1200  loop
+const DRAW_LOOP: [u8; 6] = [0xD0, 0x15, 0x70, 0x08, 0x12, 0x00];
+
+// 0x200: 6505  V5 = 5
+// 0x202: E59E  skip if key V5 is down
+// 0x204: 1202  jump back to 0x202
+// 0x206: 6107  V1 = 7
+// 0x208: 1208  loop forever
+const WAIT_FOR_KEY: [u8; 10] = [0x65, 0x05, 0xE5, 0x9E, 0x12, 0x02, 0x61, 0x07, 0x12, 0x08];
+
+// 0x200: 2204  call 0x204
+// 0x202: 1202  loop forever
+// 0x204: 2206  call 0x206
+// 0x206: 1206  loop forever
+const NESTED_CALLS: [u8; 8] = [0x22, 0x04, 0x12, 0x02, 0x22, 0x06, 0x12, 0x06];
+
+// Number of sprites DRAW_LOOP has drawn: the font's 0 is four pixels wide
+fn sprites_drawn(chip8: &Chip8) -> usize {
+    chip8.framebuffer()[0]
+        .chunks(8)
+        .filter(|sprite| sprite[..4] == [1, 1, 1, 1])
+        .count()
+}
+
+#[test]
+fn rom_must_fit_in_memory() {
+    let mut chip8 = Chip8::builder().build();
+    assert_eq!(
+        chip8.load_program(&[0; 3585]),
+        Err(Chip8Error::RomTooLarge {
+            size: 3585,
+            max: 3584
+        })
+    );
+    assert_eq!(chip8.load_program(&[0; 3584]), Ok(()));
+}
+
+#[test]
+fn step_runs_one_instruction() {
+    let mut chip8 = machine(&COUNT_LOOP);
+    assert!(chip8.step());
+    assert_eq!(chip8.cpu().read_register(0), 1);
+    assert_eq!(chip8.cpu().read_pc(), 0x202);
+}
+
+#[test]
+fn run_frame_runs_instructions_per_frame() {
+    let mut chip8 = Chip8::builder().instructions_per_frame(10).build();
+    chip8.load_program(&COUNT_LOOP).unwrap();
+    assert!(chip8.run_frame());
+    assert_eq!(chip8.cpu().read_register(0), 5);
+}
+
+#[test]
+fn keypad() {
+    let mut chip8 = machine(&WAIT_FOR_KEY);
+    chip8.run_frame();
+    assert_eq!(chip8.cpu().read_register(1), 0);
+
+    chip8.set_key(5, true).unwrap();
+    assert_eq!(chip8.keys(), 1 << 5);
+    chip8.run_frame();
+    assert_eq!(chip8.cpu().read_register(1), 7);
+
+    chip8.set_key(5, false).unwrap();
+    assert_eq!(chip8.keys(), 0);
+    assert_eq!(chip8.set_key(0x10, true), Err(Chip8Error::InvalidKey(0x10)));
+}
+
+#[test]
+fn save_states_round_trip() {
+    let mut chip8 = machine(&DRAW_LOOP);
+    chip8.step();
+    let state = chip8.save_state();
+    let framebuffer = *chip8.framebuffer();
+
+    chip8.run_frame();
+    assert_ne!(*chip8.framebuffer(), framebuffer);
+    chip8.load_state(&state).unwrap();
+    assert_eq!(*chip8.framebuffer(), framebuffer);
+    assert_eq!(chip8.save_state(), state);
+
+    assert_eq!(chip8.load_state(b"not a state"), Err(StateError::BadMagic));
+    assert_eq!(chip8.save_state(), state);
+}
+
+#[test]
+fn save_states_are_all_the_same_size() {
+    let mut chip8 = machine(&NESTED_CALLS);
+    for _ in 0..3 {
+        assert_eq!(chip8.save_state().len(), STATE_SIZE);
+        chip8.step();
+    }
+}
+
+#[test]
+fn version_4_states_still_load() {
+    let mut chip8 = machine(&NESTED_CALLS);
+    chip8.step();
+    let state = chip8.save_state();
+
+    // Version 4 held only the stack entries in use, here the first of 16,
+    // after the registers, pc, I and the depth
+    let payload = &state[10..state.len() - 4];
+    let used = 16 + 2 + 2 + 2 + 2;
+    let payload = [&payload[..used], &payload[used + 2 * 15..]].concat();
+    let mut old = b"C8SS".to_vec();
+    old.extend_from_slice(&4u16.to_le_bytes());
+    old.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    old.extend_from_slice(&payload);
+    old.extend_from_slice(&crc32(&old).to_le_bytes());
+
+    let mut loaded = machine(&COUNT_LOOP);
+    loaded.load_state(&old).unwrap();
+    assert_eq!(loaded.save_state(), state);
+}
+
+#[test]
+fn save_states_keep_platform_and_quirks() {
+    let mut quirks = Platform::CosmacVip.quirks();
+    quirks.clipping = false;
+    let mut vip = Chip8::builder()
+        .platform(Platform::CosmacVip)
+        .quirks(quirks)
+        .timing(Timing::CosmacVip)
+        .rng(Rng::new(0))
+        .build();
+    vip.load_program(&DRAW_LOOP).unwrap();
+    vip.run_frame();
+    let state = vip.save_state();
+
+    // Loaded into a plain CHIP-8 machine, the state brings the VIP along
+    let mut chip8 = Chip8::builder()
+        .timing(Timing::CosmacVip)
+        .rng(Rng::new(1))
+        .build();
+    chip8.run_frame();
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.platform(), Platform::CosmacVip);
+    assert_eq!(chip8.quirks(), quirks);
+    assert_eq!(chip8.save_state(), state);
+
+    for _ in 0..10 {
+        vip.run_frame();
+        chip8.run_frame();
+    }
+    assert_eq!(chip8.save_state(), vip.save_state());
+}
+
+#[test]
+fn builder_quirks_override_platform() {
+    let mut chip8 = Chip8::builder().platform(Platform::CosmacVip).build();
+    chip8.load_program(&DRAW_LOOP).unwrap();
+    // The first draw waits for the end of the first frame
+    chip8.run_frame();
+    chip8.run_frame();
+    chip8.run_frame();
+    assert_eq!(sprites_drawn(&chip8), 2);
+
+    let mut quirks = Platform::CosmacVip.quirks();
+    quirks.display_wait = false;
+    let mut chip8 = Chip8::builder()
+        .platform(Platform::CosmacVip)
+        .quirks(quirks)
+        .build();
+    chip8.load_program(&DRAW_LOOP).unwrap();
+    chip8.run_frame();
+    assert_eq!(chip8.platform(), Platform::CosmacVip);
+    // 16 instructions, every third one a draw
+    assert_eq!(sprites_drawn(&chip8), 6);
+}
+
+#[test]
+fn benchmarks_count_the_instructions_that_ran() {
+    let input = Script::parse("").unwrap();
+    for engine in [Engine::Interpreter, Engine::Recompiler] {
+        let config = BenchConfig {
+            frames: 10,
+            instructions_per_frame: 15,
+            engine,
+            decode_cache: true,
+        };
+        let result = bench::run(&COUNT_LOOP, &input, config).unwrap();
+        assert_eq!((result.frames, result.instructions), (10, 150));
+        // 7001 7001 00EE: stops on the third instruction, in the first frame
+        let result = bench::run(&[0x70, 0x01, 0x70, 0x01, 0x00, 0xEE], &input, config).unwrap();
+        assert_eq!((result.frames, result.instructions), (0, 3));
+    }
+}
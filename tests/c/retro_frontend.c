@@ -0,0 +1,261 @@
+/* A minimal libretro frontend: loads the core, feeds it a ROM and runs a few
+ * frames headless. Built and run by tests/libretro.rs:
+ *
+ *     retro_frontend <core> <rom>
+ */
+
+#include <dlfcn.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define CHECK(condition)                                                       \
+    do {                                                                       \
+        if (!(condition)) {                                                    \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                               \
+            exit(1);                                                           \
+        }                                                                      \
+    } while (0)
+
+/* The parts of libretro.h this frontend uses */
+#define RETRO_ENVIRONMENT_SET_PIXEL_FORMAT 10
+#define RETRO_ENVIRONMENT_GET_VARIABLE 15
+#define RETRO_ENVIRONMENT_SET_VARIABLES 16
+#define RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE 17
+#define RETRO_PIXEL_FORMAT_XRGB8888 1
+#define RETRO_DEVICE_JOYPAD 1
+
+struct retro_system_info {
+    const char *library_name;
+    const char *library_version;
+    const char *valid_extensions;
+    bool need_fullpath;
+    bool block_extract;
+};
+
+struct retro_system_av_info {
+    struct {
+        unsigned base_width, base_height, max_width, max_height;
+        float aspect_ratio;
+    } geometry;
+    struct {
+        double fps, sample_rate;
+    } timing;
+};
+
+struct retro_game_info {
+    const char *path;
+    const void *data;
+    size_t size;
+    const char *meta;
+};
+
+struct retro_variable {
+    const char *key;
+    const char *value;
+};
+
+typedef bool (*environment_t)(unsigned, void *);
+typedef void (*video_refresh_t)(const void *, unsigned, unsigned, size_t);
+typedef void (*audio_sample_t)(int16_t, int16_t);
+typedef size_t (*audio_sample_batch_t)(const int16_t *, size_t);
+typedef void (*input_poll_t)(void);
+typedef int16_t (*input_state_t)(unsigned, unsigned, unsigned, unsigned);
+
+static unsigned pixel_format = 0;
+static unsigned variables = 0;
+static unsigned frames = 0;
+static unsigned lit = 0;
+static uint32_t checksum = 0;
+static size_t samples = 0;
+static size_t loud_samples = 0;
+
+static bool environment(unsigned cmd, void *data) {
+    switch (cmd) {
+    case RETRO_ENVIRONMENT_SET_PIXEL_FORMAT:
+        pixel_format = *(const unsigned *)data;
+        return true;
+    case RETRO_ENVIRONMENT_SET_VARIABLES:
+        for (const struct retro_variable *v = data; v->key; v++) {
+            variables++;
+        }
+        return true;
+    case RETRO_ENVIRONMENT_GET_VARIABLE: {
+        struct retro_variable *v = data;
+        if (strcmp(v->key, "chip8_palette") == 0) {
+            v->value = "green";
+            return true;
+        }
+        return false;
+    }
+    case RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE:
+        *(bool *)data = false;
+        return true;
+    default:
+        return false;
+    }
+}
+
+static void video_refresh(const void *data, unsigned width, unsigned height, size_t pitch) {
+    CHECK(width == 64 && height == 32 && pitch >= width * 4);
+    frames++;
+    lit = 0;
+    checksum = 0;
+    for (unsigned y = 0; y < height; y++) {
+        const uint32_t *row = (const uint32_t *)((const uint8_t *)data + y * pitch);
+        for (unsigned x = 0; x < width; x++) {
+            lit += row[x] == 0x33FF66;
+            checksum = checksum * 31 + row[x];
+        }
+    }
+}
+
+static void audio_sample(int16_t left, int16_t right) {
+    (void)left;
+    (void)right;
+}
+
+static size_t audio_sample_batch(const int16_t *data, size_t count) {
+    for (size_t i = 0; i < count * 2; i++) {
+        loud_samples += data[i] != 0;
+    }
+    samples += count;
+    return count;
+}
+
+static void input_poll(void) {}
+
+static int16_t input_state(unsigned port, unsigned device, unsigned index, unsigned id) {
+    (void)port;
+    (void)device;
+    (void)index;
+    (void)id;
+    return 0;
+}
+
+static void *load(void *core, const char *name) {
+    void *symbol = dlsym(core, name);
+    if (!symbol) {
+        fprintf(stderr, "missing %s\n", name);
+        exit(1);
+    }
+    return symbol;
+}
+
+#define LOAD(name) name##_t name = (name##_t)load(core, #name)
+
+typedef unsigned (*retro_api_version_t)(void);
+typedef void (*retro_init_t)(void);
+typedef void (*retro_deinit_t)(void);
+typedef void (*retro_get_system_info_t)(struct retro_system_info *);
+typedef void (*retro_get_system_av_info_t)(struct retro_system_av_info *);
+typedef void (*retro_set_environment_t)(environment_t);
+typedef void (*retro_set_video_refresh_t)(video_refresh_t);
+typedef void (*retro_set_audio_sample_t)(audio_sample_t);
+typedef void (*retro_set_audio_sample_batch_t)(audio_sample_batch_t);
+typedef void (*retro_set_input_poll_t)(input_poll_t);
+typedef void (*retro_set_input_state_t)(input_state_t);
+typedef bool (*retro_load_game_t)(const struct retro_game_info *);
+typedef void (*retro_unload_game_t)(void);
+typedef void (*retro_run_t)(void);
+typedef void (*retro_reset_t)(void);
+typedef size_t (*retro_serialize_size_t)(void);
+typedef bool (*retro_serialize_t)(void *, size_t);
+typedef bool (*retro_unserialize_t)(const void *, size_t);
+
+static uint8_t *read_file(const char *path, size_t *size) {
+    FILE *file = fopen(path, "rb");
+    CHECK(file != NULL);
+    static uint8_t data[4096];
+    *size = fread(data, 1, sizeof data, file);
+    fclose(file);
+    return data;
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 3);
+    void *core = dlopen(argv[1], RTLD_NOW | RTLD_LOCAL);
+    if (!core) {
+        fprintf(stderr, "%s\n", dlerror());
+        return 1;
+    }
+
+    LOAD(retro_api_version);
+    LOAD(retro_init);
+    LOAD(retro_deinit);
+    LOAD(retro_get_system_info);
+    LOAD(retro_get_system_av_info);
+    LOAD(retro_set_environment);
+    LOAD(retro_set_video_refresh);
+    LOAD(retro_set_audio_sample);
+    LOAD(retro_set_audio_sample_batch);
+    LOAD(retro_set_input_poll);
+    LOAD(retro_set_input_state);
+    LOAD(retro_load_game);
+    LOAD(retro_unload_game);
+    LOAD(retro_run);
+    LOAD(retro_reset);
+    LOAD(retro_serialize_size);
+    LOAD(retro_serialize);
+    LOAD(retro_unserialize);
+
+    CHECK(retro_api_version() == 1);
+    retro_set_environment(environment);
+    CHECK(variables == 9);
+    retro_set_video_refresh(video_refresh);
+    retro_set_audio_sample(audio_sample);
+    retro_set_audio_sample_batch(audio_sample_batch);
+    retro_set_input_poll(input_poll);
+    retro_set_input_state(input_state);
+    retro_init();
+
+    struct retro_system_info info;
+    retro_get_system_info(&info);
+    CHECK(strcmp(info.library_name, "chip-8-in-rust") == 0);
+
+    struct retro_game_info game = {argv[2], NULL, 0, NULL};
+    game.data = read_file(argv[2], &game.size);
+    CHECK(retro_load_game(&game));
+    CHECK(pixel_format == RETRO_PIXEL_FORMAT_XRGB8888);
+
+    struct retro_system_av_info av;
+    retro_get_system_av_info(&av);
+    CHECK(av.geometry.base_width == 64 && av.geometry.base_height == 32);
+    CHECK(av.timing.fps == 60.0);
+
+    for (int i = 0; i < 10; i++) {
+        retro_run();
+    }
+    CHECK(frames == 10);
+    CHECK(lit > 0);
+    CHECK(samples == 10 * (size_t)(av.timing.sample_rate / av.timing.fps));
+
+    /* Save, run on, then rewind and check the same frames come out */
+    size_t size = retro_serialize_size();
+    CHECK(size > 0);
+    void *state = malloc(size);
+    CHECK(retro_serialize(state, size));
+    for (int i = 0; i < 30; i++) {
+        retro_run();
+    }
+    uint32_t expected = checksum;
+    CHECK(retro_unserialize(state, size));
+    for (int i = 0; i < 30; i++) {
+        retro_run();
+    }
+    CHECK(checksum == expected);
+    CHECK(!retro_unserialize(state, 3));
+    free(state);
+
+    retro_reset();
+    retro_run();
+    retro_unload_game();
+    retro_deinit();
+    dlclose(core);
+
+    printf("frames=%u lit=%u loud_samples=%zu\n", frames, lit, loud_samples);
+    return 0;
+}
@@ -0,0 +1,82 @@
+/* Drives the C API the way a test rig would; built and run by tests/ffi.rs */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define CHECK(condition)                                                       \
+    do {                                                                       \
+        if (!(condition)) {                                                    \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                               \
+            exit(1);                                                           \
+        }                                                                      \
+    } while (0)
+
+/* 0x200: 6005  V0 = 5
+ * 0x202: F018  sound timer = V0
+ * 0x204: D015  draw the font's 0 at (V0, V1)
+ * 0x206: 1206  loop forever */
+static const uint8_t PROGRAM[] = {0x60, 0x05, 0xF0, 0x18, 0xD0, 0x15, 0x12, 0x06};
+
+int main(void) {
+    chip8_t *chip8 = chip8_create(0);
+    CHECK(chip8 != NULL);
+
+    static uint8_t too_large[4096];
+    CHECK(chip8_load_rom(chip8, too_large, sizeof too_large) == CHIP8_ERROR_ROM_TOO_LARGE);
+    CHECK(chip8_load_rom(chip8, PROGRAM, sizeof PROGRAM) == CHIP8_OK);
+
+    /* Save before running, to come back to */
+    size_t len = 0;
+    CHECK(chip8_save_state(chip8, NULL, &len) == CHIP8_ERROR_BUFFER_TOO_SMALL);
+    CHECK(len > 0);
+    uint8_t *state = malloc(len);
+    CHECK(chip8_save_state(chip8, state, &len) == CHIP8_OK);
+
+    CHECK(chip8_step(chip8));
+    uint8_t v0 = 0;
+    CHECK(chip8_get_register(chip8, 0, &v0) == CHIP8_OK);
+    CHECK(v0 == 5);
+    CHECK(chip8_get_pc(chip8) == 0x202);
+
+    CHECK(chip8_run_frame(chip8));
+    const uint8_t *pixels = chip8_framebuffer(chip8);
+    CHECK(pixels != NULL);
+    CHECK(memcmp(&pixels[5], "\1\1\1\1", 4) == 0);
+    CHECK(memcmp(&pixels[CHIP8_DISPLAY_WIDTH + 5], "\1\0\0\1", 4) == 0);
+
+    CHECK(chip8_set_register(chip8, 0xF, 0xAB) == CHIP8_OK);
+    CHECK(chip8_set_register(chip8, 16, 0) == CHIP8_ERROR_INVALID_REGISTER);
+    CHECK(chip8_set_index(chip8, 0x300) == CHIP8_OK);
+    CHECK(chip8_get_index(chip8) == 0x300);
+    CHECK(chip8_set_delay_timer(chip8, 0x3C) == CHIP8_OK);
+    CHECK(chip8_get_delay_timer(chip8) == 0x3C);
+    CHECK(chip8_set_sound_timer(chip8, 7) == CHIP8_OK);
+    CHECK(chip8_get_sound_timer(chip8) == 7);
+    CHECK(chip8_get_sp(chip8) == 0);
+    CHECK(chip8_set_sp(chip8, 2) == CHIP8_OK);
+    CHECK(chip8_get_sp(chip8) == 2);
+    CHECK(chip8_set_sp(chip8, CHIP8_STACK_SIZE + 1) == CHIP8_ERROR_INVALID_STACK_DEPTH);
+    CHECK(chip8_set_key(chip8, 0xF, true) == CHIP8_OK);
+    CHECK(chip8_set_key(chip8, 0x10, true) == CHIP8_ERROR_INVALID_KEY);
+
+    CHECK(chip8_load_state(chip8, state, len) == CHIP8_OK);
+    CHECK(chip8_get_pc(chip8) == 0x200);
+    CHECK(chip8_get_sp(chip8) == 0);
+    CHECK(chip8_get_delay_timer(chip8) == 0);
+    CHECK(chip8_get_register(chip8, 0, &v0) == CHIP8_OK);
+    CHECK(v0 == 0);
+    CHECK(chip8_load_state(chip8, state, 3) != CHIP8_OK);
+    free(state);
+
+    CHECK(chip8_set_key(NULL, 0, true) == CHIP8_ERROR_NULL);
+    CHECK(strcmp(chip8_error_message(CHIP8_ERROR_INVALID_KEY), "no such keypad key") == 0);
+
+    chip8_destroy(chip8);
+    chip8_destroy(NULL);
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,17 @@
+// Fixtures shared by the integration tests, each of which pulls this in
+// with `mod common;` and uses only some of it
+#![allow(dead_code)]
+
+use chip_8_emulator::{Chip8, Engine, Rng};
+
+/// An interpreted machine with `program` loaded and its RNG seeded with 0
+pub fn machine(program: &[u8]) -> Chip8 {
+    machine_with(program, Engine::Interpreter, 0)
+}
+
+/// A machine with `program` loaded, on `engine`, its RNG seeded with `seed`
+pub fn machine_with(program: &[u8], engine: Engine, seed: u64) -> Chip8 {
+    let mut chip8 = Chip8::builder().engine(engine).rng(Rng::new(seed)).build();
+    chip8.load_program(program).unwrap();
+    chip8
+}
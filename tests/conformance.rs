@@ -0,0 +1,665 @@
+//! Conformance tests: small programs checking each opcode and each quirk,
+//! and the community test ROMs run headlessly on every platform with their
+//! final screens compared against golden images in tests/golden/conformance.
+//!
+//! The community ROMs have to be in tests/roms, or the test fails; see
+//! tests/roms/README.md.
+//! Run with `CHIP8_BLESS=1` to write the golden images again after a change
+//! that is meant to alter what a ROM draws.
+
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use chip_8_emulator::{Chip8, Engine, Framebuffer, Platform, Quirks, Rng, Timing};
+
+const PLATFORMS: [Platform; 2] = [Platform::Chip8, Platform::CosmacVip];
+
+fn machine(platform: Platform, engine: Engine, program: &[u8]) -> Chip8 {
+    let timing = match platform {
+        Platform::Chip8 => Timing::Instructions,
+        Platform::CosmacVip => Timing::CosmacVip,
+    };
+    let mut chip8 = Chip8::builder()
+        .platform(platform)
+        .timing(timing)
+        .engine(engine)
+        .rng(Rng::new(0))
+        .build();
+    chip8.load_program(program).unwrap();
+    chip8
+}
+
+// Run `steps` instructions of a program on every platform
+fn run_steps(program: &[u8], steps: usize, check: impl Fn(Platform, &Chip8)) {
+    for platform in PLATFORMS {
+        let mut chip8 = machine(platform, Engine::Interpreter, program);
+        for _ in 0..steps {
+            assert!(chip8.step(), "{}: program stopped", platform.name());
+        }
+        check(platform, &chip8);
+    }
+}
+
+fn registers(chip8: &Chip8) -> [u8; 16] {
+    *chip8.cpu().registers()
+}
+
+#[test]
+fn add_register_stores_sum_and_carry() {
+    // 60FF 6101 8014: V0 = FF + 01
+    run_steps(&[0x60, 0xFF, 0x61, 0x01, 0x80, 0x14], 3, |_, chip8| {
+        assert_eq!((registers(chip8)[0], registers(chip8)[0xF]), (0x00, 1));
+    });
+    // 6FFF 6101 8F14: the carry wins when X is VF
+    run_steps(&[0x6F, 0xFF, 0x61, 0x01, 0x8F, 0x14], 3, |_, chip8| {
+        assert_eq!(registers(chip8)[0xF], 1);
+    });
+}
+
+#[test]
+fn subtract_sets_no_borrow() {
+    // 6005 6105 8015: equal values don't borrow
+    run_steps(&[0x60, 0x05, 0x61, 0x05, 0x80, 0x15], 3, |_, chip8| {
+        assert_eq!((registers(chip8)[0], registers(chip8)[0xF]), (0x00, 1));
+    });
+    // 6003 6102 8015: 3 - 2
+    run_steps(&[0x60, 0x03, 0x61, 0x02, 0x80, 0x15], 3, |_, chip8| {
+        assert_eq!((registers(chip8)[0], registers(chip8)[0xF]), (0x01, 1));
+    });
+    // 6003 6102 8017: 2 - 3
+    run_steps(&[0x60, 0x03, 0x61, 0x02, 0x80, 0x17], 3, |_, chip8| {
+        assert_eq!((registers(chip8)[0], registers(chip8)[0xF]), (0xFF, 0));
+    });
+    // 6F01 6102 8F15: the flag wins when X is VF
+    run_steps(&[0x6F, 0x01, 0x61, 0x02, 0x8F, 0x15], 3, |_, chip8| {
+        assert_eq!(registers(chip8)[0xF], 0);
+    });
+}
+
+#[test]
+fn shifts_set_the_bit_shifted_out() {
+    // 6081 8006
+    run_steps(&[0x60, 0x81, 0x80, 0x06], 2, |_, chip8| {
+        assert_eq!((registers(chip8)[0], registers(chip8)[0xF]), (0x40, 1));
+    });
+    // 6041 800E
+    run_steps(&[0x60, 0x41, 0x80, 0x0E], 2, |_, chip8| {
+        assert_eq!((registers(chip8)[0], registers(chip8)[0xF]), (0x82, 0));
+    });
+}
+
+#[test]
+fn logic_ops() {
+    // 6033 6155 8200 8300 8400: V0 = 33, V1 = 55, V2-V4 = V0
+    // 8211 8312 8413 8510: V2 |= V1, V3 &= V1, V4 ^= V1, V5 = V1
+    let program = [
+        0x60, 0x33, 0x61, 0x55, 0x82, 0x00, 0x83, 0x00, 0x84, 0x00, 0x82, 0x11, 0x83, 0x12, 0x84,
+        0x13, 0x85, 0x10,
+    ];
+    run_steps(&program, 9, |_, chip8| {
+        assert_eq!(registers(chip8)[2..6], [0x77, 0x11, 0x66, 0x55]);
+    });
+}
+
+#[test]
+fn call_and_return() {
+    // 0x200: 2206  call 0x206
+    // 0x202: 6102  V1 = 2
+    // 0x204: 1204  loop
+    // 0x206: 6001  V0 = 1
+    // 0x208: 00EE  return
+    let program = [0x22, 0x06, 0x61, 0x02, 0x12, 0x04, 0x60, 0x01, 0x00, 0xEE];
+    run_steps(&program, 4, |_, chip8| {
+        assert_eq!(registers(chip8)[..2], [1, 2]);
+        assert_eq!(chip8.cpu().read_pc(), 0x204);
+        assert_eq!(chip8.cpu().read_sp(), 0);
+    });
+}
+
+#[test]
+fn return_with_empty_stack_stops() {
+    for platform in PLATFORMS {
+        let mut chip8 = machine(platform, Engine::Interpreter, &[0x00, 0xEE]);
+        assert!(!chip8.step());
+    }
+}
+
+#[test]
+fn skips() {
+    // 6005 6105 3005 6A01 4005 6B01 5010 6C01 9010 6D01
+    let program = [
+        0x60, 0x05, 0x61, 0x05, 0x30, 0x05, 0x6A, 0x01, 0x40, 0x05, 0x6B, 0x01, 0x50, 0x10, 0x6C,
+        0x01, 0x90, 0x10, 0x6D, 0x01,
+    ];
+    run_steps(&program, 8, |_, chip8| {
+        // Only the skips that don't skip let their load run
+        assert_eq!(registers(chip8)[0xA..0xE], [0, 1, 0, 1]);
+        assert_eq!(chip8.cpu().read_pc(), 0x214);
+    });
+}
+
+#[test]
+fn jumps_and_index() {
+    // 0x200: 6004  V0 = 4
+    // 0x202: B204  jump to 0x204 + V0
+    // 0x204: 0000
+    // 0x206: 0000
+    // 0x208: A123  I = 0x123
+    // 0x20A: F01E  I += V0
+    let program = [
+        0x60, 0x04, 0xB2, 0x04, 0x00, 0x00, 0x00, 0x00, 0xA1, 0x23, 0xF0, 0x1E,
+    ];
+    run_steps(&program, 4, |_, chip8| {
+        assert_eq!(chip8.cpu().read_i(), 0x127);
+        assert_eq!(chip8.cpu().read_pc(), 0x20C);
+    });
+}
+
+#[test]
+fn bcd_and_register_memory() {
+    // 60FE A300 F033 A300 F265: V0-V2 = digits of 254
+    let program = [0x60, 0xFE, 0xA3, 0x00, 0xF0, 0x33, 0xA3, 0x00, 0xF2, 0x65];
+    run_steps(&program, 5, |_, chip8| {
+        assert_eq!(registers(chip8)[..3], [2, 5, 4]);
+    });
+    // 6011 6122 A300 F155 6000 6100 A300 F165
+    let program = [
+        0x60, 0x11, 0x61, 0x22, 0xA3, 0x00, 0xF1, 0x55, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x00, 0xF1,
+        0x65,
+    ];
+    run_steps(&program, 8, |_, chip8| {
+        assert_eq!(registers(chip8)[..2], [0x11, 0x22]);
+        assert_eq!(chip8.memory().bytes()[0x300..0x302], [0x11, 0x22]);
+    });
+}
+
+#[test]
+fn timers() {
+    // 6030 F015 F018 F107
+    run_steps(
+        &[0x60, 0x30, 0xF0, 0x15, 0xF0, 0x18, 0xF1, 0x07],
+        4,
+        |_, chip8| {
+            assert_eq!(registers(chip8)[1], 0x30);
+            assert_eq!(chip8.cpu().read_sound_timer(), 0x30);
+        },
+    );
+}
+
+#[test]
+fn random_is_masked() {
+    // C00F C100
+    run_steps(&[0xC0, 0x0F, 0xC1, 0x00], 2, |_, chip8| {
+        assert!(registers(chip8)[0] <= 0x0F);
+        assert_eq!(registers(chip8)[1], 0);
+    });
+}
+
+#[test]
+fn keys() {
+    // 0x200: 6005  V0 = 5
+    // 0x202: E09E  skip if key 5 is down
+    // 0x204: 6A01  VA = 1
+    // 0x206: E0A1  skip if key 5 is up
+    // 0x208: 6B01  VB = 1
+    // 0x20A: F10A  wait for a key into V1
+    // 0x20C: 120C  loop
+    let program = [
+        0x60, 0x05, 0xE0, 0x9E, 0x6A, 0x01, 0xE0, 0xA1, 0x6B, 0x01, 0xF1, 0x0A, 0x12, 0x0C,
+    ];
+    for platform in PLATFORMS {
+        let mut chip8 = machine(platform, Engine::Interpreter, &program);
+        chip8.set_key(5, true).unwrap();
+        for _ in 0..4 {
+            chip8.step();
+        }
+        assert_eq!(registers(&chip8)[0xA..0xC], [0, 1]);
+
+        chip8.set_key(5, false).unwrap();
+        for _ in 0..10 {
+            chip8.step();
+        }
+        assert_eq!(chip8.cpu().read_pc(), 0x20A, "FX0A waits for a key");
+
+        chip8.set_key(0xC, true).unwrap();
+        chip8.step();
+        assert_eq!(registers(&chip8)[1], 0xC);
+        assert_eq!(chip8.cpu().read_pc(), 0x20C);
+    }
+}
+
+#[test]
+fn draw_and_collide() {
+    // 0x200: 600A  V0 = 0xA
+    // 0x202: F029  I = font A
+    // 0x204: D005  draw at (V0, V0) = (10, 10)
+    // 0x206: D005  draw again, erasing it
+    // 0x208: 00E0  clear
+    // 0x20A: D005  draw once more
+    // 0x20C: 120C  loop
+    let program = [
+        0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0x00, 0xE0, 0xD0, 0x05, 0x12, 0x0C,
+    ];
+    for platform in PLATFORMS {
+        let mut chip8 = machine(platform, Engine::Interpreter, &program);
+        run_until(&mut chip8, 0x208);
+        assert!(chip8.framebuffer().iter().flatten().all(|&p| p == 0));
+        assert_eq!(registers(&chip8)[0xF], 1);
+
+        run_until(&mut chip8, 0x20C);
+        let lit: usize = chip8
+            .framebuffer()
+            .iter()
+            .flatten()
+            .map(|&p| p as usize)
+            .sum();
+        // The font's A: F0 90 F0 90 90
+        assert_eq!(lit, 4 + 2 + 4 + 2 + 2);
+        assert_eq!(registers(&chip8)[0xF], 0);
+    }
+}
+
+// Step to an address, ending the frame whenever a draw waits for the
+// vertical blank
+fn run_until(chip8: &mut Chip8, pc: u16) {
+    for _ in 0..20 {
+        if chip8.cpu().read_pc() == pc {
+            return;
+        }
+        if chip8.cpu().waiting_for_vblank() {
+            chip8.tick_timers();
+        } else {
+            assert!(chip8.step());
+        }
+    }
+    panic!("never reached 0x{:03X}", pc);
+}
+
+// Run `steps` instructions of a program with one quirk on and off, starting
+// from each platform's quirks, on both engines. `check` gets whether the
+// quirk was on.
+fn run_quirk(
+    program: &[u8],
+    steps: usize,
+    set: impl Fn(&mut Quirks, bool),
+    check: impl Fn(bool, &Chip8),
+) {
+    for platform in PLATFORMS {
+        for engine in [Engine::Interpreter, Engine::Recompiler] {
+            for on in [false, true] {
+                let mut quirks = platform.quirks();
+                set(&mut quirks, on);
+                let mut chip8 = machine(platform, engine, program);
+                chip8.set_quirks(quirks);
+                // A draw waiting for the vertical blank leaves pc where it is
+                let mut ran = 0;
+                for _ in 0..steps * 3 {
+                    if ran == steps {
+                        break;
+                    }
+                    if chip8.cpu().waiting_for_vblank() {
+                        chip8.tick_timers();
+                        continue;
+                    }
+                    let pc = chip8.cpu().read_pc();
+                    assert!(chip8.step_engine());
+                    ran += (chip8.cpu().read_pc() != pc) as usize;
+                }
+                assert_eq!(ran, steps, "{} stalled", platform.name());
+                check(on, &chip8);
+            }
+        }
+    }
+}
+
+#[test]
+fn platform_quirks() {
+    let chip8 = Platform::Chip8.quirks();
+    assert!(!chip8.vf_reset && !chip8.memory_increment && !chip8.display_wait);
+    assert!(!chip8.clipping && chip8.shift_in_place && !chip8.jump_with_vx);
+    let vip = Platform::CosmacVip.quirks();
+    assert!(vip.vf_reset && vip.memory_increment && vip.display_wait);
+    assert!(vip.clipping && !vip.shift_in_place && !vip.jump_with_vx);
+}
+
+#[test]
+fn quirk_vf_reset() {
+    // 6F05 6003 6106 801N: 8XY1, 8XY2 and 8XY3 with VF = 5
+    for n in 1..=3 {
+        let program = [0x6F, 0x05, 0x60, 0x03, 0x61, 0x06, 0x80, 0x10 | n];
+        run_quirk(
+            &program,
+            4,
+            |q, on| q.vf_reset = on,
+            |on, chip8| {
+                assert_eq!(registers(chip8)[0xF], if on { 0 } else { 5 }, "8XY{}", n);
+            },
+        );
+    }
+}
+
+#[test]
+fn quirk_memory_increment() {
+    // A300 F255 and A300 F265
+    for op in [0x55, 0x65] {
+        let program = [0xA3, 0x00, 0xF2, op];
+        run_quirk(
+            &program,
+            2,
+            |q, on| q.memory_increment = on,
+            |on, chip8| {
+                assert_eq!(chip8.cpu().read_i(), if on { 0x303 } else { 0x300 });
+            },
+        );
+    }
+}
+
+#[test]
+fn quirk_display_wait() {
+    // A000 D005 D005: the second draw waits for another vertical blank
+    let program = [0xA0, 0x00, 0xD0, 0x05, 0xD0, 0x05];
+    for platform in PLATFORMS {
+        for on in [false, true] {
+            let mut quirks = platform.quirks();
+            quirks.display_wait = on;
+            let mut chip8 = machine(platform, Engine::Interpreter, &program);
+            chip8.set_quirks(quirks);
+            for _ in 0..3 {
+                chip8.step();
+            }
+            let expected = if on { 0x202 } else { 0x206 };
+            assert_eq!(chip8.cpu().read_pc(), expected, "{}", platform.name());
+            assert_eq!(chip8.cpu().waiting_for_vblank(), on);
+        }
+    }
+}
+
+#[test]
+fn quirk_clipping() {
+    // 603E 611E A000 D015: the font's 0 at (62, 30), hanging off the right
+    // and bottom edges
+    let program = [0x60, 0x3E, 0x61, 0x1E, 0xA0, 0x00, 0xD0, 0x15];
+    run_quirk(
+        &program,
+        4,
+        |q, on| q.clipping = on,
+        |on, chip8| {
+            let screen = chip8.framebuffer();
+            // The top row's third pixel wraps to the left edge, and the third
+            // row to the top
+            let wrapped = (screen[30][0], screen[0][62]);
+            assert_eq!(wrapped, if on { (0, 0) } else { (1, 1) });
+            assert_eq!(screen[30][62..], [1, 1]);
+        },
+    );
+}
+
+#[test]
+fn quirk_shift_in_place() {
+    // 6003 6105 8016 and 6003 6105 801E
+    let program = [0x60, 0x03, 0x61, 0x05, 0x80, 0x16];
+    run_quirk(
+        &program,
+        3,
+        |q, on| q.shift_in_place = on,
+        |on, chip8| {
+            let expected = if on { [0x01, 1] } else { [0x02, 1] };
+            assert_eq!([registers(chip8)[0], registers(chip8)[0xF]], expected);
+        },
+    );
+    let program = [0x60, 0x03, 0x61, 0x85, 0x80, 0x1E];
+    run_quirk(
+        &program,
+        3,
+        |q, on| q.shift_in_place = on,
+        |on, chip8| {
+            let expected = if on { [0x06, 0] } else { [0x0A, 1] };
+            assert_eq!([registers(chip8)[0], registers(chip8)[0xF]], expected);
+        },
+    );
+}
+
+#[test]
+fn quirk_jump_with_vx() {
+    // 6002 6304 B308: to 0x308 + V0 or + V3
+    let program = [0x60, 0x02, 0x63, 0x04, 0xB3, 0x08];
+    run_quirk(
+        &program,
+        3,
+        |q, on| q.jump_with_vx = on,
+        |on, chip8| {
+            assert_eq!(chip8.cpu().read_pc(), if on { 0x30C } else { 0x30A });
+        },
+    );
+}
+
+// Works through most of the arithmetic, stores V0-VF at 0x300 and then
+// draws all sixteen of them in hex, four to a row, through a subroutine
+const OPCODE_SCREEN: [u8; 120] = [
+    0x60, 0xFF, // 0x200: V0 = FF
+    0x61, 0x01, // 0x202: V1 = 01
+    0x80, 0x14, // 0x204: V0 += V1         00, VF = 1
+    0x82, 0xF0, // 0x206: V2 = VF
+    0x63, 0x05, // 0x208: V3 = 05
+    0x64, 0x07, // 0x20A: V4 = 07
+    0x83, 0x45, // 0x20C: V3 -= V4         FE, VF = 0
+    0x85, 0xF0, // 0x20E: V5 = VF
+    0x66, 0x81, // 0x210: V6 = 81
+    0x86, 0x66, // 0x212: V6 >>= 1         40, VF = 1
+    0x87, 0xF0, // 0x214: V7 = VF
+    0x68, 0x03, // 0x216: V8 = 03
+    0x69, 0x02, // 0x218: V9 = 02
+    0x88, 0x97, // 0x21A: V8 = V9 - V8     FF, VF = 0
+    0x8A, 0xF0, // 0x21C: VA = VF
+    0x6B, 0xC1, // 0x21E: VB = C1
+    0x8B, 0xBE, // 0x220: VB <<= 1         82, VF = 1
+    0x8C, 0xF0, // 0x222: VC = VF
+    0x6D, 0x33, // 0x224: VD = 33
+    0x6E, 0x55, // 0x226: VE = 55
+    0x8D, 0xE3, // 0x228: VD ^= VE         66
+    0x8E, 0xD1, // 0x22A: VE |= VD         77
+    0x6F, 0x0F, // 0x22C: VF = 0F
+    0x8F, 0xE2, // 0x22E: VF &= VE         07
+    0xA3, 0x00, // 0x230: I = 0x300
+    0xFF, 0x55, // 0x232: store V0-VF
+    0x61, 0x00, // 0x234: V1 = 0           x
+    0x62, 0x00, // 0x236: V2 = 0           y
+    0x63, 0x00, // 0x238: V3 = 0           register number
+    0xA3, 0x00, // 0x23A: I = 0x300
+    0xF3, 0x1E, // 0x23C: I += V3
+    0xF0, 0x65, // 0x23E: load V0
+    0x84, 0x00, // 0x240: V4 = V0
+    0x84, 0x46, // 0x242: V4 >>= 1
+    0x84, 0x46, // 0x244: V4 >>= 1
+    0x84, 0x46, // 0x246: V4 >>= 1
+    0x84, 0x46, // 0x248: V4 >>= 1         high digit
+    0x22, 0x70, // 0x24A: call 0x270
+    0x84, 0x00, // 0x24C: V4 = V0
+    0x65, 0x0F, // 0x24E: V5 = 0F
+    0x84, 0x52, // 0x250: V4 &= V5         low digit
+    0x22, 0x70, // 0x252: call 0x270
+    0x71, 0x06, // 0x254: V1 += 6
+    0x73, 0x01, // 0x256: V3 += 1
+    0x31, 0x40, // 0x258: skip if V1 == 64
+    0x12, 0x60, // 0x25A: jump 0x260
+    0x61, 0x00, // 0x25C: V1 = 0
+    0x72, 0x06, // 0x25E: V2 += 6          next row
+    0x33, 0x10, // 0x260: skip if V3 == 16
+    0x12, 0x3A, // 0x262: jump 0x23A
+    0x12, 0x64, // 0x264: loop forever
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x266
+    0xF4, 0x29, // 0x270: I = font V4
+    0xD1, 0x25, // 0x272: draw at (V1, V2)
+    0x71, 0x05, // 0x274: V1 += 5
+    0x00, 0xEE, // 0x276: return
+];
+
+/// A ROM run headlessly to a final screen
+struct Conformance {
+    name: &'static str,
+    frames: usize,
+    /// Value poked into 0x1FF, which the test suite ROMs read to pick a
+    /// test without asking for a key
+    select: Option<u8>,
+    /// Keypad input: (frame, key, pressed)
+    input: &'static [(usize, u8, bool)],
+}
+
+// The Timendus CHIP-8 test suite, in tests/roms as <name>.ch8
+const SUITE: [Conformance; 7] = [
+    Conformance {
+        name: "1-chip8-logo",
+        frames: 60,
+        select: None,
+        input: &[],
+    },
+    Conformance {
+        name: "2-ibm-logo",
+        frames: 60,
+        select: None,
+        input: &[],
+    },
+    Conformance {
+        name: "3-corax+",
+        frames: 180,
+        select: None,
+        input: &[],
+    },
+    Conformance {
+        name: "4-flags",
+        frames: 180,
+        select: None,
+        input: &[],
+    },
+    // 1 picks the original CHIP-8's quirks
+    Conformance {
+        name: "5-quirks",
+        frames: 900,
+        select: Some(1),
+        input: &[],
+    },
+    // 3 picks the FX0A test, which needs a key pressed and released
+    Conformance {
+        name: "6-keypad",
+        frames: 240,
+        select: Some(3),
+        input: &[(120, 0x5, true), (130, 0x5, false)],
+    },
+    Conformance {
+        name: "7-beep",
+        frames: 120,
+        select: None,
+        input: &[(60, 0xB, true), (90, 0xB, false)],
+    },
+];
+
+fn run_to_screen(
+    test: &Conformance,
+    platform: Platform,
+    engine: Engine,
+    rom: &[u8],
+) -> Framebuffer {
+    let mut chip8 = machine(platform, engine, rom);
+    if let Some(select) = test.select {
+        chip8.memory_mut().write_byte(0x1FF, select);
+    }
+    for frame in 0..test.frames {
+        for &(_, key, pressed) in test.input.iter().filter(|input| input.0 == frame) {
+            chip8.set_key(key, pressed).unwrap();
+        }
+        if !chip8.run_frame() {
+            break;
+        }
+    }
+    *chip8.framebuffer()
+}
+
+fn to_text(framebuffer: &Framebuffer) -> String {
+    framebuffer
+        .iter()
+        .map(|row| {
+            let mut line: String = row
+                .iter()
+                .map(|&p| if p != 0 { '#' } else { '.' })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+fn golden_path(name: &str, platform: Platform) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/conformance")
+        .join(format!("{}.{}.txt", name, platform.name()))
+}
+
+// Compare the screens of a ROM on every platform and engine against the
+// golden images, writing them instead when blessing
+fn check_golden(test: &Conformance, rom: &[u8]) {
+    let bless = env::var_os("CHIP8_BLESS").is_some();
+    for platform in PLATFORMS {
+        let screen = to_text(&run_to_screen(test, platform, Engine::Interpreter, rom));
+        let compiled = to_text(&run_to_screen(test, platform, Engine::Recompiler, rom));
+        assert_eq!(
+            screen,
+            compiled,
+            "{} on {}: the recompiler drew something else",
+            test.name,
+            platform.name()
+        );
+
+        let path = golden_path(test.name, platform);
+        if bless {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &screen).unwrap();
+            continue;
+        }
+        let golden = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "no golden image {}; run with CHIP8_BLESS=1 to write it",
+                path.display()
+            )
+        });
+        assert!(
+            screen == golden,
+            "{} on {} doesn't match {}:\n{}",
+            test.name,
+            platform.name(),
+            path.display(),
+            screen
+        );
+    }
+}
+
+#[test]
+fn opcode_screen_matches_golden() {
+    let test = Conformance {
+        name: "opcodes",
+        frames: 60,
+        select: None,
+        input: &[],
+    };
+    check_golden(&test, &OPCODE_SCREEN);
+}
+
+// Until the ROMs and their goldens are checked in, run this with
+// `cargo test --test conformance -- --ignored` after tests/roms/fetch.sh
+#[test]
+#[ignore = "needs the Timendus test ROMs; see tests/roms/README.md"]
+fn test_suite_roms_match_golden() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+    let mut missing = Vec::new();
+    for test in &SUITE {
+        let path = roms.join(format!("{}.ch8", test.name));
+        match fs::read(&path) {
+            Ok(rom) => check_golden(test, &rom),
+            Err(_) => missing.push(path.display().to_string()),
+        }
+    }
+    assert!(
+        missing.is_empty(),
+        "test suite ROMs missing, see tests/roms/README.md:\n{}",
+        missing.join("\n")
+    );
+}
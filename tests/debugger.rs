@@ -0,0 +1,130 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::{env, fs};
+
+// 0x200: A300  I = 0x300
+// 0x202: 6042  V0 = 0x42
+// 0x204: F055  store V0 at I
+// 0x206: F065  load V0 from I
+// 0x208: D001  draw memory[I] at (V0, V0)
+// 0x20A: 120A  loop
+const PROGRAM: [u8; 12] = [
+    0xA3, 0x00, 0x60, 0x42, 0xF0, 0x55, 0xF0, 0x65, 0xD0, 0x01, 0x12, 0x0A,
+];
+
+// Run PROGRAM under the debugger, typing `commands` at its prompt, and
+// return everything it printed
+fn debug(name: &str, commands: &str) -> String {
+    let rom = env::temp_dir().join(format!("chip8-debugger-{}-{}", name, std::process::id()));
+    fs::write(&rom, PROGRAM).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chip_8_emulator"))
+        .arg("--debug")
+        .arg(&rom)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(commands.as_bytes()).unwrap();
+    stdin.write_all(b"q\n").unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&rom).unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn breaks_on_writes() {
+    let output = debug("write", "w 0x300 w\nc\n");
+    assert!(
+        output.contains("Watchpoint: 0x204: F055 wrote 0x42 at 0x300"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn breaks_on_reads() {
+    let output = debug("read", "w 0x300 r\nc\n");
+    assert!(
+        output.contains("Watchpoint: 0x206: F065 read 0x42 at 0x300"),
+        "{}",
+        output
+    );
+    assert!(!output.contains("wrote"), "{}", output);
+}
+
+#[test]
+fn watchpoints_cover_their_range_only() {
+    let output = debug("range", "w 0x301-0x30F rw\ns 10\n");
+    assert!(!output.contains("Watchpoint:"), "{}", output);
+    let output = debug("range-end", "w 0x2F0-0x300\nc\n");
+    assert!(output.contains("wrote 0x42 at 0x300"), "{}", output);
+}
+
+#[test]
+fn breaks_when_conditions_become_true() {
+    // Conditions stop after the instruction that makes them true
+    let output = debug("condition", "b if V0 == 0x42 && I > 0x2FF\nc\n");
+    assert!(
+        output.contains("Breakpoint 0 (if V0 == 0x42 && I > 0x2FF) hit by 0x202: 6042"),
+        "{}",
+        output
+    );
+    // && binds tighter than ||, and mem[] reads memory
+    let output = debug(
+        "precedence",
+        "b if mem[0x300] == 0x42 || V0 == 1 && I < 0x100\nc\n",
+    );
+    assert!(output.contains("hit by 0x204: F055"), "{}", output);
+}
+
+#[test]
+fn breaks_on_opcode_classes() {
+    // Opcode breakpoints stop before the instruction runs
+    let output = debug("opcode", "b op DXYN\nc\nr\n");
+    assert!(
+        output.contains("Breakpoint 0 (on opcode DXYN) hit by 0x208: D001"),
+        "{}",
+        output
+    );
+    assert!(output.contains("PC=208"), "{}", output);
+    let output = debug("load", "b op fx65\nc\n");
+    assert!(output.contains("hit by 0x206: F065"), "{}", output);
+}
+
+#[test]
+fn bad_breakpoints_are_reported() {
+    let output = debug(
+        "errors",
+        "b if VG == 1\nb if V5 ==\nb if V5 == 1)\nb op 12345\nw 200-210 x\nw 210-200\n",
+    );
+    for error in [
+        "Unknown register: VG",
+        "Unexpected end of condition",
+        "Unexpected ')' in condition",
+        "Invalid opcode pattern: 12345",
+        "Invalid watch mode: x",
+        "Invalid watch range: 210-200 ends before it starts",
+    ] {
+        assert!(output.contains(error), "{} missing from {}", error, output);
+    }
+    assert!(!output.contains("Watchpoint 0"), "{}", output);
+}
+
+#[test]
+fn reverse_steps_through_history() {
+    let output = debug("reverse", "s 2\nrs\n");
+    let prompts: Vec<_> = output.lines().filter(|line| line.contains("PC=")).collect();
+    assert!(prompts[prompts.len() - 2].contains("V0=42"), "{}", output);
+    let last = prompts[prompts.len() - 1];
+    assert!(
+        last.contains("V0=00") && last.contains("PC=202"),
+        "{}",
+        output
+    );
+
+    let output = debug("history-off", "history off\ns\nrs\n");
+    assert!(output.contains("History is off"), "{}", output);
+}
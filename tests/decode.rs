@@ -0,0 +1,58 @@
+use chip_8_emulator::decode::Instruction;
+use chip_8_emulator::memory::Memory;
+use chip_8_emulator::{Chip8, Rng};
+
+// Patches the low byte of its first opcode, the byte after the address the
+// opcode is cached under:
+// 0x200: 7203  V2 += (patched)
+// 0x202: A201  I = 0x201
+// 0x204: 6005  V0 = 5
+// 0x206: F055  store V0 at I
+// 0x208: 1200  loop
+const PATCH_LOW: [u8; 10] = [0x72, 0x03, 0xA2, 0x01, 0x60, 0x05, 0xF0, 0x55, 0x12, 0x00];
+
+// Patches the high byte of its first opcode, turning 7203 into 7103:
+// 0x200: 7203  V2 += 3, then V1 += 3
+// 0x202: A200  I = 0x200
+// 0x204: 6071  V0 = 0x71
+// 0x206: F055  store V0 at I
+// 0x208: 1200  loop
+const PATCH_HIGH: [u8; 10] = [0x72, 0x03, 0xA2, 0x00, 0x60, 0x71, 0xF0, 0x55, 0x12, 0x00];
+
+#[test]
+fn writes_drop_cached_decodes_they_overlap() {
+    let mut memory = Memory::new();
+    memory.load_program(&[0x72, 0x03, 0x12, 0x00], 0x200);
+    assert_eq!(memory.fetch_instruction(0x200).1, Instruction::Add(2, 0x03));
+    assert_eq!(memory.fetch_instruction(0x202).1, Instruction::Jump(0x200));
+
+    // The opcode's own address
+    memory.write_byte(0x200, 0x71);
+    assert_eq!(memory.fetch_instruction(0x200).1, Instruction::Add(1, 0x03));
+    // Its second byte, so the opcode cached at the address before the write
+    memory.write_byte(0x201, 0x05);
+    assert_eq!(memory.fetch_instruction(0x200).1, Instruction::Add(1, 0x05));
+    assert_eq!(memory.fetch_instruction(0x202).1, Instruction::Jump(0x200));
+    // The byte before the opcode, which isn't part of it
+    memory.write_byte(0x1FF, 0xFF);
+    assert_eq!(memory.fetch_instruction(0x200).1, Instruction::Add(1, 0x05));
+}
+
+// Registers V1 and V2 after five times round a loop
+fn run(program: &[u8], decode_cache: bool) -> (u8, u8) {
+    let mut chip8 = Chip8::builder().rng(Rng::new(0)).build();
+    chip8.set_decode_cache(decode_cache);
+    chip8.load_program(program).unwrap();
+    for _ in 0..5 * 5 {
+        assert!(chip8.step());
+    }
+    (chip8.cpu().read_register(1), chip8.cpu().read_register(2))
+}
+
+#[test]
+fn self_modifying_code_runs_the_same_with_the_cache() {
+    for decode_cache in [false, true] {
+        assert_eq!(run(&PATCH_LOW, decode_cache), (0, 3 + 4 * 5));
+        assert_eq!(run(&PATCH_HIGH, decode_cache), (4 * 3, 3));
+    }
+}
@@ -0,0 +1,146 @@
+use chip_8_emulator::env::{Env, EnvConfig, EnvError, Game, Keys, Location, VecEnv};
+use chip_8_emulator::Chip8Builder;
+
+// One pass of the loop per frame at 3 instructions a frame:
+// 0x200: 6E03  VE = 3 lives
+// 0x202: 7501  V5 += 1, a point
+// 0x204: 7EFF  VE -= 1, a life
+// 0x206: 1202  loop
+const COUNTDOWN: [u8; 8] = [0x6E, 0x03, 0x75, 0x01, 0x7E, 0xFF, 0x12, 0x02];
+
+fn countdown_game() -> Game {
+    Game {
+        actions: vec![Keys::NONE, Keys::of(&[4, 6])],
+        score: vec![(Location::Register(5), 1.0)],
+        lives: Some(Location::Register(0xE)),
+    }
+}
+
+fn config(frame_skip: u32) -> EnvConfig {
+    EnvConfig {
+        frame_skip,
+        chip8: Chip8Builder::new().instructions_per_frame(3),
+        ..EnvConfig::default()
+    }
+}
+
+#[test]
+fn episode_ends_when_lives_run_out() {
+    let mut env = Env::new(&COUNTDOWN, countdown_game(), config(1)).unwrap();
+    env.reset(0);
+
+    let steps: Vec<_> = (0..3)
+        .map(|_| {
+            let (_, reward, done, info) = env.step(0).unwrap();
+            (reward, done, info.lives)
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            (1.0, false, Some(2)),
+            (1.0, false, Some(1)),
+            (1.0, true, Some(0))
+        ]
+    );
+}
+
+#[test]
+fn frame_skip_adds_up_rewards() {
+    let mut env = Env::new(&COUNTDOWN, countdown_game(), config(2)).unwrap();
+    env.reset(0);
+
+    let (_, reward, done, info) = env.step(1).unwrap();
+    assert_eq!((reward, done, info.frame), (2.0, false, 2));
+    assert_eq!(env.chip8().keys(), 1 << 4 | 1 << 6);
+
+    // The step stops at the frame the episode ends on
+    let (_, reward, done, info) = env.step(0).unwrap();
+    assert_eq!((reward, done, info.frame), (1.0, true, 3));
+    assert_eq!(env.chip8().keys(), 0);
+}
+
+#[test]
+fn episodes_can_end_early() {
+    let mut config = config(1);
+    config.end_on_life_lost = true;
+    let mut env = Env::new(&COUNTDOWN, countdown_game(), config).unwrap();
+    env.reset(0);
+    assert!(!env.step(0).unwrap().2);
+    assert!(env.step(0).unwrap().2);
+
+    let mut config = self::config(2);
+    config.max_frames = Some(3);
+    let game = Game {
+        lives: None,
+        ..countdown_game()
+    };
+    let mut env = Env::new(&COUNTDOWN, game, config).unwrap();
+    env.reset(0);
+    assert!(!env.step(0).unwrap().3.truncated);
+    let (_, _, done, info) = env.step(0).unwrap();
+    assert!(done && info.truncated);
+    assert_eq!(info.frame, 3);
+
+    assert_eq!(
+        env.step(2).unwrap_err(),
+        EnvError::InvalidAction {
+            action: 2,
+            actions: 2
+        }
+    );
+}
+
+#[test]
+fn batches_reset_finished_environments() {
+    let mut envs = VecEnv::new(&COUNTDOWN, countdown_game(), config(1), 2).unwrap();
+    let observations = envs.reset(0);
+    assert_eq!(observations.len(), 2);
+
+    for _ in 0..2 {
+        let step = envs.step(&[0, 1]).unwrap();
+        assert_eq!(step.dones, [false, false]);
+        assert_eq!(step.final_observations, [None, None]);
+    }
+    let step = envs.step(&[0, 1]).unwrap();
+    assert_eq!(step.dones, [true, true]);
+    assert_eq!(step.rewards, [1.0, 1.0]);
+    assert!(step.final_observations.iter().all(Option::is_some));
+
+    // Straight into the next episodes
+    let step = envs.step(&[0, 1]).unwrap();
+    assert_eq!(step.infos[0].frame, 1);
+    assert_eq!(step.infos[1].lives, Some(2));
+
+    assert_eq!(
+        envs.step(&[0]).unwrap_err(),
+        EnvError::WrongActionCount { given: 1, envs: 2 }
+    );
+    // A bad action anywhere in the batch steps none of the environments
+    assert_eq!(
+        envs.step(&[0, 2]).unwrap_err(),
+        EnvError::InvalidAction {
+            action: 2,
+            actions: 2
+        }
+    );
+    assert_eq!(envs.envs()[0].chip8().cpu().read_register(5), 1);
+}
+
+#[test]
+fn brix_starts_with_five_lives() {
+    let rom = std::fs::read("data/BRIX").unwrap();
+    let mut env = Env::new(&rom, Game::brix(), EnvConfig::default()).unwrap();
+    let observation = env.reset(7);
+    assert!(observation.iter().flatten().all(|&pixel| pixel == 0));
+
+    let (observation, reward, done, info) = env.step(0).unwrap();
+    assert_eq!((reward, done, info.lives), (0.0, false, Some(5)));
+    assert!(observation.iter().flatten().any(|&pixel| pixel == 1));
+
+    // Resetting starts over from power-on
+    let before = env.chip8().save_state();
+    env.reset(7);
+    env.step(0).unwrap();
+    assert_eq!(env.chip8().save_state(), before);
+}
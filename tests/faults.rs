@@ -0,0 +1,212 @@
+//! Bad programs and bad save states stop the machine with a fault or fail to
+//! load; nothing panics. The fuzz targets in fuzz/ search for more of these.
+
+use chip_8_emulator::cpu::STACK_SIZE;
+use chip_8_emulator::state::StateWriter;
+use chip_8_emulator::{Chip8, Engine, Fault, Platform};
+use common::{machine, machine_with};
+
+mod common;
+
+const ENGINES: [Engine; 2] = [Engine::Interpreter, Engine::Recompiler];
+
+// Run a program until it stops, returning the fault
+fn fault(program: &[u8]) -> Option<Fault> {
+    let mut faults = ENGINES.iter().map(|&engine| {
+        let mut chip8 = machine_with(program, engine, 0);
+        if (0..1000).all(|_| chip8.run_frame()) {
+            None
+        } else {
+            chip8.fault()
+        }
+    });
+    let fault = faults.next().unwrap();
+    assert_eq!(faults.next().unwrap(), fault, "engines disagree");
+    fault
+}
+
+#[test]
+fn memory_past_the_end_faults() {
+    // AFFF FF55: store 16 registers from 0xFFF
+    assert_eq!(
+        fault(&[0xAF, 0xFF, 0xFF, 0x55]),
+        Some(Fault::MemoryOutOfBounds {
+            pc: 0x202,
+            address: 0x1000
+        })
+    );
+    // AFFE F033: BCD needs three bytes
+    assert_eq!(
+        fault(&[0xAF, 0xFE, 0xF0, 0x33]),
+        Some(Fault::MemoryOutOfBounds {
+            pc: 0x202,
+            address: 0x1000
+        })
+    );
+    // AFFF F165: loads end a compiled block so they can fault
+    assert_eq!(
+        fault(&[0xAF, 0xFF, 0xF1, 0x65]),
+        Some(Fault::MemoryOutOfBounds {
+            pc: 0x202,
+            address: 0x1000
+        })
+    );
+    // AFFC D005: a sprite reaching past the end
+    assert_eq!(
+        fault(&[0xAF, 0xFC, 0xD0, 0x05]),
+        Some(Fault::MemoryOutOfBounds {
+            pc: 0x202,
+            address: 0x1000
+        })
+    );
+    // 60FF AFFF F01E F01E ... FX1E wraps I rather than overflowing, and
+    // then a read from I faults
+    let mut add_to_i = vec![0x60, 0xFF, 0xAF, 0xFF];
+    for _ in 0..300 {
+        add_to_i.extend_from_slice(&[0xF0, 0x1E]);
+    }
+    add_to_i.extend_from_slice(&[0xF0, 0x65]);
+    assert!(matches!(
+        fault(&add_to_i),
+        Some(Fault::MemoryOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn stack_faults() {
+    // 2200: call itself forever
+    assert_eq!(
+        fault(&[0x22, 0x00]),
+        Some(Fault::StackOverflow {
+            pc: 0x200,
+            address: 0x200
+        })
+    );
+    assert_eq!(
+        fault(&[0x00, 0xEE]),
+        Some(Fault::StackUnderflow { pc: 0x200 })
+    );
+}
+
+#[test]
+fn running_off_the_end_faults() {
+    // 6000 6000 ...: fill memory to the end and step past it
+    let filler = [0x60, 0x00].repeat(0xE00 / 2);
+    assert_eq!(fault(&filler), Some(Fault::PcOutOfBounds(0x1000)));
+    // 1FFF: jump to the last byte, with no room for an opcode
+    assert_eq!(fault(&[0x1F, 0xFF]), Some(Fault::PcOutOfBounds(0xFFF)));
+}
+
+#[test]
+fn keys_use_the_low_nibble() {
+    // 60F5 E09E 1202 6101 1208: skip while key 5 (0xF5 & 0xF) is down
+    let mut chip8 = machine(&[0x60, 0xF5, 0xE0, 0x9E, 0x12, 0x02, 0x61, 0x01, 0x12, 0x08]);
+    chip8.set_key(5, true).unwrap();
+    assert!(chip8.run_frame());
+    assert_eq!(chip8.cpu().read_register(1), 1);
+}
+
+// Every opcode, run once from states at the edges: I at the end of memory
+// and beyond, the return stack empty and full, and the program counter on
+// the last opcode
+#[test]
+fn every_opcode_from_edge_states() {
+    for engine in ENGINES {
+        for platform in [Platform::Chip8, Platform::CosmacVip] {
+            for (i, depth, pc) in [
+                (0xFFF, 0, 0xFFE),
+                (0xFFFF, STACK_SIZE, 0x200),
+                (0xFFD, STACK_SIZE, 0xFFC),
+            ] {
+                let mut chip8 = edge_machine(engine, platform, depth);
+                for opcode in 0..=0xFFFFu16 {
+                    if chip8.cpu().read_sp() != depth {
+                        chip8 = edge_machine(engine, platform, depth);
+                    }
+                    let [high, low] = opcode.to_be_bytes();
+                    chip8.memory_mut().write_byte(pc, high);
+                    chip8.memory_mut().write_byte(pc + 1, low);
+                    chip8.cpu_mut().write_pc(pc as u16);
+                    chip8.cpu_mut().write_i(i);
+                    for x in 0..16 {
+                        chip8.cpu_mut().write_register(x, 0xFF);
+                    }
+                    if !chip8.step_engine() {
+                        assert!(chip8.fault().is_some(), "{:04X} stopped", opcode);
+                    }
+                    // Let a draw waiting for the vertical blank go next time
+                    chip8.tick_timers();
+                }
+            }
+        }
+    }
+}
+
+// A machine with `depth` return addresses on the stack
+fn edge_machine(engine: Engine, platform: Platform, depth: usize) -> Chip8 {
+    // 2200: call itself
+    let mut chip8 = machine_with(&[0x22, 0x00], engine, 0);
+    for _ in 0..depth {
+        assert!(chip8.step());
+    }
+    chip8.set_platform(platform);
+    chip8
+}
+
+// xorshift, to make up ROMs and states
+fn noise(seed: &mut u64, length: usize) -> Vec<u8> {
+    (0..length)
+        .map(|_| {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            *seed as u8
+        })
+        .collect()
+}
+
+#[test]
+fn random_roms_run_or_fault() {
+    let mut seed = 0x2545_F491_4F6C_DD1D;
+    for round in 0..200 {
+        let length = 2 + round * 7 % 600;
+        let rom = noise(&mut seed, length);
+        for engine in ENGINES {
+            let mut chip8 = machine_with(&rom, engine, 0);
+            chip8.set_key((round % 16) as u8, true).unwrap();
+            for _ in 0..30 {
+                if !chip8.run_frame() {
+                    assert!(chip8.fault().is_some());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn damaged_save_states_fail_to_load() {
+    let mut chip8 = machine(&[0x22, 0x02, 0x12, 0x00]);
+    chip8.run_frame();
+    let state = chip8.save_state();
+
+    let mut seed = 7;
+    for length in 0..state.len() {
+        assert!(chip8.load_state(&state[..length]).is_err());
+    }
+    for round in 0..500 {
+        // Random payloads behind a valid header and checksum reach the
+        // parser; whatever loads must run
+        let mut writer = StateWriter::new();
+        writer.write_bytes(&noise(&mut seed, round * 11 % (state.len() + 64)));
+        let state = writer.finish();
+        if chip8.load_state(&state).is_ok() {
+            for _ in 0..5 {
+                if !chip8.run_frame() {
+                    assert!(chip8.fault().is_some());
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+use std::process::Command;
+
+// Builds the staticlib with the C API and returns the system libraries it
+// needs
+fn cargo_build_staticlib(target_dir: &Path) -> Vec<String> {
+    let output = Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "rustc",
+            "--lib",
+            "--crate-type",
+            "staticlib",
+            "--features",
+            "ffi",
+        ])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .args(["--", "--print", "native-static-libs"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "staticlib build failed:\n{}",
+        stderr
+    );
+
+    stderr
+        .lines()
+        .find_map(|line| line.split("native-static-libs: ").nth(1))
+        .map(|libs| libs.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn c_smoke_test() {
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&compiler).arg("--version").output().is_err() {
+        eprintln!("no C compiler ({}), skipping the C smoke test", compiler);
+        return;
+    }
+
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("target/ffi");
+    let native_libs = cargo_build_staticlib(&target_dir);
+
+    let smoke = target_dir.join("smoke");
+    let output = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Werror"])
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/smoke.c"))
+        .arg(target_dir.join("debug/libchip_8_emulator.a"))
+        .args(&native_libs)
+        .arg("-o")
+        .arg(&smoke)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "compiling smoke.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(&smoke).output().unwrap();
+    assert!(
+        output.status.success(),
+        "smoke test failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
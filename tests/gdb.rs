@@ -0,0 +1,180 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::{env, fs, thread};
+
+/// Minimal GDB remote protocol client
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'+', "packet {} was not acknowledged", data);
+
+        self.stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'$');
+        let mut reply = Vec::new();
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+fn start_server(name: &str, program: &[u8]) -> (Child, Client) {
+    let rom = env::temp_dir().join(format!("chip8-gdb-{}-{}", name, std::process::id()));
+    fs::write(&rom, program).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chip_8_emulator"))
+        .args(["--gdb", "0"])
+        .arg(&rom)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    let port = line.trim().rsplit(':').next().unwrap().to_string();
+    // Keep draining the instruction trace so the emulator never blocks on it
+    thread::spawn(move || std::io::copy(&mut stdout, &mut std::io::sink()));
+
+    let stream = TcpStream::connect(format!("127.0.0.1:{}", port)).unwrap();
+    (child, Client { stream })
+}
+
+// 0x200: 6005  V0 = 5
+// 0x202: 7001  V0 += 1
+// 0x204: A300  I = 0x300
+// 0x206: F033  BCD of V0 at I
+// 0x208: 1208  loop forever
+const PROGRAM: [u8; 10] = [0x60, 0x05, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x08];
+
+#[test]
+fn registers_and_single_step() {
+    let (mut child, mut client) = start_server("step", &PROGRAM);
+
+    assert_eq!(client.send("?"), "S05");
+    let registers = client.send("g");
+    assert_eq!(registers.len(), 23 * 2);
+    // PC is little endian after V0-VF and I
+    assert_eq!(&registers[36..40], "0002");
+
+    assert_eq!(client.send("s"), "S05");
+    let registers = client.send("g");
+    assert_eq!(&registers[0..2], "05");
+    assert_eq!(&registers[36..40], "0202");
+
+    // Write V1 = 0xAB and PC back to 0x200 through G
+    let mut changed = registers.clone();
+    changed.replace_range(2..4, "ab");
+    changed.replace_range(36..40, "0002");
+    assert_eq!(client.send(&format!("G{}", changed)), "OK");
+    let registers = client.send("g");
+    assert_eq!(&registers[2..4], "ab");
+    assert_eq!(&registers[36..40], "0002");
+
+    client.send("k");
+    child.wait().unwrap();
+}
+
+#[test]
+fn breakpoints_and_watchpoints() {
+    let (mut child, mut client) = start_server("break", &PROGRAM);
+
+    assert_eq!(client.send("Z0,204,2"), "OK");
+    assert_eq!(client.send("c"), "S05");
+    assert_eq!(&client.send("g")[36..40], "0402");
+    assert_eq!(client.send("z0,204,2"), "OK");
+
+    assert_eq!(client.send("Z2,300,3"), "OK");
+    assert_eq!(client.send("c"), "T05watch:300;");
+    // Stopped after the BCD store, reporting the first byte written
+    assert_eq!(&client.send("g")[36..40], "0802");
+    assert_eq!(client.send("m300,3"), "000006");
+
+    client.send("k");
+    child.wait().unwrap();
+}
+
+#[test]
+fn memory_access() {
+    let (mut child, mut client) = start_server("memory", &PROGRAM);
+
+    assert_eq!(client.send("m200,4"), "60057001");
+    assert_eq!(client.send("M400,2:abcd"), "OK");
+    assert_eq!(client.send("m400,2"), "abcd");
+    // Out of the 4 KiB address space
+    assert_eq!(client.send("mfff,2"), "E01");
+    // Ranges whose end overflows
+    assert_eq!(client.send("mffffffffffffffff,2"), "E01");
+    assert_eq!(client.send("Mffffffffffffffff,1:ab"), "E01");
+    assert_eq!(
+        client.send("qXfer:features:read:target.xml:1,ffffffffffffffff"),
+        "E01"
+    );
+    assert!(client
+        .send("qXfer:features:read:target.xml:0,1000")
+        .starts_with("l<?xml"));
+
+    client.send("D");
+    child.wait().unwrap();
+}
+
+#[test]
+fn faults_stop_with_a_signal() {
+    // 0x200: 00EE  return with nothing to return to
+    let (mut child, mut client) = start_server("fault", &[0x00, 0xEE]);
+
+    assert_eq!(client.send("c"), "S0B");
+    // The program stays stopped, and says so again
+    assert_eq!(client.send("s"), "S0B");
+
+    client.send("k");
+    child.wait().unwrap();
+}
+
+#[test]
+fn bad_ports_and_missing_roms_exit_with_an_error() {
+    let rom = env::temp_dir().join(format!("chip8-gdb-args-{}", std::process::id()));
+    fs::write(&rom, PROGRAM).unwrap();
+    let missing = env::temp_dir().join("chip8-gdb-no-such-rom");
+
+    for port in ["70000", "--debug"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_chip_8_emulator"))
+            .args(["--gdb", port])
+            .arg(&rom)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{}", stderr);
+        assert!(stderr.contains("Usage: --gdb <port>"), "{}", stderr);
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chip_8_emulator"))
+        .args(["--gdb", "0"])
+        .arg(&missing)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(
+        stderr.starts_with(&format!("{}: ", missing.display())),
+        "{}",
+        stderr
+    );
+    fs::remove_file(&rom).unwrap();
+}
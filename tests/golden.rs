@@ -0,0 +1,282 @@
+//! Golden-frame tests for the ROMs in data/. Each ROM plays the session in
+//! tests/golden/roms/<ROM>.script on a seeded machine, and the framebuffer
+//! hash every `CHECKPOINT_INTERVAL` frames must match <ROM>.golden. The last
+//! screen is kept as text art in <ROM>.txt, for review and for diffs.
+//!
+//! ```text
+//! cargo test --test golden               # check every ROM
+//! cargo test --test golden -- PONG BRIX  # check some
+//! cargo test --test golden -- --bless    # write the goldens again
+//! ```
+//!
+//! On a failure the screen at the first checkpoint that differs is written
+//! as a PNG, with a diff of the last screen against <ROM>.txt: white where
+//! both are lit, dark gray for pixels that went out, light gray for pixels
+//! that came on. The paths are printed with the failure.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{env, fs};
+
+use chip_8_emulator::script::Script;
+use chip_8_emulator::state::crc32;
+use chip_8_emulator::{png, Chip8, Framebuffer, Platform, Rng, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+const FRAMES: u32 = 600;
+const CHECKPOINT_INTERVAL: u32 = 60;
+const SEED: u64 = 0;
+const INSTRUCTIONS_PER_FRAME: u16 = 16;
+
+const PNG_SCALE: usize = 8;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn golden_dir() -> PathBuf {
+    manifest_dir().join("tests/golden/roms")
+}
+
+fn hash(framebuffer: &Framebuffer) -> u32 {
+    crc32(framebuffer.as_flattened())
+}
+
+fn to_text(framebuffer: &Framebuffer) -> String {
+    framebuffer
+        .iter()
+        .map(|row| {
+            let mut line: String = row
+                .iter()
+                .map(|&p| if p != 0 { '#' } else { '.' })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+fn from_text(text: &str) -> Option<Framebuffer> {
+    let mut framebuffer = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+    let mut lines = text.lines();
+    for row in framebuffer.iter_mut() {
+        let line = lines.next()?.as_bytes();
+        if line.len() != DISPLAY_WIDTH {
+            return None;
+        }
+        for (pixel, &c) in row.iter_mut().zip(line) {
+            *pixel = (c == b'#') as u8;
+        }
+    }
+    Some(framebuffer)
+}
+
+/// What a session produced: the hash at every checkpoint, and the screens
+struct Session {
+    hashes: Vec<(u32, u32)>,
+    screens: Vec<Framebuffer>,
+}
+
+fn play(rom: &[u8], script: &Script) -> Result<Session, String> {
+    let mut chip8 = Chip8::builder()
+        .platform(Platform::Chip8)
+        .rng(Rng::new(SEED))
+        .instructions_per_frame(INSTRUCTIONS_PER_FRAME)
+        .build();
+    chip8.load_program(rom).map_err(|e| e.to_string())?;
+
+    let mut session = Session {
+        hashes: Vec::new(),
+        screens: Vec::new(),
+    };
+    let mut checkpoint = |frame: u32, chip8: &Chip8| {
+        if frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            session.hashes.push((frame, hash(chip8.framebuffer())));
+            session.screens.push(*chip8.framebuffer());
+        }
+    };
+
+    // Sessions shorter than FRAMES idle to the end
+    let mut frame = script.run_with(&mut chip8, FRAMES, &mut checkpoint)?;
+    while frame < FRAMES {
+        if !chip8.run_frame() {
+            return Err(format!("program stopped at frame {}", frame));
+        }
+        frame += 1;
+        checkpoint(frame, &chip8);
+    }
+    Ok(session)
+}
+
+fn golden_text(rom: &str, session: &Session) -> String {
+    let mut text = format!(
+        "# {}: {} frames on {}, seed {}, {} instructions per frame\n# frame  framebuffer crc32\n",
+        rom,
+        FRAMES,
+        Platform::Chip8.name(),
+        SEED,
+        INSTRUCTIONS_PER_FRAME
+    );
+    for (frame, hash) in session.hashes.iter() {
+        writeln!(text, "{} {:08X}", frame, hash).unwrap();
+    }
+    text
+}
+
+fn parse_golden(text: &str) -> Result<Vec<(u32, u32)>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (frame, hash) = line
+                .split_once(' ')
+                .ok_or_else(|| format!("bad golden line '{}'", line))?;
+            let frame = frame
+                .parse()
+                .map_err(|_| format!("bad frame '{}'", frame))?;
+            let hash =
+                u32::from_str_radix(hash.trim(), 16).map_err(|_| format!("bad hash '{}'", hash))?;
+            Ok((frame, hash))
+        })
+        .collect()
+}
+
+fn write_png(path: &Path, pixels: impl Fn(usize, usize) -> u8) -> Result<(), String> {
+    let (width, height) = (DISPLAY_WIDTH * PNG_SCALE, DISPLAY_HEIGHT * PNG_SCALE);
+    let mut image = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            image.push(pixels(x / PNG_SCALE, y / PNG_SCALE));
+        }
+    }
+    fs::write(path, png::encode_gray(width as u32, height as u32, &image))
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+fn diff_pixel(expected: u8, actual: u8) -> u8 {
+    match (expected != 0, actual != 0) {
+        (true, true) => 0xFF,
+        (true, false) => 0x60,
+        (false, true) => 0xB0,
+        (false, false) => 0x00,
+    }
+}
+
+// Write the PNGs for a failed ROM and say where they are
+fn write_failure_images(rom: &str, frame: u32, screen: &Framebuffer, last: &Framebuffer) -> String {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    if let Err(e) = fs::create_dir_all(&dir) {
+        return format!("could not create {}: {}", dir.display(), e);
+    }
+    let mut report = String::new();
+    let actual = dir.join(format!("{}.{}.png", rom, frame));
+    match write_png(&actual, |x, y| screen[y][x] * 0xFF) {
+        Ok(()) => writeln!(report, "  screen at frame {}: {}", frame, actual.display()).unwrap(),
+        Err(e) => writeln!(report, "  {}", e).unwrap(),
+    }
+
+    let expected = fs::read_to_string(golden_dir().join(format!("{}.txt", rom)))
+        .ok()
+        .and_then(|text| from_text(&text));
+    if let Some(expected) = expected {
+        let diff = dir.join(format!("{}.diff.png", rom));
+        match write_png(&diff, |x, y| diff_pixel(expected[y][x], last[y][x])) {
+            Ok(()) => writeln!(report, "  diff of the last screen: {}", diff.display()).unwrap(),
+            Err(e) => writeln!(report, "  {}", e).unwrap(),
+        }
+    }
+    report
+}
+
+fn check(rom: &str, bless: bool) -> Result<(), String> {
+    let program = fs::read(manifest_dir().join("data").join(rom))
+        .map_err(|e| format!("could not read data/{}: {}", rom, e))?;
+    let script_path = golden_dir().join(format!("{}.script", rom));
+    let script = fs::read_to_string(&script_path)
+        .map_err(|e| format!("could not read {}: {}", script_path.display(), e))?;
+    let script = Script::parse(&script).map_err(|e| format!("{}: {}", script_path.display(), e))?;
+
+    let session = play(&program, &script)?;
+    let last = session.screens.last().expect("no checkpoints");
+    let golden_path = golden_dir().join(format!("{}.golden", rom));
+    let screen_path = golden_dir().join(format!("{}.txt", rom));
+
+    if bless {
+        fs::write(&golden_path, golden_text(rom, &session))
+            .and_then(|()| fs::write(&screen_path, to_text(last)))
+            .map_err(|e| format!("could not write goldens: {}", e))?;
+        return Ok(());
+    }
+
+    let golden = fs::read_to_string(&golden_path)
+        .map_err(|e| format!("could not read {}: {}", golden_path.display(), e))?;
+    let golden = parse_golden(&golden)?;
+    if golden.len() != session.hashes.len() {
+        return Err(format!(
+            "{} has {} checkpoints, the session has {}",
+            golden_path.display(),
+            golden.len(),
+            session.hashes.len()
+        ));
+    }
+    let mismatch = golden
+        .iter()
+        .zip(session.hashes.iter())
+        .position(|(expected, actual)| expected != actual);
+    match mismatch {
+        None => Ok(()),
+        Some(index) => {
+            let (frame, actual) = session.hashes[index];
+            let mut message = format!(
+                "frame {}: framebuffer hash {:08X}, golden {:08X}\n",
+                frame, actual, golden[index].1
+            );
+            message += &write_failure_images(rom, frame, &session.screens[index], last);
+            Err(message)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut bless = env::var_os("CHIP8_BLESS").is_some();
+    let mut filters = Vec::new();
+    // Other flags come from cargo test and don't apply here
+    for arg in env::args().skip(1) {
+        if arg == "--bless" {
+            bless = true;
+        } else if !arg.starts_with('-') {
+            filters.push(arg);
+        }
+    }
+
+    let mut roms: Vec<String> = fs::read_dir(manifest_dir().join("data"))
+        .expect("no data directory")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|rom| filters.is_empty() || filters.iter().any(|f| rom.contains(f.as_str())))
+        .collect();
+    roms.sort();
+
+    let mut failed = 0;
+    for rom in roms.iter() {
+        match check(rom, bless) {
+            Ok(()) if bless => println!("golden {} ... blessed", rom),
+            Ok(()) => println!("golden {} ... ok", rom),
+            Err(message) => {
+                println!("golden {} ... FAILED\n  {}", rom, message.trim_end());
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "\ngolden result: {} passed; {} failed",
+        roms.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        println!("run with --bless to accept the new frames");
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,84 @@
+use chip_8_emulator::keymap::{HostKey, Keymap, Layout};
+
+const KEYMAP: &str = r#"
+layout = "azerty"   # every ROM
+
+[keys]
+space = "5"
+F2 = "0xA"
+
+[rom.PONG]
+layout = "qwerty"
+up = "1"
+down = "4"
+"#;
+
+#[test]
+fn keymap_files_bind_keys() {
+    let keymap = Keymap::parse(KEYMAP, "brix").unwrap();
+    assert_eq!(keymap.key_for(HostKey::Char('a')), Some(0x4));
+    assert_eq!(keymap.key_for(HostKey::Space), Some(0x5));
+    assert_eq!(keymap.key_for(HostKey::F(2)), Some(0xA));
+    assert_eq!(keymap.key_for(HostKey::Up), None);
+
+    // The ROM's section adds to the shared one and picks its own layout
+    let keymap = Keymap::parse(KEYMAP, "pong").unwrap();
+    assert_eq!(keymap.key_for(HostKey::Char('a')), Some(0x7));
+    assert_eq!(keymap.key_for(HostKey::Space), Some(0x5));
+    assert_eq!(keymap.key_for(HostKey::Up), Some(0x1));
+    assert_eq!(keymap.key_for(HostKey::Down), Some(0x4));
+
+    // Key names read back as themselves
+    for (host, _) in Keymap::new(Layout::Numpad)
+        .bindings()
+        .chain(keymap.bindings())
+    {
+        assert_eq!(HostKey::parse(&host.to_string()), Ok(host));
+    }
+}
+
+#[test]
+fn azerty_top_row_works_with_and_without_shift() {
+    let keymap = Keymap::new(Layout::Azerty);
+    for (unshifted, shifted, key) in [
+        ('&', '1', 0x1),
+        ('é', '2', 0x2),
+        ('"', '3', 0x3),
+        ('\'', '4', 0xC),
+    ] {
+        assert_eq!(keymap.key_for(HostKey::Char(unshifted)), Some(key));
+        assert_eq!(keymap.key_for(HostKey::Char(shifted)), Some(key));
+    }
+    assert_eq!(keymap.key_for(HostKey::Char('w')), Some(0xA));
+
+    assert_eq!(HostKey::parse("é"), Ok(HostKey::Char('é')));
+    assert_eq!(HostKey::parse("É"), Ok(HostKey::Char('é')));
+    for (host, _) in keymap.bindings() {
+        assert_eq!(HostKey::parse(&host.to_string()), Ok(host));
+    }
+}
+
+#[test]
+fn bad_keymap_files_fail() {
+    for (text, error) in [
+        (
+            "[keys]\nspace = 5\nSPACE = 6",
+            "line 3: duplicate binding for 'space'",
+        ),
+        (
+            "[keys]\npagedown = 5",
+            "line 2: unknown host key 'pagedown'",
+        ),
+        ("[keys]\nf13 = 5", "line 2: unknown host key 'f13'"),
+        (
+            "[keys]\nspace = 10",
+            "line 2: unknown CHIP-8 key '10', expected 0-F",
+        ),
+        (
+            "[keys]\nspace = G",
+            "line 2: unknown CHIP-8 key 'G', expected 0-F",
+        ),
+    ] {
+        assert_eq!(Keymap::parse(text, "pong").unwrap_err(), error);
+    }
+}
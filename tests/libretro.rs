@@ -0,0 +1,76 @@
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::Path;
+use std::process::Command;
+
+// 0x200: 6005  V0 = 5
+// 0x202: F018  sound timer = V0
+// 0x204: D015  draw the font's 0 at (V0, V1)
+// 0x206: 1206  loop forever
+const PROGRAM: [u8; 8] = [0x60, 0x05, 0xF0, 0x18, 0xD0, 0x15, 0x12, 0x06];
+
+fn cargo_build_core(target_dir: &Path) {
+    let output = Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "rustc",
+            "--lib",
+            "--crate-type",
+            "cdylib",
+            "--features",
+            "libretro",
+        ])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "core build failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn frontend_runs_the_core() {
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&compiler).arg("--version").output().is_err() {
+        eprintln!(
+            "no C compiler ({}), skipping the libretro frontend",
+            compiler
+        );
+        return;
+    }
+
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("target/libretro");
+    cargo_build_core(&target_dir);
+
+    let frontend = target_dir.join("retro_frontend");
+    let output = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Werror", "-D_DEFAULT_SOURCE"])
+        .arg(manifest_dir.join("tests/c/retro_frontend.c"))
+        .args(["-ldl", "-o"])
+        .arg(&frontend)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "compiling retro_frontend.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let rom = target_dir.join("program.ch8");
+    std::fs::write(&rom, PROGRAM).unwrap();
+    let core = target_dir.join(format!("debug/{}chip_8_emulator{}", DLL_PREFIX, DLL_SUFFIX));
+    let output = Command::new(&frontend).arg(core).arg(rom).output().unwrap();
+    assert!(
+        output.status.success(),
+        "frontend failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The font's 0 is 14 pixels, and the buzzer sounds for the first 5
+    // frames of 735 stereo samples
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), "frames=71 lit=14 loud_samples=7350");
+}
@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use chip_8_emulator::lockstep::{self, Divergence, Lockstep, Reference, Step};
+use chip_8_emulator::{Engine, Platform};
+use common::machine_with;
+
+mod common;
+
+// 0x200: A300  I = 0x300
+// 0x202: 6001  V0 = 1
+// 0x204: C1FF  V1 = random
+// 0x206: F155  store V0-V1 at I
+// 0x208: D015  draw the font's 0 at (V0, V1)
+// 0x20A: 1204  loop
+const RANDOM_LOOP: [u8; 12] = [
+    0xA3, 0x00, 0x60, 0x01, 0xC1, 0xFF, 0xF1, 0x55, 0xD0, 0x15, 0x12, 0x04,
+];
+
+fn rom(name: &str) -> Vec<u8> {
+    std::fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("data")
+            .join(name),
+    )
+    .unwrap()
+}
+
+#[test]
+fn engines_run_in_lockstep() {
+    for name in ["BRIX", "INVADERS", "TETRIS", "BLINKY"] {
+        let program = rom(name);
+        let reference =
+            Reference::Machine(Box::new(machine_with(&program, Engine::Interpreter, 7)));
+        let mut lockstep = Lockstep::new(machine_with(&program, Engine::Recompiler, 7), reference);
+        for frame in 0..300 {
+            // Hold each of a few keys for a while so the games react
+            let key = [4, 5, 6][frame / 50 % 3];
+            lockstep.set_key(key, frame % 50 < 25).unwrap();
+            if let Err(divergence) = lockstep.run_frame() {
+                panic!("{}: {}", name, divergence);
+            }
+        }
+        assert!(lockstep.instructions() > 1000, "{} barely ran", name);
+    }
+}
+
+#[test]
+fn reports_the_first_divergence() {
+    let reference =
+        Reference::Machine(Box::new(machine_with(&RANDOM_LOOP, Engine::Interpreter, 1)));
+    let mut lockstep = Lockstep::new(
+        machine_with(&RANDOM_LOOP, Engine::Interpreter, 2),
+        reference,
+    );
+    let divergence = lockstep.run(10).unwrap_err();
+
+    assert_eq!(divergence.instruction, 2);
+    assert_eq!(divergence.frame, 0);
+    assert_eq!((divergence.pc, divergence.opcode), (0x204, 0xC1FF));
+    assert_eq!(divergence.differences.len(), 1);
+    assert_eq!(divergence.differences[0].0, "V1");
+    assert!(divergence.to_string().contains("C1FF at 0x204"));
+
+    // The state before the instruction reproduces it
+    let mut replay = machine_with(&[], Engine::Interpreter, 2);
+    replay.load_state(&divergence.state).unwrap();
+    replay.step();
+    assert_eq!(
+        format!("0x{:02X}", replay.cpu().read_register(1)),
+        divergence.differences[0].1
+    );
+}
+
+#[test]
+fn narrows_memory_differences_to_an_address() {
+    let mut tampered = machine_with(&RANDOM_LOOP, Engine::Interpreter, 1);
+    tampered.memory_mut().write_byte(0x400, 0xAA);
+    let reference = Reference::Machine(Box::new(tampered));
+    let mut lockstep = Lockstep::new(
+        machine_with(&RANDOM_LOOP, Engine::Interpreter, 1),
+        reference,
+    );
+    let divergence = lockstep.run(1).unwrap_err();
+
+    assert_eq!(divergence.instruction, 0);
+    assert_eq!(
+        divergence.differences,
+        [(
+            "memory[0x400]".to_string(),
+            "0x00".to_string(),
+            "0xAA".to_string()
+        )]
+    );
+}
+
+#[test]
+fn platforms_diverge_at_the_display_wait() {
+    let mut vip = machine_with(&RANDOM_LOOP, Engine::Interpreter, 1);
+    vip.set_platform(Platform::CosmacVip);
+    // F155 would leave I somewhere else first
+    let mut quirks = vip.quirks();
+    quirks.memory_increment = false;
+    vip.set_quirks(quirks);
+    let reference = Reference::Machine(Box::new(vip));
+    let mut lockstep = Lockstep::new(
+        machine_with(&RANDOM_LOOP, Engine::Interpreter, 1),
+        reference,
+    );
+    let divergence = lockstep.run(10).unwrap_err();
+
+    assert_eq!(divergence.pc, 0x208);
+    let what: Vec<&str> = divergence
+        .differences
+        .iter()
+        .map(|d| d.0.as_str())
+        .collect();
+    assert_eq!(what[..2], ["pc", "waiting for vblank"]);
+    assert!(what[2].starts_with("pixel"));
+}
+
+#[test]
+fn recorded_traces_round_trip_and_catch_changes() {
+    let program = rom("PONG");
+    let mut recording = machine_with(&program, Engine::Interpreter, 3);
+    let steps = lockstep::record(&mut recording, 120, |frame, chip8| {
+        chip8.set_key(1, frame >= 60).unwrap();
+    });
+    let text = lockstep::format_trace(&steps);
+    assert_eq!(lockstep::parse_trace(&text).unwrap(), steps);
+
+    let run = |steps: Vec<Step>| -> Result<u64, Divergence> {
+        let reference = Reference::Trace(steps);
+        let mut lockstep = Lockstep::new(machine_with(&program, Engine::Recompiler, 3), reference);
+        for frame in 0..120 {
+            lockstep.set_key(1, frame >= 60).unwrap();
+            lockstep.run_frame()?;
+        }
+        Ok(lockstep.instructions())
+    };
+    assert_eq!(run(steps.clone()), Ok(steps.len() as u64 - 120));
+
+    // Change one register in the middle of the trace
+    let mut changed = steps.clone();
+    let index = changed.len() / 2;
+    if let Step::Instruction(snapshot) = &mut changed[index] {
+        snapshot.registers[0xE] ^= 0x80;
+    } else {
+        panic!("expected an instruction at {}", index);
+    }
+    let vblanks = steps[..index]
+        .iter()
+        .filter(|step| **step == Step::VerticalBlank)
+        .count();
+    let divergence = run(changed).unwrap_err();
+    assert_eq!(divergence.instruction as usize, index - vblanks);
+    assert_eq!(divergence.differences[0].0, "VE");
+}
+
+#[test]
+fn bad_trace_lines_are_reported() {
+    assert_eq!(
+        lockstep::parse_trace("vblank\npc=200 v=00").unwrap_err(),
+        "line 2: expected 16 registers in hex, found '00'"
+    );
+}
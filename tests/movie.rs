@@ -0,0 +1,77 @@
+use chip_8_emulator::movie::{Movie, MovieError, MovieEvent, MovieHeader, Player, Recorder};
+use chip_8_emulator::rng::RngKind;
+use chip_8_emulator::{Chip8, Platform};
+use common::machine;
+
+mod common;
+
+// 0x200: C0FF  V0 = random
+// 0x202: E19E  skip if key V1 (0) is down
+// 0x204: 1208  jump over the count
+// 0x206: 7201  V2 += 1
+// 0x208: A300  I = 0x300
+// 0x20A: F255  store V0..V2 at I
+// 0x20C: 1200  loop
+const PROGRAM: [u8; 14] = [
+    0xC0, 0xFF, 0xE1, 0x9E, 0x12, 0x08, 0x72, 0x01, 0xA3, 0x00, 0xF2, 0x55, 0x12, 0x00,
+];
+
+// Record 200 frames, holding key 0 for some of them
+fn record() -> (Movie, Vec<u8>) {
+    let header = MovieHeader::new(&PROGRAM, Platform::CosmacVip, RngKind::CosmacVip, 42, 10);
+    let mut chip8 = machine(&PROGRAM);
+    let mut recorder = Recorder::new(header, &mut chip8);
+    for frame in 0..200 {
+        chip8
+            .set_key(0, (30..90).contains(&frame) || frame % 7 == 0)
+            .unwrap();
+        assert!(recorder.run_frame(&mut chip8));
+    }
+    (recorder.finish(), chip8.save_state())
+}
+
+fn play(movie: Movie) -> Result<Chip8, MovieError> {
+    let mut chip8 = machine(&PROGRAM);
+    let mut player = Player::new(movie, &PROGRAM, &mut chip8)?;
+    while player.run_frame(&mut chip8)? {}
+    Ok(chip8)
+}
+
+#[test]
+fn movies_play_back_what_was_recorded() {
+    let (movie, recorded) = record();
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.header.platform, Platform::CosmacVip);
+    assert_eq!(movie.events.last(), Some(&MovieEvent::End { frame: 200 }));
+
+    let chip8 = play(movie).unwrap();
+    assert_eq!(chip8.save_state(), recorded);
+}
+
+#[test]
+fn tampered_checkpoints_desync() {
+    let (mut movie, _) = record();
+    let checkpoint = movie
+        .events
+        .iter_mut()
+        .filter_map(|event| match event {
+            MovieEvent::Checkpoint { frame, hash } if *frame == 120 => Some(hash),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+    *checkpoint ^= 1;
+    assert!(matches!(
+        play(movie),
+        Err(MovieError::Desync { frame: 120, .. })
+    ));
+}
+
+#[test]
+fn long_header_text_is_cut_at_a_character() {
+    let (mut movie, _) = record();
+    // 2 bytes a character, so 255 bytes falls inside one
+    movie.header.emulator_version = "é".repeat(200);
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.header.emulator_version, "é".repeat(127));
+}
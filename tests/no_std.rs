@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chip_8_emulator::pacer::FramePacer;
+use chip_8_emulator::{Chip8, Clock, RandomSource, Trace};
+
+// A microcontroller with an FPU, as found driving small OLED panels
+const BARE_METAL_TARGET: &str = "thumbv7em-none-eabihf";
+
+// 0x200: C0FF  V0 = random
+// 0x202: 6105  V1 = 5
+// 0x204: 1204  loop forever
+const RANDOM: [u8; 6] = [0xC0, 0xFF, 0x61, 0x05, 0x12, 0x04];
+
+fn cargo_build(features: &[&str], target: Option<&str>) {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let mut command = Command::new(env!("CARGO"));
+    command
+        .current_dir(manifest_dir)
+        .args(["build", "--lib", "--no-default-features"])
+        .arg("--target-dir")
+        .arg(Path::new(manifest_dir).join("target/no_std"));
+    if !features.is_empty() {
+        command.args(["--features", &features.join(",")]);
+    }
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "no_std build with features {:?} for {:?} failed:\n{}",
+        features,
+        target,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn core_builds_without_std() {
+    // A no_std library can't reach std on the host either, so this catches
+    // most regressions even where the bare-metal target isn't installed
+    cargo_build(&[], None);
+    cargo_build(&["alloc"], None);
+}
+
+// After `rustup target add thumbv7em-none-eabihf`, run with
+// `cargo test --test no_std -- --ignored`
+#[test]
+#[ignore = "needs thumbv7em-none-eabihf"]
+fn core_builds_for_bare_metal() {
+    cargo_build(&[], Some(BARE_METAL_TARGET));
+    cargo_build(&["alloc"], Some(BARE_METAL_TARGET));
+}
+
+struct Constant(u8);
+
+impl RandomSource for Constant {
+    fn next_byte(&mut self) -> u8 {
+        self.0
+    }
+}
+
+#[test]
+fn host_supplies_random_numbers() {
+    let mut chip8 = Chip8::builder().build_with_rng(Constant(0x5A));
+    chip8.load_program(&RANDOM).unwrap();
+    chip8.run_frame();
+    assert_eq!(chip8.cpu().read_register(0), 0x5A);
+}
+
+struct Capture(Mutex<String>);
+
+impl Trace for Capture {
+    fn write(&self, args: std::fmt::Arguments) {
+        self.0.lock().unwrap().push_str(&args.to_string());
+    }
+}
+
+static CAPTURE: Capture = Capture(Mutex::new(String::new()));
+
+#[test]
+fn host_receives_trace() {
+    let mut chip8 = Chip8::builder()
+        .tracer(Some(&CAPTURE))
+        .build_with_rng(Constant(0));
+    chip8.load_program(&RANDOM).unwrap();
+    chip8.step();
+    chip8.step();
+
+    let trace = CAPTURE.0.lock().unwrap();
+    assert!(trace.contains("Fetched opcode: C0FF at 512\n"), "{}", trace);
+    assert!(trace.contains("Fetched opcode: 6105 at 514\n"), "{}", trace);
+}
+
+// A clock that only moves when slept on, or when the test moves it
+struct FakeClock {
+    now: Duration,
+    lag: Duration,
+}
+
+impl Clock for FakeClock {
+    fn now(&mut self) -> Duration {
+        self.now
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.now += duration + self.lag;
+    }
+}
+
+#[test]
+fn host_supplies_clock() {
+    let period = Duration::from_secs(1) / 60;
+    let mut pacer = FramePacer::with_clock(FakeClock {
+        now: Duration::ZERO,
+        lag: Duration::ZERO,
+    });
+    assert_eq!(pacer.wait(), 1);
+    assert_eq!(pacer.wait(), 1);
+
+    // Oversleeping by two and a half frames is caught up on
+    let mut pacer = FramePacer::with_clock(FakeClock {
+        now: Duration::ZERO,
+        lag: period * 5 / 2,
+    });
+    assert_eq!(pacer.wait(), 1);
+    assert_eq!(pacer.wait(), 3);
+}
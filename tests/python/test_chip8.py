@@ -0,0 +1,87 @@
+# Run with `maturin develop --features python && pytest`
+
+import pytest
+
+from chip8 import DISPLAY_HEIGHT, DISPLAY_WIDTH, Chip8
+
+# 0x200: 6005  V0 = 5
+# 0x202: F018  sound timer = V0
+# 0x204: D015  draw the font's 0 at (V0, V1)
+# 0x206: 1206  loop forever
+PROGRAM = bytes([0x60, 0x05, 0xF0, 0x18, 0xD0, 0x15, 0x12, 0x06])
+
+
+def machine():
+    chip8 = Chip8(seed=0)
+    chip8.load_rom(PROGRAM)
+    return chip8
+
+
+def test_step():
+    chip8 = machine()
+    assert chip8.step()
+    assert chip8.pc == 0x202
+    assert chip8.registers[0] == 5
+
+
+def test_screen_is_a_buffer():
+    chip8 = machine()
+    chip8.run_frame()
+
+    screen = memoryview(chip8.screen)
+    assert screen.shape == (DISPLAY_HEIGHT, DISPLAY_WIDTH)
+    assert screen.format == "B"
+    assert screen.readonly
+    assert screen.tolist()[0][5:9] == [1, 1, 1, 1]
+    assert screen.tolist()[1][5:9] == [1, 0, 0, 1]
+
+
+def test_screen_with_numpy():
+    np = pytest.importorskip("numpy")
+    chip8 = machine()
+    chip8.run_frame()
+
+    screen = np.asarray(chip8.screen)
+    assert screen.shape == (32, 64)
+    assert screen.dtype == np.uint8
+    assert screen.sum() == 14
+
+
+def test_views_are_live():
+    chip8 = machine()
+    memory = memoryview(chip8.memory)
+    registers = memoryview(chip8.registers)
+    assert len(memory) == 4096
+    assert memory[0x200:0x208].tobytes() == PROGRAM
+
+    assert registers[0] == 0
+    chip8.step()
+    assert registers[0] == 5
+    chip8.set_register(0xF, 0xAB)
+    assert registers[0xF] == 0xAB
+
+
+def test_rejects_bad_input():
+    chip8 = Chip8(seed=0)
+    with pytest.raises(ValueError):
+        chip8.load_rom(bytes(4096))
+    with pytest.raises(ValueError):
+        chip8.set_key(0x10, True)
+    with pytest.raises(ValueError):
+        chip8.set_register(16, 0)
+    with pytest.raises(ValueError):
+        chip8.load_state(b"\x01\x02\x03")
+    with pytest.raises(ValueError):
+        Chip8(platform="nes")
+    chip8.set_key(0xF, True)
+
+
+def test_save_states_round_trip():
+    chip8 = machine()
+    state = chip8.save_state()
+    chip8.run_frame()
+    assert chip8.save_state() != state
+
+    chip8.load_state(state)
+    assert chip8.save_state() == state
+    assert chip8.sound_timer == 0
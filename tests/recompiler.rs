@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::process::Command;
+use std::{env, fs};
+
+use chip_8_emulator::{Chip8, Engine, Rng};
+
+// Patches the operand of the add at 0x210 every time round the loop, so the
+// block holding it has to be thrown away and recompiled.
+//
+// 0x200: A211  I = 0x211
+// 0x202: 7001  V0 += 1
+// 0x204: F055  store V0 at I
+// 0x206: C3FF  V3 = random
+// 0x208: 8434  V4 += V3
+// 0x20A: 8536  V5 >>= 1
+// 0x20C: F315  delay timer = V3
+// 0x20E: F607  V6 = delay timer
+// 0x210: 7200  V2 += (patched)
+// 0x212: 1200  jump to 0x200
+const SELF_MODIFYING: [u8; 20] = [
+    0xA2, 0x11, 0x70, 0x01, 0xF0, 0x55, 0xC3, 0xFF, 0x84, 0x34, 0x85, 0x36, 0xF3, 0x15, 0xF6, 0x07,
+    0x72, 0x00, 0x12, 0x00,
+];
+
+const SCRIPT: &str = "wait 60\n\
+                      hold 5 for 20\n\
+                      wait 60\n\
+                      hold 4 for 20\n\
+                      hold 6 for 20\n\
+                      wait 60\n";
+
+// Run a ROM under a script that ends by saving state, and return the state
+fn final_state(name: &str, rom: &Path, engine: &str) -> Vec<u8> {
+    let dir = env::temp_dir();
+    let id = format!("{}-{}-{}", name, engine, std::process::id());
+    let script = dir.join(format!("chip8-recompiler-{}.txt", id));
+    let state = dir.join(format!("chip8-recompiler-{}.state", id));
+    fs::write(
+        &script,
+        format!("{}savestate {}\n", SCRIPT, state.display()),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chip_8_emulator"))
+        .args(["--no-trace", "--ipf", "100", "--engine", engine, "--script"])
+        .arg(&script)
+        .arg(rom)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{} under {}: {}",
+        name,
+        engine,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    fs::read(&state).unwrap()
+}
+
+fn assert_engines_agree(name: &str, rom: &Path) {
+    let interpreted = final_state(name, rom, "interpreter");
+    let compiled = final_state(name, rom, "recompiler");
+    assert!(
+        interpreted == compiled,
+        "{} ends in a different state under the recompiler",
+        name
+    );
+}
+
+#[test]
+fn roms_match_interpreter() {
+    let mut roms: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/data"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_none())
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty());
+
+    for rom in roms {
+        let name = rom.file_name().unwrap().to_string_lossy().into_owned();
+        assert_engines_agree(&name, &rom);
+    }
+}
+
+#[test]
+fn self_modifying_code_matches_interpreter() {
+    let rom = env::temp_dir().join(format!("chip8-recompiler-smc-{}", std::process::id()));
+    fs::write(&rom, SELF_MODIFYING).unwrap();
+    assert_engines_agree("smc", &rom);
+}
+
+// 0x200: 6101  V1 = 1
+// 0x202: 7201  V2 += 1
+// 0x204: 1200  jump to 0x200
+const COUNT_BY_ONE: [u8; 6] = [0x61, 0x01, 0x72, 0x01, 0x12, 0x00];
+// The same loop setting V1 = 3 and counting by 2
+const COUNT_BY_TWO: [u8; 6] = [0x61, 0x03, 0x72, 0x02, 0x12, 0x00];
+
+#[test]
+fn new_code_is_never_run_from_old_blocks() {
+    let run = |engine| {
+        // Five times round the loop a frame, so each frame starts on the
+        // block at 0x200
+        let mut chip8 = Chip8::builder()
+            .engine(engine)
+            .instructions_per_frame(15)
+            .rng(Rng::new(0))
+            .build();
+        chip8.load_program(&COUNT_BY_ONE).unwrap();
+        chip8.run_frame();
+        let state = chip8.save_state();
+
+        // Loading a program over the running one
+        chip8.load_program(&COUNT_BY_TWO).unwrap();
+        chip8.run_frame();
+        let reloaded = chip8.save_state();
+
+        // Loading a state holding the old program
+        chip8.load_state(&state).unwrap();
+        chip8.run_frame();
+        // A write from outside
+        chip8.memory_mut().write_byte(0x201, 0x07);
+        chip8.run_frame();
+        (reloaded, chip8.save_state())
+    };
+    let (interpreted, compiled) = (run(Engine::Interpreter), run(Engine::Recompiler));
+    assert!(interpreted.0 == compiled.0, "differs after load_program");
+    assert!(interpreted.1 == compiled.1, "differs after load_state");
+}
+
+#[test]
+fn compiled_machines_move_between_threads() {
+    let build = || {
+        let mut chip8 = Chip8::builder()
+            .engine(Engine::Recompiler)
+            .rng(Rng::new(0))
+            .build();
+        chip8.load_program(&SELF_MODIFYING).unwrap();
+        chip8
+    };
+    let mut here = build();
+    here.run_frame();
+    // With blocks compiled on this thread, finish the run on another
+    let mut there = build();
+    there.run_frame();
+    let there = std::thread::spawn(move || {
+        there.run_frame();
+        there
+    })
+    .join()
+    .unwrap();
+    here.run_frame();
+    assert!(here.save_state() == there.save_state());
+}
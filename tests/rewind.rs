@@ -0,0 +1,64 @@
+use chip_8_emulator::rewind::Rewind;
+use chip_8_emulator::{Chip8, Rng};
+
+// 0x200: C0FF  V0 = random
+// 0x202: 7101  V1 += 1
+// 0x204: A300  I = 0x300
+// 0x206: F155  store V0 and V1 at I
+// 0x208: D013  draw memory[I..I + 3] at (V0, V1)
+// 0x20A: 1200  loop
+const PROGRAM: [u8; 12] = [
+    0xC0, 0xFF, 0x71, 0x01, 0xA3, 0x00, 0xF1, 0x55, 0xD0, 0x13, 0x12, 0x00,
+];
+
+const FRAMES: usize = 100;
+
+// Run FRAMES frames, ticking `rewind` before each one; returns the machine
+// and the state it was in before every frame
+fn run(rewind: &mut Rewind) -> (Chip8, Vec<Vec<u8>>) {
+    let mut chip8 = Chip8::builder()
+        .rng(Rng::new(3))
+        .instructions_per_frame(6)
+        .build();
+    chip8.load_program(&PROGRAM).unwrap();
+    let mut states = Vec::new();
+    for _ in 0..FRAMES {
+        rewind.tick(&chip8);
+        states.push(chip8.save_state());
+        assert!(chip8.run_frame());
+    }
+    (chip8, states)
+}
+
+#[test]
+fn rewinding_restores_earlier_frames_exactly() {
+    for interval in [1, 3] {
+        let mut rewind = Rewind::new(interval, 1 << 20);
+        let (mut chip8, states) = run(&mut rewind);
+        let snapshots = FRAMES.div_ceil(interval);
+        assert_eq!(rewind.len(), snapshots);
+
+        // Newest first, every snapshot patched back from the deltas
+        for snapshot in (0..snapshots).rev() {
+            assert!(rewind.step_back(&mut chip8));
+            assert_eq!(chip8.save_state(), states[snapshot * interval]);
+        }
+        assert!(!rewind.step_back(&mut chip8));
+        assert!(rewind.is_empty());
+    }
+}
+
+#[test]
+fn rewinding_past_the_budget_drops_the_oldest_frames() {
+    let state_size = run(&mut Rewind::new(1, 0)).1[0].len();
+    let mut rewind = Rewind::new(1, state_size + 1000);
+    let (mut chip8, states) = run(&mut rewind);
+    let kept = rewind.len();
+    assert!(kept > 1 && kept < FRAMES, "kept {} frames", kept);
+
+    for frame in (FRAMES - kept..FRAMES).rev() {
+        assert!(rewind.step_back(&mut chip8));
+        assert_eq!(chip8.save_state(), states[frame]);
+    }
+    assert!(!rewind.step_back(&mut chip8));
+}
@@ -0,0 +1,89 @@
+use chip_8_emulator::rng::RandomSource;
+use chip_8_emulator::state::{StateReader, StateWriter};
+use chip_8_emulator::{Chip8, Rng, StateError};
+
+// 0x200: C0FF  V0 = random
+// 0x202: 6100  V1 = 0, padding that advances the VIP generator
+// 0x204: 1200  loop
+const RANDOM_LOOP: [u8; 6] = [0xC0, 0xFF, 0x61, 0x00, 0x12, 0x00];
+
+// The first `count` bytes CXNN draws
+fn draws(chip8: &mut Chip8, count: usize) -> Vec<u8> {
+    (0..count)
+        .map(|_| {
+            for _ in 0..3 {
+                assert!(chip8.step());
+            }
+            chip8.cpu().read_register(0)
+        })
+        .collect()
+}
+
+fn machine(rng: Rng) -> Chip8 {
+    let mut chip8 = Chip8::builder().rng(rng).build();
+    chip8.load_program(&RANDOM_LOOP).unwrap();
+    chip8
+}
+
+#[test]
+fn seeded_runs_repeat() {
+    for seed in [0, 1, 0xDEAD_BEEF] {
+        let first = draws(&mut machine(Rng::new(seed)), 64);
+        assert_eq!(draws(&mut machine(Rng::new(seed)), 64), first);
+        assert_ne!(draws(&mut machine(Rng::new(seed + 2)), 64), first);
+        // Not stuck on one value
+        assert!(first.iter().any(|&byte| byte != first[0]));
+    }
+}
+
+#[test]
+fn vip_runs_repeat() {
+    let first = draws(&mut machine(Rng::cosmac_vip(0x1234)), 64);
+    assert_eq!(draws(&mut machine(Rng::cosmac_vip(0x1234)), 64), first);
+    assert_ne!(draws(&mut machine(Rng::cosmac_vip(0x1235)), 64), first);
+
+    // R9.1 += R9.0 on every draw, and R9 counts instruction fetches
+    let mut rng = Rng::cosmac_vip(0x0102);
+    assert_eq!(rng.next_byte(), 0x03);
+    rng.on_fetch();
+    assert_eq!(rng.next_byte(), 0x06);
+    for _ in 0..0xFD {
+        rng.on_fetch();
+    }
+    // R9 = 0x0700, the count carrying into the high byte
+    assert_eq!(rng.next_byte(), 0x07);
+    rng.on_fetch();
+    assert_eq!(rng.next_byte(), 0x08);
+}
+
+#[test]
+fn generators_continue_from_save_states() {
+    for rng in [Rng::new(9), Rng::cosmac_vip(9)] {
+        let mut chip8 = machine(rng);
+        draws(&mut chip8, 10);
+        let state = chip8.save_state();
+        let expected = draws(&mut chip8, 20);
+
+        let mut restored = machine(Rng::new(0));
+        restored.load_state(&state).unwrap();
+        assert_eq!(draws(&mut restored, 20), expected);
+    }
+}
+
+#[test]
+fn zero_xorshift_state_fails_to_load() {
+    for (kind, valid) in [(0, false), (1, true)] {
+        let mut writer = StateWriter::new();
+        writer.write_u8(kind);
+        writer.write_bytes(&0u64.to_le_bytes());
+        let state = writer.finish();
+        let result = Rng::from_state(&mut StateReader::open(&state).unwrap());
+        assert_eq!(result.is_ok(), valid);
+        if !valid {
+            assert_eq!(
+                result.unwrap_err(),
+                StateError::Invalid("xorshift state is zero")
+            );
+        }
+    }
+}
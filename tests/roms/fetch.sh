@@ -0,0 +1,13 @@
+#!/bin/sh
+# Download Timendus' CHIP-8 test suite, pinned to a release, into this
+# directory and write the golden images for it. Check the images by eye
+# before committing them with the ROMs and the licence.
+set -e
+cd "$(dirname "$0")"
+release=https://raw.githubusercontent.com/Timendus/chip8-test-suite/v4.1
+curl -fsSL -o LICENSE "$release/LICENSE"
+for rom in 1-chip8-logo 2-ibm-logo 3-corax+ 4-flags 5-quirks 6-keypad 7-beep; do
+    curl -fsSL -o "$rom.ch8" "$release/bin/$rom.ch8"
+done
+cd ../..
+CHIP8_BLESS=1 cargo test --test conformance -- --include-ignored
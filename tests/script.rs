@@ -0,0 +1,110 @@
+use std::process::{Command, Output};
+use std::{env, fs};
+
+// 0x200: 6505  V5 = 5
+// 0x202: A300  I = 0x300
+// 0x204: E59E  skip if key V5 is down
+// 0x206: 1204  jump back to 0x204
+// 0x208: 6007  V0 = 7
+// 0x20A: F055  store V0 at I
+// 0x20C: A212  I = sprite
+// 0x20E: D111  draw 1 row at (V1, V1) = (0, 0)
+// 0x210: 1210  loop forever
+// 0x212: 80    sprite: leftmost pixel
+const PROGRAM: [u8; 19] = [
+    0x65, 0x05, 0xA3, 0x00, 0xE5, 0x9E, 0x12, 0x04, 0x60, 0x07, 0xF0, 0x55, 0xA2, 0x12, 0xD1, 0x11,
+    0x12, 0x10, 0x80,
+];
+
+// 0x200: D001  draw
+// 0x202: 7101  V1 += 1
+// 0x204: 1200  loop
+const DRAW_LOOP: [u8; 6] = [0xD0, 0x01, 0x71, 0x01, 0x12, 0x00];
+
+fn run_script(name: &str, script: &str) -> Output {
+    run_program(name, &PROGRAM, &[], script)
+}
+
+fn run_program(name: &str, program: &[u8], args: &[&str], script: &str) -> Output {
+    let dir = env::temp_dir();
+    let rom = dir.join(format!("chip8-script-{}-{}", name, std::process::id()));
+    let path = dir.join(format!("chip8-script-{}-{}.txt", name, std::process::id()));
+    fs::write(&rom, program).unwrap();
+    fs::write(&path, script).unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_chip_8_emulator"))
+        .args(args)
+        .arg("--script")
+        .arg(&path)
+        .arg(&rom)
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn passing_script() {
+    let screenshot = env::temp_dir().join(format!("chip8-script-{}.png", std::process::id()));
+    let script = format!(
+        "# wait for key 5, then check the stored value and the sprite\n\
+         wait 10\n\
+         assert V0 == 0 && pixel(0, 0) == 0\n\
+         press 5\n\
+         wait-until mem[0x300] == 7 within 5\n\
+         release all\n\
+         wait 1\n\
+         assert pixel(0,0)==1 && V0 == 7\n\
+         hold 5 for 3\n\
+         screenshot {}\n",
+        screenshot.display()
+    );
+    let output = run_script("pass", &script);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let png = fs::read(&screenshot).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    fs::remove_file(screenshot).unwrap();
+}
+
+#[test]
+fn failed_assertion() {
+    let output = run_script("assert", "wait 2\nassert mem[0x300] == 7\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("line 2: assertion failed at frame 2"));
+}
+
+#[test]
+fn wait_until_times_out() {
+    let output = run_script("timeout", "wait-until V0 == 7 within 30\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("line 1: timed out after 30 frames"));
+}
+
+#[test]
+fn invalid_script() {
+    let output = run_script("invalid", "wait 1\n\nhold 5 30\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("line 3: usage: hold <0-F> for <frames>"));
+}
+
+#[test]
+fn display_wait() {
+    // Without the quirk a frame runs 16 instructions, 5 of them draws
+    let output = run_program("no-wait", &DRAW_LOOP, &[], "wait 10\nassert V1 == 53\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // The VIP draws once per frame; the first draw waits for a vertical blank
+    let script = "wait 10\nassert V1 == 9\n";
+    let output = run_program("vip-wait", &DRAW_LOOP, &["--platform", "vip"], script);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = run_program(
+        "vip-timing-wait",
+        &DRAW_LOOP,
+        &["--platform", "vip", "--vip-timing"],
+        script,
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+}
@@ -0,0 +1,49 @@
+use chip_8_emulator::decode::Instruction;
+use chip_8_emulator::timing::vip_cycles;
+use chip_8_emulator::{Chip8, Rng, Timing};
+
+#[test]
+fn vip_timing_charges_skips_that_are_taken() {
+    // A frame leaves 3668 - 1070 = 2598 machine cycles, and every instruction
+    // costs 40 to fetch
+    let instructions_in_a_frame = |program: &[u8]| {
+        let mut chip8 = Chip8::builder()
+            .timing(Timing::CosmacVip)
+            .rng(Rng::new(0))
+            .build();
+        chip8.load_program(program).unwrap();
+        assert!(chip8.run_frame());
+        chip8.instructions_run()
+    };
+
+    // 1204 1208 ... 12FC: jumps over every other opcode, 52 cycles each
+    let mut jumps = vec![0; 0x100];
+    for address in (0..0x100).step_by(4) {
+        let target = 0x200 + (address + 4) % 0x100;
+        jumps[address..address + 2].copy_from_slice(&(0x1000 | target as u16).to_be_bytes());
+    }
+    assert_eq!(instructions_in_a_frame(&jumps), 50);
+    // 3000 1200: a taken skip costs 54, then 1200 costs 52
+    assert_eq!(
+        instructions_in_a_frame(&[0x30, 0x00, 0x00, 0x00, 0x12, 0x00]),
+        49
+    );
+    // 3001 1200: a skip not taken costs 50
+    assert_eq!(instructions_in_a_frame(&[0x30, 0x01, 0x12, 0x00]), 51);
+}
+
+#[test]
+fn vip_timing_charges_page_crossings_from_the_register_jumped_with() {
+    // B2F0 crosses into the next page with V0 = 0x20, and not with V2 = 0x01
+    let jump = Instruction::JumpOffset(0x2F0);
+    let mut chip8 = Chip8::builder().rng(Rng::new(0)).build();
+    chip8.cpu_mut().write_register(0, 0x20);
+    chip8.cpu_mut().write_register(2, 0x01);
+    assert_eq!(vip_cycles(jump, chip8.cpu()), 40 + 24);
+
+    // With the jump quirk it jumps with V2
+    let mut quirks = chip8.quirks();
+    quirks.jump_with_vx = true;
+    chip8.set_quirks(quirks);
+    assert_eq!(vip_cycles(jump, chip8.cpu()), 40 + 22);
+}
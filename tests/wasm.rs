@@ -0,0 +1,56 @@
+//! Run with `wasm-pack test --node -- --features wasm`
+
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use chip_8_emulator::wasm::WasmChip8;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// 0x200: 6005  V0 = 5
+// 0x202: F018  sound timer = V0
+// 0x204: D015  draw the font's 0 at (V0, V1)
+// 0x206: 1206  loop forever
+const PROGRAM: [u8; 8] = [0x60, 0x05, 0xF0, 0x18, 0xD0, 0x15, 0x12, 0x06];
+
+fn framebuffer(chip8: &WasmChip8) -> &[u8] {
+    // Where JavaScript would build a Uint8Array over wasm memory
+    unsafe { std::slice::from_raw_parts(chip8.framebuffer_ptr(), chip8.framebuffer_len()) }
+}
+
+#[wasm_bindgen_test]
+fn runs_a_frame() {
+    let mut chip8 = WasmChip8::new(0);
+    chip8.load_rom(&PROGRAM).unwrap();
+    assert!(chip8.run_frame());
+
+    let width = chip8.width();
+    let pixels = framebuffer(&chip8);
+    assert_eq!(pixels.len(), width * chip8.height());
+    assert_eq!(pixels[5..9], [1, 1, 1, 1]);
+    assert_eq!(pixels[width + 5..width + 9], [1, 0, 0, 1]);
+
+    assert!(chip8.sound_active());
+    assert_eq!(chip8.sound_timer(), 4);
+}
+
+#[wasm_bindgen_test]
+fn rejects_bad_input() {
+    let mut chip8 = WasmChip8::new(0);
+    assert!(chip8.load_rom(&[0; 4096]).is_err());
+    assert!(chip8.set_key(0x10, true).is_err());
+    assert!(chip8.set_platform("nes").is_err());
+    assert!(chip8.load_state(&[1, 2, 3]).is_err());
+    assert!(chip8.set_key(0xF, true).is_ok());
+}
+
+#[wasm_bindgen_test]
+fn save_states_round_trip() {
+    let mut chip8 = WasmChip8::new(0);
+    chip8.load_rom(&PROGRAM).unwrap();
+    let state = chip8.save_state();
+    chip8.run_frame();
+    assert_ne!(chip8.save_state(), state);
+
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.save_state(), state);
+    assert!(!chip8.sound_active());
+}